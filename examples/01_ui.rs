@@ -21,17 +21,15 @@ impl gimslib_rs::App for App {
     }
 
     fn draw(&mut self, res: &FrameResources) -> Result<(), Box<dyn std::error::Error>> {
-        unsafe {
-            res.command_list.ClearRenderTargetView(
-                res.render_target_handle_srgb,
-                &self.clear_color,
-                None,
-            )
-        };
+        res.clear_srgb(self.clear_color);
         Ok(())
     }
 }
 
 fn main() {
-    gimslib_rs::run_app(AppConfig::default(), |_| App::default()).unwrap();
+    let app_config = AppConfig {
+        settings_panel: true,
+        ..Default::default()
+    };
+    gimslib_rs::run_app(app_config, |_| App::default()).unwrap();
 }
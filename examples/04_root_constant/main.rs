@@ -1,9 +1,12 @@
-use std::{mem::ManuallyDrop, sync::Arc};
+use std::sync::Arc;
 
-use gimslib_rs::{AppConfig, FrameResources, gpulib::GPULib};
-use windows::Win32::Graphics::{
-    Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST, Direct3D12::*, Dxgi::Common::*,
+use gimslib_rs::{
+    AppConfig, FrameResources,
+    gpulib::{GPULib, compile_shader},
+    pipeline::GraphicsPipelineBuilder,
+    root_signature::set_graphics_root_32bit_constants,
 };
+use windows::Win32::Graphics::{Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST, Direct3D12::*};
 
 struct App {
     root_signature: ID3D12RootSignature,
@@ -13,15 +16,15 @@ struct App {
 }
 
 impl App {
-    fn new(lib: Arc<GPULib>) -> Self {
-        let root_signature = create_root_signature(&lib).unwrap();
-        let pipeline = create_pipeline(&lib, root_signature.clone()).unwrap();
-        App {
+    fn new(lib: Arc<GPULib>) -> Result<Self, Box<dyn std::error::Error>> {
+        let root_signature = create_root_signature(&lib)?;
+        let pipeline = create_pipeline(&lib, &root_signature)?;
+        Ok(App {
             root_signature,
             pipeline,
             clear_color: [0.0, 0.0, 0.0, 1.0],
             scale: 1.0,
-        }
+        })
     }
 }
 
@@ -67,62 +70,14 @@ fn create_root_signature(lib: &GPULib) -> Result<ID3D12RootSignature, Box<dyn st
 
 fn create_pipeline(
     lib: &GPULib,
-    root_signature: ID3D12RootSignature,
+    root_signature: &ID3D12RootSignature,
 ) -> Result<ID3D12PipelineState, Box<dyn std::error::Error>> {
-    let mut vertex_shader = hassle_rs::compile_hlsl(
-        "shader.hlsl",
-        include_str!("shader.hlsl"),
-        "VS_main",
-        "vs_6_5",
-        &[],
-        &[],
-    )?;
-    if !hassle_rs::fake_sign_dxil_in_place(&mut vertex_shader) {
-        return Err("Failed to sign vertex shader".into());
-    }
-    let mut pixel_shader = hassle_rs::compile_hlsl(
-        "shader.hlsl",
-        include_str!("shader.hlsl"),
-        "PS_main",
-        "ps_6_5",
-        &[],
-        &[],
-    )?;
-    if !hassle_rs::fake_sign_dxil_in_place(&mut pixel_shader) {
-        return Err("Failed to sign pixel shader".into());
-    }
-
-    let mut pipeline_desc = D3D12_GRAPHICS_PIPELINE_STATE_DESC::default();
-    pipeline_desc.pRootSignature = ManuallyDrop::new(Some(root_signature));
-    pipeline_desc.VS = D3D12_SHADER_BYTECODE {
-        pShaderBytecode: vertex_shader.as_ptr() as _,
-        BytecodeLength: vertex_shader.len(),
-    };
-    pipeline_desc.PS = D3D12_SHADER_BYTECODE {
-        pShaderBytecode: pixel_shader.as_ptr() as _,
-        BytecodeLength: pixel_shader.len(),
-    };
-    pipeline_desc.RasterizerState = D3D12_RASTERIZER_DESC {
-        FillMode: D3D12_FILL_MODE_SOLID,
-        CullMode: D3D12_CULL_MODE_NONE,
-        FrontCounterClockwise: false.into(),
-        DepthBias: D3D12_DEFAULT_DEPTH_BIAS,
-        DepthBiasClamp: D3D12_DEFAULT_DEPTH_BIAS_CLAMP,
-        SlopeScaledDepthBias: D3D12_DEFAULT_SLOPE_SCALED_DEPTH_BIAS,
-        DepthClipEnable: true.into(),
-        MultisampleEnable: false.into(),
-        AntialiasedLineEnable: false.into(),
-        ForcedSampleCount: 0,
-        ConservativeRaster: D3D12_CONSERVATIVE_RASTERIZATION_MODE_OFF,
-    };
-    pipeline_desc.BlendState.RenderTarget[0].RenderTargetWriteMask = 0b1111;
-    pipeline_desc.SampleMask = u32::MAX;
-    pipeline_desc.PrimitiveTopologyType = D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE;
-    pipeline_desc.NumRenderTargets = 1;
-    pipeline_desc.SampleDesc.Count = 1;
-    pipeline_desc.RTVFormats[0] = DXGI_FORMAT_R8G8B8A8_UNORM;
+    let vertex_shader = compile_shader(include_str!("shader.hlsl"), "VS_main", "vs_6_5")?;
+    let pixel_shader = compile_shader(include_str!("shader.hlsl"), "PS_main", "ps_6_5")?;
 
-    let pipeline = unsafe { lib.device.CreateGraphicsPipelineState(&pipeline_desc)? };
+    let pipeline = GraphicsPipelineBuilder::new(root_signature, &vertex_shader)
+        .pixel_shader(&pixel_shader)
+        .build(lib)?;
 
     Ok(pipeline)
 }
@@ -150,7 +105,7 @@ impl gimslib_rs::App for App {
             command_list.SetGraphicsRootSignature(&self.root_signature);
             command_list.SetPipelineState(&self.pipeline);
             command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
-            command_list.SetGraphicsRoot32BitConstant(0, std::mem::transmute(self.scale), 0);
+            set_graphics_root_32bit_constants(command_list, 0, &self.scale, 0);
             command_list.DrawInstanced(3, 1, 0, 0);
         }
         Ok(())
@@ -158,5 +113,5 @@ impl gimslib_rs::App for App {
 }
 
 fn main() {
-    gimslib_rs::run_app(AppConfig::default(), App::new).unwrap();
+    gimslib_rs::run_app_fallible(AppConfig::default(), App::new).unwrap();
 }
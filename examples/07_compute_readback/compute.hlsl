@@ -0,0 +1,7 @@
+RWStructuredBuffer<uint> output : register(u0);
+
+[numthreads(64, 1, 1)]
+void CS_main(uint3 thread_id : SV_DispatchThreadID)
+{
+    output[thread_id.x] = thread_id.x * thread_id.x;
+}
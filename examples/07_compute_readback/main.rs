@@ -0,0 +1,192 @@
+use gimslib_rs::{
+    barrier,
+    gpulib::{GPULib, compile_shader},
+    pipeline::create_compute_pipeline,
+};
+use windows::{
+    Win32::{
+        Foundation::HANDLE,
+        Graphics::{Direct3D12::*, Dxgi::Common::*},
+    },
+    core::Interface,
+};
+
+/// Number of values computed on the GPU, a multiple of the thread group size in the shader
+const ELEMENT_COUNT: u32 = 64;
+
+fn create_root_signature(lib: &GPULib) -> Result<ID3D12RootSignature, Box<dyn std::error::Error>> {
+    // Root parameter: UAV of the output buffer
+    let parameter = D3D12_ROOT_PARAMETER {
+        ParameterType: D3D12_ROOT_PARAMETER_TYPE_UAV,
+        ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
+        Anonymous: D3D12_ROOT_PARAMETER_0 {
+            Descriptor: D3D12_ROOT_DESCRIPTOR {
+                ShaderRegister: 0,
+                RegisterSpace: 0,
+            },
+        },
+    };
+
+    let mut root_blob_option = None;
+    unsafe {
+        D3D12SerializeRootSignature(
+            &D3D12_ROOT_SIGNATURE_DESC {
+                NumParameters: 1,
+                pParameters: &parameter,
+                ..Default::default()
+            },
+            D3D_ROOT_SIGNATURE_VERSION_1,
+            &mut root_blob_option,
+            None,
+        )
+    }?;
+
+    let root_blob = root_blob_option.ok_or("Failed to create root signature")?;
+    let blob_data = unsafe {
+        std::slice::from_raw_parts(
+            root_blob.GetBufferPointer() as *const u8,
+            root_blob.GetBufferSize(),
+        )
+    };
+
+    let root_signature = unsafe { lib.device.CreateRootSignature(0, blob_data) }?;
+
+    Ok(root_signature)
+}
+
+fn create_buffer(
+    lib: &GPULib,
+    heap_type: D3D12_HEAP_TYPE,
+    size: u64,
+    flags: D3D12_RESOURCE_FLAGS,
+    initial_state: D3D12_RESOURCE_STATES,
+) -> Result<ID3D12Resource, Box<dyn std::error::Error>> {
+    let mut resource = None;
+    unsafe {
+        lib.device.CreateCommittedResource(
+            &D3D12_HEAP_PROPERTIES {
+                Type: heap_type,
+                ..Default::default()
+            },
+            D3D12_HEAP_FLAG_NONE,
+            &D3D12_RESOURCE_DESC {
+                Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+                Width: size,
+                Height: 1,
+                DepthOrArraySize: 1,
+                MipLevels: 1,
+                Format: DXGI_FORMAT_UNKNOWN,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+                Flags: flags,
+                ..Default::default()
+            },
+            initial_state,
+            None,
+            &mut resource,
+        )
+    }?;
+
+    let resource = resource.ok_or("Failed to create buffer")?;
+
+    Ok(resource)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // No window is needed for compute work
+    let lib = GPULib::new()?;
+
+    let root_signature = create_root_signature(&lib)?;
+    let compute_shader = compile_shader(include_str!("compute.hlsl"), "CS_main", "cs_6_5")?;
+    let pipeline = create_compute_pipeline(&lib, &root_signature, &compute_shader)?;
+
+    // The shader writes into a buffer in GPU memory, which is then copied into CPU readable memory
+    let size = ELEMENT_COUNT as u64 * size_of::<u32>() as u64;
+    let output_buffer = create_buffer(
+        &lib,
+        D3D12_HEAP_TYPE_DEFAULT,
+        size,
+        D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
+        D3D12_RESOURCE_STATE_COMMON,
+    )?;
+    let readback_buffer = create_buffer(
+        &lib,
+        D3D12_HEAP_TYPE_READBACK,
+        size,
+        D3D12_RESOURCE_FLAG_NONE,
+        D3D12_RESOURCE_STATE_COPY_DEST,
+    )?;
+
+    let command_allocator: ID3D12CommandAllocator = unsafe {
+        lib.device
+            .CreateCommandAllocator(D3D12_COMMAND_LIST_TYPE_DIRECT)
+    }?;
+    let command_list: ID3D12GraphicsCommandList = unsafe {
+        lib.device.CreateCommandList(
+            0,
+            D3D12_COMMAND_LIST_TYPE_DIRECT,
+            &command_allocator,
+            &pipeline,
+        )
+    }?;
+
+    unsafe {
+        command_list.SetComputeRootSignature(&root_signature);
+        command_list.SetComputeRootUnorderedAccessView(0, output_buffer.GetGPUVirtualAddress());
+    }
+    unsafe {
+        barrier::record(
+            &command_list,
+            &[barrier::transition(
+                &output_buffer,
+                D3D12_RESOURCE_STATE_COMMON,
+                D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+            )],
+        );
+        command_list.Dispatch(ELEMENT_COUNT / 64, 1, 1);
+
+        // Leaving the UNORDERED_ACCESS state waits for the shader writes, so no UAV barrier is needed before copying
+        barrier::record(
+            &command_list,
+            &[barrier::transition(
+                &output_buffer,
+                D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+                D3D12_RESOURCE_STATE_COPY_SOURCE,
+            )],
+        );
+        command_list.CopyResource(&readback_buffer, &output_buffer);
+        command_list.Close()?;
+        lib.queue.ExecuteCommandLists(&[Some(command_list.cast()?)]);
+    }
+
+    // Without an event handle, SetEventOnCompletion blocks until the fence is signaled
+    let fence: ID3D12Fence = unsafe { lib.device.CreateFence(0, D3D12_FENCE_FLAG_NONE) }?;
+    unsafe {
+        lib.queue.Signal(&fence, 1)?;
+        fence.SetEventOnCompletion(1, HANDLE::default())?;
+    }
+
+    let results = unsafe {
+        let mut pointer = std::ptr::null_mut();
+        readback_buffer.Map(
+            0,
+            Some(&D3D12_RANGE {
+                Begin: 0,
+                End: size as usize,
+            }),
+            Some(&mut pointer),
+        )?;
+        let results =
+            std::slice::from_raw_parts(pointer as *const u32, ELEMENT_COUNT as usize).to_vec();
+        // Nothing was written by the CPU
+        readback_buffer.Unmap(0, Some(&D3D12_RANGE::default()));
+        results
+    };
+
+    println!("Squares computed on the GPU: {:?}", results);
+
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use gimslib_rs::{
+    AppConfig, FrameResources,
+    gpulib::{GPULib, compile_shader},
+    pipeline::{DepthPrepass, DepthRange, GraphicsPipelineBuilder},
+    root_signature::{create_root_signature, set_graphics_root_32bit_constants},
+};
+use windows::Win32::Graphics::{
+    Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST, Direct3D12::*, Dxgi::Common::*,
+};
+
+const DEPTH_FORMAT: DXGI_FORMAT = DXGI_FORMAT_D32_FLOAT;
+
+struct App {
+    lib: Arc<GPULib>,
+    root_signature: ID3D12RootSignature,
+    /// Pipeline with a regular `LESS` depth test for drawing without prepass
+    pipeline: ID3D12PipelineState,
+    prepass: DepthPrepass,
+    /// Created in the first frame, when the size of the render target is known
+    depth_buffer: Option<ID3D12Resource>,
+    use_prepass: bool,
+    layer_count: u32,
+}
+
+impl App {
+    fn new(lib: Arc<GPULib>) -> Result<Self, Box<dyn std::error::Error>> {
+        // Root parameter: number of layers
+        let parameter = D3D12_ROOT_PARAMETER1 {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS,
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_VERTEX,
+            Anonymous: D3D12_ROOT_PARAMETER1_0 {
+                Constants: D3D12_ROOT_CONSTANTS {
+                    ShaderRegister: 0,
+                    RegisterSpace: 0,
+                    Num32BitValues: 1,
+                },
+            },
+        };
+        let root_signature = create_root_signature(
+            &lib,
+            &D3D12_ROOT_SIGNATURE_DESC1 {
+                NumParameters: 1,
+                pParameters: &parameter,
+                ..Default::default()
+            },
+        )?;
+
+        let vertex_shader = compile_shader(include_str!("shader.hlsl"), "VS_main", "vs_6_5")?;
+        let pixel_shader = compile_shader(include_str!("shader.hlsl"), "PS_main", "ps_6_5")?;
+        let builder = GraphicsPipelineBuilder::new(&root_signature, &vertex_shader)
+            .pixel_shader(&pixel_shader);
+
+        let pipeline = builder
+            .clone()
+            .depth_stencil(
+                DEPTH_FORMAT,
+                D3D12_DEPTH_STENCIL_DESC {
+                    DepthEnable: true.into(),
+                    DepthWriteMask: D3D12_DEPTH_WRITE_MASK_ALL,
+                    DepthFunc: D3D12_COMPARISON_FUNC_LESS,
+                    ..Default::default()
+                },
+            )
+            .build(&lib)?;
+        let prepass = DepthPrepass::new(&lib, &builder, DEPTH_FORMAT, DepthRange::Standard)?;
+
+        Ok(App {
+            lib,
+            root_signature,
+            pipeline,
+            prepass,
+            depth_buffer: None,
+            use_prepass: true,
+            layer_count: 16,
+        })
+    }
+}
+
+fn create_depth_buffer(
+    lib: &GPULib,
+    width: u64,
+    height: u32,
+) -> Result<ID3D12Resource, Box<dyn std::error::Error>> {
+    let mut resource = None;
+    unsafe {
+        lib.device.CreateCommittedResource(
+            &D3D12_HEAP_PROPERTIES {
+                Type: D3D12_HEAP_TYPE_DEFAULT,
+                ..Default::default()
+            },
+            D3D12_HEAP_FLAG_NONE,
+            &D3D12_RESOURCE_DESC {
+                Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+                Width: width,
+                Height: height,
+                DepthOrArraySize: 1,
+                MipLevels: 1,
+                Format: DEPTH_FORMAT,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Flags: D3D12_RESOURCE_FLAG_ALLOW_DEPTH_STENCIL,
+                ..Default::default()
+            },
+            D3D12_RESOURCE_STATE_DEPTH_WRITE,
+            Some(&D3D12_CLEAR_VALUE {
+                Format: DEPTH_FORMAT,
+                Anonymous: D3D12_CLEAR_VALUE_0 {
+                    DepthStencil: D3D12_DEPTH_STENCIL_VALUE {
+                        Depth: 1.0,
+                        Stencil: 0,
+                    },
+                },
+            }),
+            &mut resource,
+        )
+    }?;
+
+    Ok(resource.ok_or("Failed to create depth buffer")?)
+}
+
+impl gimslib_rs::App for App {
+    fn record_ui(&mut self, ctx: &egui::Context) {
+        let frame_time = ctx.input(|input| input.stable_dt);
+        egui::Window::new("Depth prepass").show(ctx, |ui| {
+            ui.checkbox(&mut self.use_prepass, "Use depth prepass");
+            ui.add(egui::Slider::new(&mut self.layer_count, 1..=64).text("Layers"));
+            ui.label(format!("Frame time: {:.2} ms", frame_time * 1000.0));
+        });
+        // Keep drawing, so the frame time stays meaningful
+        ctx.request_repaint();
+    }
+
+    fn draw(&mut self, res: &FrameResources) -> Result<(), Box<dyn std::error::Error>> {
+        let depth_buffer = match &self.depth_buffer {
+            Some(depth_buffer) => depth_buffer,
+            None => self.depth_buffer.insert(create_depth_buffer(
+                &self.lib,
+                res.viewport.Width as u64,
+                res.viewport.Height as u32,
+            )?),
+        };
+        let depth_stencil_handle = *res
+            .depth_stencil_handles
+            .first()
+            .ok_or("No depth stencil view reserved in the app config")?;
+
+        let command_list = res.command_list;
+        unsafe {
+            self.lib.device.CreateDepthStencilView(
+                depth_buffer,
+                Some(&D3D12_DEPTH_STENCIL_VIEW_DESC {
+                    Format: DEPTH_FORMAT,
+                    ViewDimension: D3D12_DSV_DIMENSION_TEXTURE2D,
+                    Flags: D3D12_DSV_FLAG_NONE,
+                    Anonymous: D3D12_DEPTH_STENCIL_VIEW_DESC_0 {
+                        Texture2D: D3D12_TEX2D_DSV { MipSlice: 0 },
+                    },
+                }),
+                depth_stencil_handle,
+            );
+
+            command_list.ClearRenderTargetView(
+                res.render_target_handle_srgb,
+                &[0.0, 0.0, 0.0, 1.0],
+                None,
+            );
+            command_list.ClearDepthStencilView(
+                depth_stencil_handle,
+                D3D12_CLEAR_FLAG_DEPTH,
+                1.0,
+                0,
+                &[],
+            );
+            command_list.RSSetViewports(&[res.viewport]);
+            command_list.RSSetScissorRects(&[res.scissor]);
+            command_list.SetGraphicsRootSignature(&self.root_signature);
+            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+        }
+        set_graphics_root_32bit_constants(command_list, 0, &self.layer_count, 0);
+
+        let layer_count = self.layer_count;
+        let draw_layers = |command_list: &ID3D12GraphicsCommandList| unsafe {
+            command_list.DrawInstanced(6, layer_count, 0, 0)
+        };
+        if self.use_prepass {
+            self.prepass.record(
+                command_list,
+                &[res.render_target_handle],
+                depth_stencil_handle,
+                draw_layers,
+            );
+        } else {
+            unsafe {
+                command_list.OMSetRenderTargets(
+                    1,
+                    Some(&res.render_target_handle),
+                    false,
+                    Some(&depth_stencil_handle),
+                );
+                command_list.SetPipelineState(&self.pipeline);
+            }
+            draw_layers(command_list);
+        }
+
+        Ok(())
+    }
+}
+
+fn main() {
+    let app_config = AppConfig {
+        window_title: "Depth prepass".to_string(),
+        depth_stencil_views: 1,
+        ..Default::default()
+    };
+    gimslib_rs::run_app_fallible(app_config, App::new).unwrap();
+}
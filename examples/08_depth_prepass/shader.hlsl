@@ -0,0 +1,44 @@
+struct RootConstants
+{
+  uint layer_count;
+};
+
+ConstantBuffer<RootConstants> rootConstants : register(b0);
+
+struct VertexShaderOutput
+{
+  float4 position : SV_POSITION;
+  float2 uv : TEXCOORD;
+  nointerpolation uint layer : LAYER;
+};
+
+static const float2 corners[] = {{-1.0f, -1.0f}, {-1.0f, 1.0f}, {1.0f, 1.0f}, {-1.0f, -1.0f}, {1.0f, 1.0f}, {1.0f, -1.0f}};
+
+// Every instance is a square layer. Layers are drawn back to front and get smaller towards the viewer,
+// so without a depth prepass every layer shades the pixels it covers.
+VertexShaderOutput VS_main(uint vertex_id : SV_VertexID, uint layer : SV_InstanceID)
+{
+  float t = (layer + 1.0f) / (rootConstants.layer_count + 1.0f);
+  float2 corner = corners[vertex_id];
+
+  VertexShaderOutput output;
+  output.position = float4(corner * (1.0f - 0.9f * t), 1.0f - t, 1.0f);
+  output.uv = corner * 0.5f + 0.5f;
+  output.layer = layer;
+  return output;
+}
+
+// Deliberately expensive, so the saved shading work shows up in the frame time
+float4 PS_main(VertexShaderOutput input) : SV_TARGET
+{
+  float2 p = input.uv * 8.0f;
+  float value = 0.0f;
+  for (int i = 0; i < 256; i++)
+  {
+    p = float2(sin(p.y * 1.1f + value), cos(p.x * 0.9f - value));
+    value += 0.01f * (p.x + p.y);
+  }
+  float hue = frac(input.layer * 0.13f);
+  float3 color = saturate(abs(frac(hue + float3(0.0f, 2.0f / 3.0f, 1.0f / 3.0f)) * 6.0f - 3.0f) - 1.0f);
+  return float4(color * (0.75f + 0.25f * sin(value)), 1.0f);
+}
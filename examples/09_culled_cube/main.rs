@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use gimslib_rs::{
+    AppConfig, FrameResources,
+    gpulib::{GPULib, compile_shader},
+    pipeline::GraphicsPipelineBuilder,
+    root_signature::{
+        create_root_signature, root_constant_count, set_graphics_root_32bit_constants,
+    },
+};
+use windows::Win32::Graphics::{Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST, Direct3D12::*};
+
+/// Column major model view projection matrix
+type Constants = [f32; 16];
+
+struct App {
+    lib: Arc<GPULib>,
+    root_signature: ID3D12RootSignature,
+    vertex_shader: Vec<u8>,
+    pixel_shader: Vec<u8>,
+    pipeline: ID3D12PipelineState,
+    /// Replaced pipelines, which frames in flight may still use. Kept until the app is dropped,
+    /// since they are only created when the settings change.
+    retired_pipelines: Vec<ID3D12PipelineState>,
+    cull_mode: D3D12_CULL_MODE,
+    front_counter_clockwise: bool,
+    /// Whether the settings changed and the pipeline has to be rebuilt
+    pipeline_outdated: bool,
+}
+
+impl App {
+    fn new(lib: Arc<GPULib>) -> Result<Self, Box<dyn std::error::Error>> {
+        let parameter = D3D12_ROOT_PARAMETER1 {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS,
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_VERTEX,
+            Anonymous: D3D12_ROOT_PARAMETER1_0 {
+                Constants: D3D12_ROOT_CONSTANTS {
+                    ShaderRegister: 0,
+                    RegisterSpace: 0,
+                    Num32BitValues: root_constant_count::<Constants>(),
+                },
+            },
+        };
+        let root_signature = create_root_signature(
+            &lib,
+            &D3D12_ROOT_SIGNATURE_DESC1 {
+                NumParameters: 1,
+                pParameters: &parameter,
+                ..Default::default()
+            },
+        )?;
+        let vertex_shader = compile_shader(include_str!("shader.hlsl"), "VS_main", "vs_6_5")?;
+        let pixel_shader = compile_shader(include_str!("shader.hlsl"), "PS_main", "ps_6_5")?;
+
+        // The cube is wound counter-clockwise, so culling back faces only works with `front_counter_clockwise`
+        let cull_mode = D3D12_CULL_MODE_BACK;
+        let front_counter_clockwise = true;
+        let pipeline = create_pipeline(
+            &lib,
+            &root_signature,
+            &vertex_shader,
+            &pixel_shader,
+            cull_mode,
+            front_counter_clockwise,
+        )?;
+
+        Ok(App {
+            lib,
+            root_signature,
+            vertex_shader,
+            pixel_shader,
+            pipeline,
+            retired_pipelines: Vec::new(),
+            cull_mode,
+            front_counter_clockwise,
+            pipeline_outdated: false,
+        })
+    }
+}
+
+fn create_pipeline(
+    lib: &GPULib,
+    root_signature: &ID3D12RootSignature,
+    vertex_shader: &[u8],
+    pixel_shader: &[u8],
+    cull_mode: D3D12_CULL_MODE,
+    front_counter_clockwise: bool,
+) -> Result<ID3D12PipelineState, Box<dyn std::error::Error>> {
+    Ok(GraphicsPipelineBuilder::new(root_signature, vertex_shader)
+        .pixel_shader(pixel_shader)
+        .cull_mode(cull_mode)
+        .front_counter_clockwise(front_counter_clockwise)
+        .build(lib)?)
+}
+
+impl gimslib_rs::App for App {
+    fn record_ui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Culling").show(ctx, |ui| {
+            let mut changed = false;
+            for (cull_mode, label) in [
+                (D3D12_CULL_MODE_NONE, "Cull nothing"),
+                (D3D12_CULL_MODE_BACK, "Cull back faces"),
+                (D3D12_CULL_MODE_FRONT, "Cull front faces"),
+            ] {
+                changed |= ui
+                    .radio_value(&mut self.cull_mode, cull_mode, label)
+                    .changed();
+            }
+            changed |= ui
+                .checkbox(
+                    &mut self.front_counter_clockwise,
+                    "Counter-clockwise front faces",
+                )
+                .changed();
+            ui.label(
+                "Without depth buffer, only back face culling hides the far side of the cube.",
+            );
+            self.pipeline_outdated |= changed;
+        });
+    }
+
+    fn draw(&mut self, res: &FrameResources) -> Result<(), Box<dyn std::error::Error>> {
+        if self.pipeline_outdated {
+            let pipeline = create_pipeline(
+                &self.lib,
+                &self.root_signature,
+                &self.vertex_shader,
+                &self.pixel_shader,
+                self.cull_mode,
+                self.front_counter_clockwise,
+            )?;
+            self.retired_pipelines
+                .push(std::mem::replace(&mut self.pipeline, pipeline));
+            self.pipeline_outdated = false;
+        }
+
+        let angle = res.timing.elapsed.as_secs_f32();
+        let aspect_ratio = res.viewport.Width / res.viewport.Height;
+        let model = nalgebra_glm::rotation(angle, &nalgebra_glm::vec3(0.3, 1.0, 0.2).normalize());
+        let view = nalgebra_glm::look_at_rh(
+            &nalgebra_glm::vec3(0.0, 0.0, 2.5),
+            &nalgebra_glm::vec3(0.0, 0.0, 0.0),
+            &nalgebra_glm::vec3(0.0, 1.0, 0.0),
+        );
+        let projection = nalgebra_glm::perspective_rh_zo(aspect_ratio, 1.0, 0.1, 10.0);
+        let constants: Constants = (projection * view * model).as_slice().try_into()?;
+
+        let command_list = res.command_list;
+        unsafe {
+            command_list.ClearRenderTargetView(
+                res.render_target_handle_srgb,
+                &[0.1, 0.1, 0.1, 1.0],
+                None,
+            );
+            command_list.OMSetRenderTargets(1, Some(&res.render_target_handle), false, None);
+            command_list.RSSetViewports(&[res.viewport]);
+            command_list.RSSetScissorRects(&[res.scissor]);
+            command_list.SetGraphicsRootSignature(&self.root_signature);
+            command_list.SetPipelineState(&self.pipeline);
+            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+        }
+        set_graphics_root_32bit_constants(command_list, 0, &constants, 0);
+        unsafe { command_list.DrawInstanced(36, 1, 0, 0) };
+
+        Ok(())
+    }
+}
+
+fn main() {
+    let app_config = AppConfig {
+        window_title: "Culled cube".to_string(),
+        ..Default::default()
+    };
+    gimslib_rs::run_app_fallible(app_config, App::new).unwrap();
+}
@@ -0,0 +1,42 @@
+struct RootConstants
+{
+  float4x4 model_view_projection;
+};
+
+ConstantBuffer<RootConstants> rootConstants : register(b0);
+
+struct VertexShaderOutput
+{
+  float4 position : SV_POSITION;
+  float3 color : COLOR;
+};
+
+// Corners of a cube around the origin
+static const float3 corners[] = {
+  {-0.5f, -0.5f, -0.5f}, {0.5f, -0.5f, -0.5f}, {0.5f, 0.5f, -0.5f}, {-0.5f, 0.5f, -0.5f},
+  {-0.5f, -0.5f, 0.5f}, {0.5f, -0.5f, 0.5f}, {0.5f, 0.5f, 0.5f}, {-0.5f, 0.5f, 0.5f}};
+
+// Two triangles per face, counter-clockwise when looking at the face from outside in a right-handed system
+static const uint indices[] = {
+  0, 3, 2, 0, 2, 1, // -z
+  4, 5, 6, 4, 6, 7, // +z
+  0, 4, 7, 0, 7, 3, // -x
+  1, 2, 6, 1, 6, 5, // +x
+  0, 1, 5, 0, 5, 4, // -y
+  3, 7, 6, 3, 6, 2  // +y
+};
+
+VertexShaderOutput VS_main(uint i : SV_VertexID)
+{
+  float3 corner = corners[indices[i]];
+
+  VertexShaderOutput output;
+  output.position = mul(rootConstants.model_view_projection, float4(corner, 1.0f));
+  output.color = corner + 0.5f;
+  return output;
+}
+
+float4 PS_main(VertexShaderOutput input) : SV_TARGET
+{
+  return float4(input.color, 1.0f);
+}
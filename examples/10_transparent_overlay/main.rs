@@ -0,0 +1,55 @@
+use gimslib_rs::{AppConfig, FrameResources, WindowSize};
+use windows::Win32::Graphics::Dxgi::Common::DXGI_ALPHA_MODE_PREMULTIPLIED;
+
+/// A borderless window that only shows a translucent panel, with the desktop visible around it
+struct App {
+    /// Opacity of the panel background
+    opacity: f32,
+    /// Set when the title of the panel is dragged. The window can only be moved from `draw`.
+    drag_requested: bool,
+}
+
+impl gimslib_rs::App for App {
+    fn record_ui(&mut self, ctx: &egui::Context) {
+        let fill = egui::Color32::from_black_alpha((self.opacity * 255.0) as u8);
+        egui::CentralPanel::default()
+            // The default frame of the panel is opaque
+            .frame(egui::Frame::default().fill(fill).inner_margin(8.0))
+            .show(ctx, |ui| {
+                let title = ui.add(
+                    egui::Label::new(egui::RichText::new("Overlay").heading())
+                        .sense(egui::Sense::drag()),
+                );
+                if title.drag_started() {
+                    self.drag_requested = true;
+                }
+                ui.label("Drag the title to move the window, Alt+F4 closes it.");
+                ui.add(egui::Slider::new(&mut self.opacity, 0.0..=1.0).text("Opacity"));
+            });
+    }
+
+    fn draw(&mut self, res: &FrameResources) -> Result<(), Box<dyn std::error::Error>> {
+        if std::mem::take(&mut self.drag_requested) {
+            res.window.drag_window()?;
+        }
+
+        // Fully transparent, premultiplied black lets the desktop show through everywhere the UI does not draw
+        res.clear([0.0; 4]);
+        Ok(())
+    }
+}
+
+fn main() {
+    let app_config = AppConfig {
+        window_title: "Transparent overlay".to_string(),
+        window_size: WindowSize::Logical(320, 140),
+        window_decorations: false,
+        swapchain_alpha_mode: DXGI_ALPHA_MODE_PREMULTIPLIED,
+        ..Default::default()
+    };
+    gimslib_rs::run_app(app_config, |_| App {
+        opacity: 0.6,
+        drag_requested: false,
+    })
+    .unwrap();
+}
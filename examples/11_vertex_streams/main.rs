@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use gimslib_rs::{
+    AppConfig, FrameResources,
+    gpulib::{GPULib, compile_shader},
+    pipeline::{GraphicsPipelineBuilder, set_vertex_buffers, vertex_element},
+    root_signature::create_root_signature,
+    vector_constant_buffer::{BufferLocation, VectorConstantBuffer},
+};
+use windows::{
+    Win32::Graphics::{
+        Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST, Direct3D12::*, Dxgi::Common::*,
+    },
+    core::s,
+};
+
+/// Positions and colors live in separate vertex buffers, so the colors can be rewritten
+/// every frame without touching the positions
+struct App {
+    root_signature: ID3D12RootSignature,
+    pipeline: ID3D12PipelineState,
+    positions: VectorConstantBuffer<[f32; 2]>,
+    hue_shift: f32,
+}
+
+impl App {
+    fn new(lib: Arc<GPULib>) -> Result<Self, Box<dyn std::error::Error>> {
+        let root_signature = create_root_signature(
+            &lib,
+            &D3D12_ROOT_SIGNATURE_DESC1 {
+                Flags: D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT,
+                ..Default::default()
+            },
+        )?;
+        let vertex_shader = compile_shader(include_str!("shader.hlsl"), "VS_main", "vs_6_5")?;
+        let pixel_shader = compile_shader(include_str!("shader.hlsl"), "PS_main", "ps_6_5")?;
+
+        let input_layout = [
+            vertex_element(s!("POSITION"), 0, DXGI_FORMAT_R32G32_FLOAT, 0),
+            vertex_element(s!("COLOR"), 0, DXGI_FORMAT_R32G32B32_FLOAT, 1),
+        ];
+        let pipeline = GraphicsPipelineBuilder::new(&root_signature, &vertex_shader)
+            .pixel_shader(&pixel_shader)
+            .input_layout(&input_layout)
+            .build(&lib)?;
+
+        let mut positions =
+            VectorConstantBuffer::new(lib.clone(), 3, BufferLocation::GpuUpload, None)?;
+        positions.upload(&[[0.0, 0.5], [0.5, -0.5], [-0.5, -0.5]])?;
+
+        Ok(App {
+            root_signature,
+            pipeline,
+            positions,
+            hue_shift: 0.0,
+        })
+    }
+}
+
+impl gimslib_rs::App for App {
+    fn record_ui(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Window").show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.hue_shift, 0.0..=1.0).text("Hue shift"));
+        });
+    }
+
+    fn draw(&mut self, res: &FrameResources) -> Result<(), Box<dyn std::error::Error>> {
+        // Only the color stream is written every frame, into memory that lives as long as the frame
+        let colors: Vec<[f32; 3]> = (0..3)
+            .map(|vertex| {
+                let hue = (vertex as f32 / 3.0 + self.hue_shift).fract();
+                let color = egui::Rgba::from(egui::ecolor::Hsva::new(hue, 1.0, 1.0, 1.0));
+                [color.r(), color.g(), color.b()]
+            })
+            .collect();
+        let colors = D3D12_VERTEX_BUFFER_VIEW {
+            BufferLocation: res.upload_arena.upload(&colors)?,
+            SizeInBytes: size_of_val(colors.as_slice()) as u32,
+            StrideInBytes: size_of::<[f32; 3]>() as u32,
+        };
+
+        let command_list = res.command_list;
+        res.clear_srgb([0.0, 0.0, 0.0, 1.0]);
+        unsafe {
+            command_list.OMSetRenderTargets(1, Some(&res.render_target_handle_srgb), false, None);
+            command_list.RSSetViewports(&[res.viewport]);
+            command_list.RSSetScissorRects(&[res.scissor]);
+            command_list.SetGraphicsRootSignature(&self.root_signature);
+            command_list.SetPipelineState(&self.pipeline);
+            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+        }
+        set_vertex_buffers(
+            command_list,
+            0,
+            &[self.positions.vertex_buffer_view(), colors],
+        );
+        unsafe { command_list.DrawInstanced(3, 1, 0, 0) };
+        Ok(())
+    }
+}
+
+fn main() {
+    let app_config = AppConfig {
+        window_title: "Vertex streams".to_string(),
+        ..Default::default()
+    };
+    gimslib_rs::run_app_fallible(app_config, App::new).unwrap();
+}
@@ -0,0 +1,26 @@
+struct VertexShaderInput
+{
+  // Slot 0
+  float2 position : POSITION;
+  // Slot 1
+  float3 color : COLOR;
+};
+
+struct VertexShaderOutput
+{
+  float4 position : SV_POSITION;
+  float3 color : COLOR;
+};
+
+VertexShaderOutput VS_main(VertexShaderInput input)
+{
+  VertexShaderOutput output;
+  output.position = float4(input.position, 0.0f, 1.0f);
+  output.color = input.color;
+  return output;
+}
+
+float4 PS_main(VertexShaderOutput input) : SV_TARGET
+{
+  return float4(input.color, 1.0f);
+}
@@ -0,0 +1,348 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use windows::{
+    Win32::{
+        Foundation::HANDLE,
+        Graphics::{
+            Direct3D12::*,
+            Dxgi::Common::{DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC},
+        },
+    },
+    core::{Interface, h},
+};
+
+use crate::{
+    error::GimsError,
+    gpulib::GPULib,
+    texture::{self, TextureDesc},
+};
+
+/// Identifies an upload enqueued with `AssetLoader`, returned again by `poll_completed` once it is ready
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetHandle(u64);
+
+/// An upload submitted to the copy queue that may still be executing
+struct PendingUpload {
+    handle: AssetHandle,
+    fence_value: u64,
+    resource: ID3D12Resource,
+    command_allocator: ID3D12CommandAllocator,
+    command_list: ID3D12GraphicsCommandList,
+    /// Read by the copy, released once the upload has completed
+    _upload_buffer: ID3D12Resource,
+}
+
+/// Streams textures and buffers to the GPU on `GPULib::copy_queue`, so large assets are uploaded
+/// while frames continue to render on the direct queue.
+///
+/// Uploads are submitted immediately by the enqueue functions. Call `poll_completed` once per frame to retrieve the
+/// resources whose copies have finished. They are in the `COMMON` state, from which textures and buffers are
+/// implicitly promoted to shader resource states on first use, so no barrier is needed.
+pub struct AssetLoader {
+    lib: Arc<GPULib>,
+    /// Signaled on the copy queue after each upload, so the value marks only the work of this loader as done
+    fence: ID3D12Fence,
+    last_fence_value: u64,
+    pending: VecDeque<PendingUpload>,
+    /// Finished uploads not yet returned by `poll_completed`
+    completed: Vec<(AssetHandle, ID3D12Resource)>,
+    /// Command allocators and lists of completed uploads, ready for reuse
+    free_command_lists: Vec<(ID3D12CommandAllocator, ID3D12GraphicsCommandList)>,
+    max_pending: usize,
+    next_handle: u64,
+}
+
+impl AssetLoader {
+    /// At most `max_pending` uploads are in flight, each holding its upload buffer.
+    /// Enqueuing more waits for the oldest upload to complete, which bounds the memory used for staging.
+    pub fn new(lib: Arc<GPULib>, max_pending: usize) -> Result<Self, GimsError> {
+        if max_pending == 0 {
+            return Err("AssetLoader needs room for at least one pending upload".into());
+        }
+
+        let fence = unsafe { lib.device.CreateFence(0, D3D12_FENCE_FLAG_NONE) }?;
+
+        Ok(AssetLoader {
+            lib,
+            fence,
+            last_fence_value: 0,
+            pending: VecDeque::new(),
+            completed: Vec::new(),
+            free_command_lists: Vec::new(),
+            max_pending,
+            next_handle: 0,
+        })
+    }
+
+    /// Uploads a texture described by `desc`. `bytes` contains all subresources in order (`mip + slice * mip_levels`),
+    /// each with tightly packed rows. Block compressed formats have one row per row of blocks.
+    pub fn enqueue_texture(
+        &mut self,
+        bytes: &[u8],
+        desc: &TextureDesc,
+    ) -> Result<AssetHandle, GimsError> {
+        let resource_desc = desc.resource_desc()?;
+        let subresource_count = match desc.dimension {
+            texture::TextureDimension::Texture3D(_) => desc.mip_levels as u32,
+            _ => desc.mip_levels as u32 * desc.dimension.array_size(),
+        };
+
+        // Place the subresources one after another in the upload buffer
+        let mut footprints = Vec::with_capacity(subresource_count as usize);
+        let mut upload_size = 0;
+        for subresource in 0..subresource_count {
+            let mut footprint =
+                texture::copyable_footprint(&self.lib, &resource_desc, subresource)?;
+            footprint.layout.Offset =
+                upload_size.next_multiple_of(D3D12_TEXTURE_DATA_PLACEMENT_ALIGNMENT as u64);
+            upload_size = footprint.layout.Offset + footprint.total_bytes;
+            footprints.push(footprint);
+        }
+
+        let packed_size: u64 = footprints
+            .iter()
+            .map(|footprint| {
+                footprint.row_bytes
+                    * footprint.row_count as u64
+                    * footprint.layout.Footprint.Depth as u64
+            })
+            .sum();
+        if (bytes.len() as u64) < packed_size {
+            return Err(format!(
+                "Texture data has {} bytes, but the texture needs {}",
+                bytes.len(),
+                packed_size
+            )
+            .into());
+        }
+
+        let upload_buffer = self.create_upload_buffer(upload_size)?;
+        unsafe {
+            let mut mapped = std::ptr::null_mut();
+            upload_buffer.Map(0, Some(&D3D12_RANGE::default()), Some(&mut mapped))?;
+            let destination =
+                std::slice::from_raw_parts_mut(mapped as *mut u8, upload_size as usize);
+
+            let mut source_offset = 0;
+            for footprint in &footprints {
+                let row_bytes = footprint.row_bytes as usize;
+                let row_pitch = footprint.layout.Footprint.RowPitch as usize;
+                let rows = footprint.row_count as usize * footprint.layout.Footprint.Depth as usize;
+                for row in 0..rows {
+                    let destination_start = footprint.layout.Offset as usize + row * row_pitch;
+                    destination[destination_start..destination_start + row_bytes]
+                        .copy_from_slice(&bytes[source_offset..source_offset + row_bytes]);
+                    source_offset += row_bytes;
+                }
+            }
+
+            upload_buffer.Unmap(0, None);
+        }
+
+        let texture = texture::create_texture(
+            &self.lib,
+            desc,
+            D3D12_HEAP_TYPE_DEFAULT,
+            Some("Asset texture"),
+        )?;
+
+        self.submit(
+            texture,
+            upload_buffer,
+            |command_list, texture, upload_buffer| {
+                for (subresource, footprint) in footprints.iter().enumerate() {
+                    let destination = D3D12_TEXTURE_COPY_LOCATION {
+                        pResource: std::mem::ManuallyDrop::new(Some(texture.clone())),
+                        Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+                        Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                            SubresourceIndex: subresource as u32,
+                        },
+                    };
+                    let source = D3D12_TEXTURE_COPY_LOCATION {
+                        pResource: std::mem::ManuallyDrop::new(Some(upload_buffer.clone())),
+                        Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
+                        Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                            PlacedFootprint: footprint.layout,
+                        },
+                    };
+                    let mut locations = [destination, source];
+                    unsafe {
+                        command_list.CopyTextureRegion(&locations[0], 0, 0, 0, &locations[1], None);
+                    }
+                    for location in &mut locations {
+                        // Release the references taken for the copy description
+                        unsafe { std::mem::ManuallyDrop::drop(&mut location.pResource) };
+                    }
+                }
+            },
+        )
+    }
+
+    /// Uploads `bytes` into a new buffer in GPU memory, e.g. vertices or indices of a mesh
+    pub fn enqueue_buffer(&mut self, bytes: &[u8]) -> Result<AssetHandle, GimsError> {
+        let size = bytes.len() as u64;
+        let upload_buffer = self.create_upload_buffer(size)?;
+        unsafe {
+            let mut mapped = std::ptr::null_mut();
+            upload_buffer.Map(0, Some(&D3D12_RANGE::default()), Some(&mut mapped))?;
+            std::slice::from_raw_parts_mut(mapped as *mut u8, bytes.len()).copy_from_slice(bytes);
+            upload_buffer.Unmap(0, None);
+        }
+
+        let buffer = create_buffer(&self.lib, D3D12_HEAP_TYPE_DEFAULT, size)?;
+        unsafe { buffer.SetName(h!("Asset buffer")) }?;
+
+        self.submit(
+            buffer,
+            upload_buffer,
+            |command_list, buffer, upload_buffer| unsafe {
+                command_list.CopyBufferRegion(buffer, 0, upload_buffer, 0, size);
+            },
+        )
+    }
+
+    /// Returns all uploads that have finished on the GPU, in the order they were enqueued.
+    /// The returned resources can be used by the direct queue right away.
+    pub fn poll_completed(&mut self) -> Vec<(AssetHandle, ID3D12Resource)> {
+        self.retire_completed();
+        std::mem::take(&mut self.completed)
+    }
+
+    /// Number of uploads still executing on the GPU
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Moves finished uploads to `completed`, releasing their upload buffers and recycling their command lists
+    fn retire_completed(&mut self) {
+        let completed_value = unsafe { self.fence.GetCompletedValue() };
+        while let Some(upload) = self.pending.front()
+            && upload.fence_value <= completed_value
+        {
+            let upload = self.pending.pop_front().unwrap();
+            self.free_command_lists
+                .push((upload.command_allocator, upload.command_list));
+            self.completed.push((upload.handle, upload.resource));
+        }
+    }
+
+    fn submit(
+        &mut self,
+        resource: ID3D12Resource,
+        upload_buffer: ID3D12Resource,
+        record: impl FnOnce(&ID3D12GraphicsCommandList, &ID3D12Resource, &ID3D12Resource),
+    ) -> Result<AssetHandle, GimsError> {
+        // Backpressure: wait until the oldest upload has finished
+        self.retire_completed();
+        if self.pending.len() >= self.max_pending {
+            let oldest_fence_value = self.pending[0].fence_value;
+            // A null event handle blocks until the fence reaches the value
+            unsafe {
+                self.fence
+                    .SetEventOnCompletion(oldest_fence_value, HANDLE::default())
+            }?;
+            self.retire_completed();
+        }
+
+        let (command_allocator, command_list) = match self.free_command_lists.pop() {
+            Some((command_allocator, command_list)) => {
+                unsafe {
+                    command_allocator.Reset()?;
+                    command_list.Reset(&command_allocator, None)?;
+                }
+                (command_allocator, command_list)
+            }
+            None => self.lib.create_command_list(D3D12_COMMAND_LIST_TYPE_COPY)?,
+        };
+
+        record(&command_list, &resource, &upload_buffer);
+
+        self.last_fence_value += 1;
+        unsafe {
+            command_list.Close()?;
+            self.lib
+                .copy_queue
+                .ExecuteCommandLists(&[Some(command_list.cast()?)]);
+            self.lib
+                .copy_queue
+                .Signal(&self.fence, self.last_fence_value)?;
+        }
+
+        let handle = AssetHandle(self.next_handle);
+        self.next_handle += 1;
+        self.pending.push_back(PendingUpload {
+            handle,
+            fence_value: self.last_fence_value,
+            resource,
+            command_allocator,
+            command_list,
+            _upload_buffer: upload_buffer,
+        });
+
+        Ok(handle)
+    }
+
+    fn create_upload_buffer(&self, size: u64) -> Result<ID3D12Resource, GimsError> {
+        let upload_buffer = create_buffer(&self.lib, D3D12_HEAP_TYPE_UPLOAD, size)?;
+        unsafe { upload_buffer.SetName(h!("Asset upload buffer")) }?;
+        Ok(upload_buffer)
+    }
+}
+
+impl Drop for AssetLoader {
+    fn drop(&mut self) {
+        // Upload buffers and command lists must outlive the copies
+        let result = unsafe {
+            self.fence
+                .SetEventOnCompletion(self.last_fence_value, HANDLE::default())
+        };
+        if let Err(error) = result {
+            println!("Error while waiting for pending asset uploads:\n{}", error);
+        }
+    }
+}
+
+fn create_buffer(
+    lib: &GPULib,
+    heap_type: D3D12_HEAP_TYPE,
+    size: u64,
+) -> Result<ID3D12Resource, GimsError> {
+    lib.validate_buffer_size(size)?;
+
+    let mut resource_option = None;
+    unsafe {
+        lib.device.CreateCommittedResource(
+            &D3D12_HEAP_PROPERTIES {
+                Type: heap_type,
+                ..Default::default()
+            },
+            D3D12_HEAP_FLAGS::default(),
+            &D3D12_RESOURCE_DESC {
+                Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+                Width: size,
+                Height: 1,
+                DepthOrArraySize: 1,
+                Alignment: 0,
+                MipLevels: 1,
+                Format: DXGI_FORMAT_UNKNOWN,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+                Flags: D3D12_RESOURCE_FLAGS::default(),
+            },
+            // Upload heaps require GENERIC_READ, default heap buffers start in COMMON in any case
+            if heap_type == D3D12_HEAP_TYPE_UPLOAD {
+                D3D12_RESOURCE_STATE_GENERIC_READ
+            } else {
+                D3D12_RESOURCE_STATE_COMMON
+            },
+            None,
+            &mut resource_option,
+        )
+    }
+    .map_err(GimsError::resource_creation)?;
+
+    Ok(resource_option.ok_or("Failed to create asset buffer")?)
+}
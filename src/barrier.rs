@@ -0,0 +1,183 @@
+use std::mem::ManuallyDrop;
+
+use windows::Win32::Graphics::Direct3D12::*;
+
+/// A resource barrier that holds a counted reference to its resource, which is released again when it is dropped.
+/// Record barriers with `record`.
+#[repr(transparent)]
+pub struct Barrier(D3D12_RESOURCE_BARRIER);
+
+impl Drop for Barrier {
+    fn drop(&mut self) {
+        // Release the reference taken when the barrier was created
+        unsafe {
+            match self.0.Type {
+                D3D12_RESOURCE_BARRIER_TYPE_TRANSITION => {
+                    ManuallyDrop::drop(&mut (*self.0.Anonymous.Transition).pResource)
+                }
+                D3D12_RESOURCE_BARRIER_TYPE_UAV => {
+                    ManuallyDrop::drop(&mut (*self.0.Anonymous.UAV).pResource)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Records `barriers` into the command list with a single `ResourceBarrier` call
+pub fn record(command_list: &ID3D12GraphicsCommandList, barriers: &[Barrier]) {
+    // `Barrier` is a transparent wrapper, so the slice has the layout of the raw barriers
+    let barriers = unsafe {
+        std::slice::from_raw_parts(
+            barriers.as_ptr() as *const D3D12_RESOURCE_BARRIER,
+            barriers.len(),
+        )
+    };
+    unsafe { command_list.ResourceBarrier(barriers) };
+}
+
+/// Transition of all subresources of `resource`.
+/// Use `Transitions` to also validate the before-states in debug builds.
+pub fn transition(
+    resource: &ID3D12Resource,
+    before: D3D12_RESOURCE_STATES,
+    after: D3D12_RESOURCE_STATES,
+) -> Barrier {
+    transition_subresource(
+        resource,
+        D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
+        before,
+        after,
+    )
+}
+
+/// Transition of a single subresource, e.g. one mip level, see `D3D12CalcSubresource`
+pub fn transition_subresource(
+    resource: &ID3D12Resource,
+    subresource: u32,
+    before: D3D12_RESOURCE_STATES,
+    after: D3D12_RESOURCE_STATES,
+) -> Barrier {
+    Barrier(D3D12_RESOURCE_BARRIER {
+        Type: D3D12_RESOURCE_BARRIER_TYPE_TRANSITION,
+        Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
+        Anonymous: D3D12_RESOURCE_BARRIER_0 {
+            Transition: ManuallyDrop::new(D3D12_RESOURCE_TRANSITION_BARRIER {
+                pResource: ManuallyDrop::new(Some(resource.clone())),
+                Subresource: subresource,
+                StateBefore: before,
+                StateAfter: after,
+            }),
+        },
+    })
+}
+
+/// Waits for all unordered access to `resource` before later unordered access, e.g. between two compute dispatches
+/// writing the same buffer
+pub fn uav(resource: &ID3D12Resource) -> Barrier {
+    Barrier(D3D12_RESOURCE_BARRIER {
+        Type: D3D12_RESOURCE_BARRIER_TYPE_UAV,
+        Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
+        Anonymous: D3D12_RESOURCE_BARRIER_0 {
+            UAV: ManuallyDrop::new(D3D12_RESOURCE_UAV_BARRIER {
+                pResource: ManuallyDrop::new(Some(resource.clone())),
+            }),
+        },
+    })
+}
+
+/// Waits for all unordered access to any resource before later unordered access,
+/// e.g. after a dispatch that writes several buffers read by the next one
+pub fn uav_all() -> Barrier {
+    Barrier(D3D12_RESOURCE_BARRIER {
+        Type: D3D12_RESOURCE_BARRIER_TYPE_UAV,
+        Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
+        Anonymous: D3D12_RESOURCE_BARRIER_0 {
+            UAV: ManuallyDrop::new(D3D12_RESOURCE_UAV_BARRIER {
+                pResource: ManuallyDrop::new(None),
+            }),
+        },
+    })
+}
+
+/// Remembers the last known state of resources, so barriers with a wrong before-state are caught in debug builds.
+/// In release builds nothing is tracked.
+#[derive(Default)]
+pub struct StateTracker {
+    #[cfg(debug_assertions)]
+    states: std::collections::HashMap<usize, D3D12_RESOURCE_STATES>,
+}
+
+impl StateTracker {
+    /// Checks that `before` matches the tracked state of the resource and stores `after` as its new state.
+    /// Resources that were never transitioned are assumed to be in `before`.
+    #[cfg(debug_assertions)]
+    fn track(
+        &mut self,
+        resource: &ID3D12Resource,
+        before: D3D12_RESOURCE_STATES,
+        after: D3D12_RESOURCE_STATES,
+    ) {
+        use windows::core::Interface;
+
+        if let Some(tracked) = self.states.insert(resource.as_raw() as usize, after) {
+            assert_eq!(
+                tracked, before,
+                "Transition barrier before-state does not match the tracked resource state"
+            );
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn track(
+        &mut self,
+        _resource: &ID3D12Resource,
+        _before: D3D12_RESOURCE_STATES,
+        _after: D3D12_RESOURCE_STATES,
+    ) {
+    }
+
+    /// Stops tracking a resource. Must be called before the resource is released,
+    /// because a new resource could be created at the same address.
+    #[cfg(debug_assertions)]
+    pub fn forget(&mut self, resource: &ID3D12Resource) {
+        use windows::core::Interface;
+
+        self.states.remove(&(resource.as_raw() as usize));
+    }
+
+    #[cfg(not(debug_assertions))]
+    pub fn forget(&mut self, _resource: &ID3D12Resource) {}
+}
+
+/// Collects transition barriers and records them with a single `ResourceBarrier` call
+pub struct Transitions<'a> {
+    tracker: &'a mut StateTracker,
+    barriers: Vec<Barrier>,
+}
+
+impl<'a> Transitions<'a> {
+    pub fn new(tracker: &'a mut StateTracker) -> Self {
+        Transitions {
+            tracker,
+            barriers: Vec::new(),
+        }
+    }
+
+    /// Adds a transition of all subresources of `resource`
+    pub fn add(
+        mut self,
+        resource: &ID3D12Resource,
+        before: D3D12_RESOURCE_STATES,
+        after: D3D12_RESOURCE_STATES,
+    ) -> Self {
+        self.tracker.track(resource, before, after);
+        self.barriers.push(transition(resource, before, after));
+        self
+    }
+
+    /// Records all collected barriers into the command list
+    pub fn record(self, command_list: &ID3D12GraphicsCommandList) {
+        record(command_list, &self.barriers);
+    }
+}
@@ -0,0 +1,186 @@
+use std::{f32::consts::FRAC_PI_2, time::Duration};
+
+use nalgebra_glm::{Mat4, Vec3};
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use crate::{input::InputState, pipeline::DepthRange};
+
+/// Keeps the pitch away from straight up and down, where the view direction would be parallel to `up`
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+/// How points in view space are mapped to the depth range of Direct3D 12, which is 0 to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// `fov_y` is the vertical field of view in radians
+    Perspective { fov_y: f32, near: f32, far: f32 },
+    /// `height` is the visible height in world units, the width follows from the aspect ratio
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+/// View and projection matrices for the right handed coordinate system used in the examples:
+/// y points up and the camera looks along -z in view space. The matrices are column major like HLSL expects
+/// by default, so they can be uploaded into constant buffers and applied with `mul(matrix, position)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    /// Point the camera looks at
+    pub target: Vec3,
+    pub up: Vec3,
+    pub projection: Projection,
+    /// Must match the depth test of the pipelines, see `GraphicsPipelineBuilder::depth_test`
+    pub depth_range: DepthRange,
+}
+
+impl Camera {
+    /// Perspective camera with a vertical field of view of 60 degrees, seeing from 0.1 to 100 units
+    pub fn new(position: Vec3, target: Vec3) -> Self {
+        Camera {
+            position,
+            target,
+            up: Vec3::y(),
+            projection: Projection::Perspective {
+                fov_y: 60f32.to_radians(),
+                near: 0.1,
+                far: 100.0,
+            },
+            depth_range: DepthRange::Standard,
+        }
+    }
+
+    pub fn view(&self) -> Mat4 {
+        nalgebra_glm::look_at_rh(&self.position, &self.target, &self.up)
+    }
+
+    /// `aspect_ratio` is width divided by height, e.g. of `FrameResources::viewport`
+    pub fn projection(&self, aspect_ratio: f32) -> Mat4 {
+        // Swapping the planes maps near to 1 and far to 0
+        let planes = |near, far| match self.depth_range {
+            DepthRange::Standard => (near, far),
+            DepthRange::Reversed => (far, near),
+        };
+        match self.projection {
+            Projection::Perspective { fov_y, near, far } => {
+                let (near, far) = planes(near, far);
+                nalgebra_glm::perspective_rh_zo(aspect_ratio, fov_y, near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                let (near, far) = planes(near, far);
+                let half_height = height / 2.0;
+                let half_width = half_height * aspect_ratio;
+                nalgebra_glm::ortho_rh_zo(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+        }
+    }
+
+    /// Projection times view, which transforms world space positions to clip space
+    pub fn view_projection(&self, aspect_ratio: f32) -> Mat4 {
+        self.projection(aspect_ratio) * self.view()
+    }
+}
+
+/// Direction of the view for angles in radians. Yaw turns right around y, pitch looks up.
+/// Both zero look along -z.
+fn forward(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+        -pitch.cos() * yaw.cos(),
+    )
+}
+
+/// Rotates a camera around a target while the left mouse button is held and zooms with the mouse wheel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitController {
+    pub target: Vec3,
+    pub distance: f32,
+    /// Radians, see `FlyController::yaw`
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Radians per unit of `InputState::mouse_motion`
+    pub rotate_speed: f32,
+    /// Fraction of the distance per scrolled line
+    pub zoom_speed: f32,
+}
+
+impl OrbitController {
+    /// Starts on the +z side of `target`, looking at it
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        OrbitController {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            rotate_speed: 0.005,
+            zoom_speed: 0.1,
+        }
+    }
+
+    /// Applies the input since the previous frame and moves `camera` accordingly
+    pub fn update(&mut self, camera: &mut Camera, input: &InputState) {
+        if input.button_held(MouseButton::Left) {
+            let [x, y] = input.mouse_motion();
+            self.yaw += x as f32 * self.rotate_speed;
+            self.pitch = (self.pitch - y as f32 * self.rotate_speed).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+        let [_, scroll] = input.scroll_delta();
+        self.distance *= (1.0 - self.zoom_speed).powf(scroll);
+
+        camera.target = self.target;
+        camera.position = self.target - forward(self.yaw, self.pitch) * self.distance;
+    }
+}
+
+/// First person camera moved with WASD, space and left shift, and turned by `InputState::mouse_motion`.
+/// The mouse always turns the camera, so lock the cursor with `FrameResources::lock_cursor` while flying.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlyController {
+    /// Rotation around y in radians, positive values turn right
+    pub yaw: f32,
+    /// Rotation up and down in radians, limited to just below straight up and down
+    pub pitch: f32,
+    /// World units per second
+    pub move_speed: f32,
+    /// Radians per unit of `InputState::mouse_motion`
+    pub look_speed: f32,
+}
+
+impl Default for FlyController {
+    /// Looking along -z
+    fn default() -> Self {
+        FlyController {
+            yaw: 0.0,
+            pitch: 0.0,
+            move_speed: 2.0,
+            look_speed: 0.002,
+        }
+    }
+}
+
+impl FlyController {
+    /// Applies the input since the previous frame, which took `delta`, e.g. `FrameTiming::delta`
+    pub fn update(&mut self, camera: &mut Camera, input: &InputState, delta: Duration) {
+        let [x, y] = input.mouse_motion();
+        self.yaw += x as f32 * self.look_speed;
+        self.pitch = (self.pitch - y as f32 * self.look_speed).clamp(-MAX_PITCH, MAX_PITCH);
+
+        let forward = forward(self.yaw, self.pitch);
+        let right = forward.cross(&camera.up).normalize();
+        let axis = |positive, negative| {
+            input.key_held(positive) as i32 as f32 - input.key_held(negative) as i32 as f32
+        };
+        let direction = forward * axis(KeyCode::KeyW, KeyCode::KeyS)
+            + right * axis(KeyCode::KeyD, KeyCode::KeyA)
+            + camera.up * axis(KeyCode::Space, KeyCode::ShiftLeft);
+        if direction != Vec3::zeros() {
+            camera.position += direction.normalize() * self.move_speed * delta.as_secs_f32();
+        }
+        camera.target = camera.position + forward;
+    }
+}
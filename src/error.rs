@@ -0,0 +1,218 @@
+use std::fmt::Display;
+
+use windows::{
+    Win32::{
+        Foundation::E_OUTOFMEMORY,
+        Graphics::Dxgi::{DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET},
+    },
+    core::HRESULT,
+};
+
+/// Error type of all fallible functions of the library.
+/// Conversions from the error types used internally exist, so `?` works as with `Box<dyn Error>`.
+///
+/// Allocations that fail with `OutOfMemory` can be retried after freeing memory, e.g. by evicting cached assets:
+/// ```ignore
+/// let buffer = match VectorConstantBuffer::new(lib.clone(), count, location, None) {
+///     Err(GimsError::OutOfMemory) => {
+///         asset_cache.evict_unused();
+///         VectorConstantBuffer::new(lib.clone(), count, location, None)?
+///     }
+///     result => result?,
+/// };
+/// ```
+#[derive(Debug)]
+pub enum GimsError {
+    /// A Direct3D 12, DXGI or other Windows API call failed
+    Windows(windows::core::Error),
+    /// Creating the Direct3D 12 device, its command queue or the DXGI factory failed
+    DeviceCreation(windows::core::Error),
+    /// Creating or resizing the swapchain failed
+    SwapchainCreation(windows::core::Error),
+    /// Creating a buffer or texture failed for another reason than exhausted memory
+    ResourceCreation(windows::core::Error),
+    /// The device was removed or reset, e.g. by a driver update or a GPU timeout (TDR).
+    /// Contains the reason reported by `ID3D12Device::GetDeviceRemovedReason`.
+    DeviceRemoved(HRESULT),
+    /// The GPU did not finish a frame within `AppConfig::frame_timeout`, although the device was not removed
+    GpuTimeout(std::time::Duration),
+    /// DXC failed to compile a shader. Contains the compiler output.
+    ShaderCompile(String),
+    /// The DirectX Shader Compiler library could not be loaded. Contains the library name.
+    ShaderCompilerNotFound(String),
+    /// No adapter was found that can create a Direct3D 12 device
+    NoSuitableAdapter,
+    /// Video or system memory is exhausted (`E_OUTOFMEMORY`)
+    OutOfMemory,
+    /// A resource is larger than the GPU can address, see `GPULib::max_resource_size`
+    ResourceTooLarge { requested: u64, max: u64 },
+    /// Library specific failure described by a message
+    Message(String),
+    /// Error of the app or of another library, e.g. winit
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Display for GimsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GimsError::Windows(error) => write!(f, "Windows API error: {}", error),
+            GimsError::DeviceCreation(error) => {
+                write!(f, "Failed to create the Direct3D 12 device: {}", error)
+            }
+            GimsError::SwapchainCreation(error) => {
+                write!(f, "Failed to create the swapchain: {}", error)
+            }
+            GimsError::ResourceCreation(error) => {
+                write!(f, "Failed to create a GPU resource: {}", error)
+            }
+            GimsError::DeviceRemoved(reason) => write!(
+                f,
+                "The Direct3D 12 device was removed ({:?}): {}",
+                reason,
+                reason.message()
+            ),
+            GimsError::GpuTimeout(timeout) => write!(
+                f,
+                "GPU timeout: a frame did not finish within {:?}",
+                timeout
+            ),
+            GimsError::ShaderCompile(output) => write!(f, "Shader compilation failed:\n{}", output),
+            GimsError::ShaderCompilerNotFound(library) => write!(
+                f,
+                "DirectX Shader Compiler not found ({}); ensure dxcompiler.dll is alongside the executable",
+                library
+            ),
+            GimsError::NoSuitableAdapter => write!(f, "No suitable Direct3D 12 adapter found"),
+            GimsError::OutOfMemory => write!(f, "Out of memory"),
+            GimsError::ResourceTooLarge { requested, max } => write!(
+                f,
+                "Resource of {} bytes exceeds the maximum resource size of {} bytes",
+                requested, max
+            ),
+            GimsError::Message(message) => write!(f, "{}", message),
+            GimsError::Other(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for GimsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GimsError::Windows(error)
+            | GimsError::DeviceCreation(error)
+            | GimsError::SwapchainCreation(error)
+            | GimsError::ResourceCreation(error) => Some(error),
+            GimsError::Other(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl GimsError {
+    /// Maps a failed resource creation to `OutOfMemory` or `ResourceCreation`, for use with `map_err`
+    pub(crate) fn resource_creation(error: windows::core::Error) -> Self {
+        if error.code() == E_OUTOFMEMORY {
+            GimsError::OutOfMemory
+        } else {
+            GimsError::ResourceCreation(error)
+        }
+    }
+}
+
+impl From<windows::core::Error> for GimsError {
+    /// Removal is reported with the code of the failed call, which is only the actual reason if no device is at hand
+    /// to ask for `GetDeviceRemovedReason`
+    fn from(error: windows::core::Error) -> Self {
+        match error.code() {
+            E_OUTOFMEMORY => GimsError::OutOfMemory,
+            code @ (DXGI_ERROR_DEVICE_REMOVED | DXGI_ERROR_DEVICE_RESET) => {
+                GimsError::DeviceRemoved(code)
+            }
+            _ => GimsError::Windows(error),
+        }
+    }
+}
+
+impl From<hassle_rs::HassleError> for GimsError {
+    fn from(error: hassle_rs::HassleError) -> Self {
+        match error {
+            hassle_rs::HassleError::CompileError(output) => GimsError::ShaderCompile(output),
+            hassle_rs::HassleError::LoadLibraryError { filename, .. } => {
+                GimsError::ShaderCompilerNotFound(filename.display().to_string())
+            }
+            error => GimsError::Other(error.into()),
+        }
+    }
+}
+
+impl From<&str> for GimsError {
+    fn from(message: &str) -> Self {
+        GimsError::Message(message.to_string())
+    }
+}
+
+impl From<String> for GimsError {
+    fn from(message: String) -> Self {
+        GimsError::Message(message)
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for GimsError {
+    fn from(error: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        GimsError::Other(error)
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for GimsError {
+    /// Errors returned by the app, which keep their variant if they are a `GimsError`.
+    /// Others may not be `Send`, so only their message is kept.
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        match error.downcast::<GimsError>() {
+            Ok(error) => *error,
+            Err(error) => GimsError::Message(error.to_string()),
+        }
+    }
+}
+
+impl From<std::num::TryFromIntError> for GimsError {
+    fn from(error: std::num::TryFromIntError) -> Self {
+        GimsError::Other(error.into())
+    }
+}
+
+impl From<std::io::Error> for GimsError {
+    fn from(error: std::io::Error) -> Self {
+        GimsError::Other(error.into())
+    }
+}
+
+#[cfg(feature = "shader-reload")]
+impl From<notify::Error> for GimsError {
+    fn from(error: notify::Error) -> Self {
+        GimsError::Other(error.into())
+    }
+}
+
+impl From<winit::error::OsError> for GimsError {
+    fn from(error: winit::error::OsError) -> Self {
+        GimsError::Other(error.into())
+    }
+}
+
+impl From<winit::error::EventLoopError> for GimsError {
+    fn from(error: winit::error::EventLoopError) -> Self {
+        GimsError::Other(error.into())
+    }
+}
+
+impl From<winit::error::ExternalError> for GimsError {
+    fn from(error: winit::error::ExternalError) -> Self {
+        GimsError::Other(error.into())
+    }
+}
+
+impl From<winit::raw_window_handle::HandleError> for GimsError {
+    fn from(error: winit::raw_window_handle::HandleError) -> Self {
+        GimsError::Other(error.into())
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use windows::Win32::Graphics::Direct3D12::{
+    D3D12_FENCE_FLAG_NONE, ID3D12CommandAllocator, ID3D12CommandQueue, ID3D12Fence,
+};
+
+use crate::{error::GimsError, gpulib::GPULib, running_state::event::Event};
+
+/// `ID3D12Fence` with an increasing counter, so each signal gets a new value that can be waited for.
+/// Values that were never signaled count as complete, so waiting on work that was never submitted returns immediately.
+pub struct Fence {
+    fence: ID3D12Fence,
+    event: Event,
+    /// Value of the last `signal`, 0 before the first one
+    last_signaled: u64,
+}
+
+impl Fence {
+    pub fn new(lib: &GPULib) -> Result<Self, GimsError> {
+        let fence = unsafe { lib.device.CreateFence(0, D3D12_FENCE_FLAG_NONE) }?;
+        let event = Event::new(false)?;
+
+        Ok(Fence {
+            fence,
+            event,
+            last_signaled: 0,
+        })
+    }
+
+    /// Signals the next value once `queue` has finished the work submitted so far, and returns the value
+    pub fn signal(&mut self, queue: &ID3D12CommandQueue) -> Result<u64, GimsError> {
+        self.last_signaled += 1;
+        unsafe { queue.Signal(&self.fence, self.last_signaled) }?;
+        Ok(self.last_signaled)
+    }
+
+    /// Value of the last `signal`
+    pub fn last_signaled(&self) -> u64 {
+        self.last_signaled
+    }
+
+    /// Highest value the GPU has reached so far
+    pub fn completed_value(&self) -> u64 {
+        unsafe { self.fence.GetCompletedValue() }
+    }
+
+    pub fn is_complete(&self, value: u64) -> bool {
+        self.completed_value() >= value
+    }
+
+    /// Blocks until the GPU has reached `value`. The event round trip is skipped if it already has.
+    /// A timed out `wait_for_timeout` leaves its completion armed on the shared event, so a wake up
+    /// may belong to an older value and the fence is checked again after each one.
+    pub fn wait_for(&self, value: u64) -> Result<(), GimsError> {
+        while !self.is_complete(value) {
+            unsafe { self.fence.SetEventOnCompletion(value, *self.event) }?;
+            self.event.wait()?;
+        }
+        Ok(())
+    }
+
+    /// Blocks at most `timeout` until the GPU has reached `value`. Returns whether it was reached.
+    pub fn wait_for_timeout(&self, value: u64, timeout: Duration) -> Result<bool, GimsError> {
+        let start = Instant::now();
+        while !self.is_complete(value) {
+            let remaining = timeout.saturating_sub(start.elapsed());
+            if remaining.is_zero() {
+                return Ok(false);
+            }
+            unsafe { self.fence.SetEventOnCompletion(value, *self.event) }?;
+            if !self.event.wait_timeout(remaining)? {
+                // The value may have been reached between the timeout and this check
+                return Ok(self.is_complete(value));
+            }
+        }
+        Ok(true)
+    }
+
+    /// Blocks until the GPU has reached the value of the last `signal`
+    pub fn wait_latest(&self) -> Result<(), GimsError> {
+        self.wait_for(self.last_signaled)
+    }
+
+    /// Resets `allocator` after checking that the GPU has reached `value`, which marks the end of the work
+    /// recorded with it. Resetting an allocator whose commands are still executing corrupts them, and without
+    /// the debug layer this would go unnoticed.
+    pub fn reset_command_allocator(
+        &self,
+        allocator: &ID3D12CommandAllocator,
+        value: u64,
+    ) -> Result<(), GimsError> {
+        let completed_value = self.completed_value();
+        if completed_value < value {
+            return Err(format!(
+                "Command allocator reset while its commands are still executing on the GPU (fence at {}, waiting for {})",
+                completed_value, value
+            )
+            .into());
+        }
+        unsafe { allocator.Reset() }?;
+
+        Ok(())
+    }
+
+    /// Makes `queue` wait on the GPU until the value of the last `signal` is reached, without blocking the CPU
+    pub fn queue_wait_latest(&self, queue: &ID3D12CommandQueue) -> Result<(), GimsError> {
+        unsafe { queue.Wait(&self.fence, self.last_signaled) }?;
+        Ok(())
+    }
+}
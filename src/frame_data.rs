@@ -1,4 +1,7 @@
-use std::cell::Cell;
+use std::{
+    cell::Cell,
+    ops::{Index, IndexMut},
+};
 
 pub struct FrameData<T> {
     current_frame: Cell<usize>,
@@ -39,6 +42,15 @@ impl<T> FrameData<T> {
         })
     }
 
+    /// The number of frames in flight
+    pub fn len(&self) -> usize {
+        self.frame_datas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frame_datas.is_empty()
+    }
+
     pub fn increment_frame(&self) {
         self.current_frame
             .set((self.current_frame.get() + 1) % self.frame_datas.len());
@@ -52,6 +64,37 @@ impl<T> FrameData<T> {
         &mut self.frame_datas[self.current_frame.get()]
     }
 
+    /// The data of the frame `offset` frames after the current one, wrapping around in both directions
+    pub fn get_relative(&self, offset: isize) -> &T {
+        let len = self.frame_datas.len() as isize;
+        let frame = (self.current_frame.get() as isize + offset).rem_euclid(len);
+        &self.frame_datas[frame as usize]
+    }
+
+    /// The data of the previous frame, which is the most recently recorded one
+    pub fn get_previous(&self) -> &T {
+        self.get_relative(-1)
+    }
+
+    /// The data of the next frame, which is the oldest one that may still be in flight
+    pub fn get_next(&self) -> &T {
+        self.get_relative(1)
+    }
+
+    /// Iterates over the data of all frames, starting with the current one
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let current_frame = self.current_frame.get();
+        self.frame_datas[current_frame..]
+            .iter()
+            .chain(&self.frame_datas[..current_frame])
+    }
+
+    /// Mutably iterates over the data of all frames, starting with the current one
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        let (before, after) = self.frame_datas.split_at_mut(self.current_frame.get());
+        after.iter_mut().chain(before)
+    }
+
     pub fn for_each_frame(&mut self, function: impl Fn(&mut T)) {
         for frame_from_zero in 0..self.frame_datas.len() {
             let frame = (self.current_frame.get() + frame_from_zero) % self.frame_datas.len();
@@ -59,3 +102,18 @@ impl<T> FrameData<T> {
         }
     }
 }
+
+/// Accesses the data of a frame by its position, independent of the current frame
+impl<T> Index<usize> for FrameData<T> {
+    type Output = T;
+
+    fn index(&self, frame: usize) -> &T {
+        &self.frame_datas[frame]
+    }
+}
+
+impl<T> IndexMut<usize> for FrameData<T> {
+    fn index_mut(&mut self, frame: usize) -> &mut T {
+        &mut self.frame_datas[frame]
+    }
+}
@@ -0,0 +1,15 @@
+struct FullscreenVertex
+{
+    float4 position : SV_Position;
+    // (0, 0) in the top left corner, (1, 1) in the bottom right corner of the screen
+    float2 uv : TEXCOORD;
+};
+
+// Vertices 0, 1 and 2 span a triangle covering the whole screen, without a vertex buffer
+FullscreenVertex main(uint vertex_id : SV_VertexID)
+{
+    FullscreenVertex output;
+    output.uv = float2((vertex_id << 1) & 2, vertex_id & 2);
+    output.position = float4(output.uv * float2(2.0, -2.0) + float2(-1.0, 1.0), 0.0, 1.0);
+    return output;
+}
@@ -0,0 +1,155 @@
+use windows::Win32::Graphics::{
+    Direct3D12::*,
+    Dxgi::Common::{DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC},
+};
+
+use crate::{error::GimsError, gpulib::GPULib};
+
+/// Size of a resolved timestamp in the readback buffer
+const TIMESTAMP_SIZE: u64 = std::mem::size_of::<u64>() as u64;
+
+/// Measures the GPU time between `begin` and `end` with timestamp queries on the direct queue.
+/// The timestamps of a frame can only be read once the GPU has finished it, so each of the `frame_count` frames
+/// in flight gets its own pair of queries and `last_frame_ms` lags behind by `frame_count` frames.
+///
+/// Each call of `begin` reuses the queries of the frame `frame_count` calls earlier and reads its results,
+/// so the caller has to wait for that frame to finish first, like `RunningState::draw` does with its fence.
+pub struct GpuTimer {
+    query_heap: ID3D12QueryHeap,
+    /// Two resolved timestamps per frame in flight
+    readback: ID3D12Resource,
+    /// Ticks per second of the timestamps
+    frequency: u64,
+    frame_count: usize,
+    /// Frame whose queries are used by the next `begin` and `end`
+    current: usize,
+    /// Frames whose timestamps were resolved but not yet read
+    pending: Vec<bool>,
+    last_frame_ms: f64,
+}
+
+impl GpuTimer {
+    pub fn new(lib: &GPULib, frame_count: usize) -> Result<Self, GimsError> {
+        let query_count = u32::try_from(frame_count * 2)?;
+        let mut query_heap_option: Option<ID3D12QueryHeap> = None;
+        unsafe {
+            lib.device.CreateQueryHeap(
+                &D3D12_QUERY_HEAP_DESC {
+                    Type: D3D12_QUERY_HEAP_TYPE_TIMESTAMP,
+                    Count: query_count,
+                    NodeMask: 0,
+                },
+                &mut query_heap_option,
+            )
+        }?;
+        let query_heap = query_heap_option.ok_or("Failed to create timestamp query heap")?;
+
+        let mut readback_option = None;
+        unsafe {
+            lib.device.CreateCommittedResource(
+                &D3D12_HEAP_PROPERTIES {
+                    Type: D3D12_HEAP_TYPE_READBACK,
+                    ..Default::default()
+                },
+                D3D12_HEAP_FLAGS::default(),
+                &D3D12_RESOURCE_DESC {
+                    Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+                    Width: query_count as u64 * TIMESTAMP_SIZE,
+                    Height: 1,
+                    DepthOrArraySize: 1,
+                    Alignment: 0,
+                    MipLevels: 1,
+                    Format: DXGI_FORMAT_UNKNOWN,
+                    SampleDesc: DXGI_SAMPLE_DESC {
+                        Count: 1,
+                        Quality: 0,
+                    },
+                    Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+                    Flags: D3D12_RESOURCE_FLAGS::default(),
+                },
+                D3D12_RESOURCE_STATE_COPY_DEST,
+                None,
+                &mut readback_option,
+            )
+        }
+        .map_err(GimsError::resource_creation)?;
+        let readback: ID3D12Resource =
+            readback_option.ok_or("Failed to create timestamp readback buffer")?;
+
+        let frequency = unsafe { lib.queue.GetTimestampFrequency() }?;
+
+        Ok(GpuTimer {
+            query_heap,
+            readback,
+            frequency,
+            frame_count,
+            current: 0,
+            pending: vec![false; frame_count],
+            last_frame_ms: 0.0,
+        })
+    }
+
+    /// Reads the result of the frame `frame_count` calls ago, which must have finished on the GPU,
+    /// and records the start timestamp of the current frame
+    pub fn begin(&mut self, command_list: &ID3D12GraphicsCommandList) -> Result<(), GimsError> {
+        if self.pending[self.current] {
+            self.last_frame_ms = self.read(self.current)?;
+            self.pending[self.current] = false;
+        }
+        unsafe {
+            command_list.EndQuery(
+                &self.query_heap,
+                D3D12_QUERY_TYPE_TIMESTAMP,
+                self.current as u32 * 2,
+            );
+        }
+        Ok(())
+    }
+
+    /// Records the end timestamp of the current frame and copies both timestamps into the readback buffer
+    pub fn end(&mut self, command_list: &ID3D12GraphicsCommandList) {
+        let first_query = self.current as u32 * 2;
+        unsafe {
+            command_list.EndQuery(
+                &self.query_heap,
+                D3D12_QUERY_TYPE_TIMESTAMP,
+                first_query + 1,
+            );
+            command_list.ResolveQueryData(
+                &self.query_heap,
+                D3D12_QUERY_TYPE_TIMESTAMP,
+                first_query,
+                2,
+                &self.readback,
+                first_query as u64 * TIMESTAMP_SIZE,
+            );
+        }
+        self.pending[self.current] = true;
+        self.current = (self.current + 1) % self.frame_count;
+    }
+
+    /// GPU time in milliseconds of the last frame that has been read back, 0 before the first one
+    pub fn last_frame_ms(&self) -> f64 {
+        self.last_frame_ms
+    }
+
+    fn read(&self, frame: usize) -> Result<f64, GimsError> {
+        let offset = frame * 2 * TIMESTAMP_SIZE as usize;
+        let range = D3D12_RANGE {
+            Begin: offset,
+            End: offset + 2 * TIMESTAMP_SIZE as usize,
+        };
+        let [begin, end] = unsafe {
+            let mut pointer = std::ptr::null_mut();
+            self.readback.Map(0, Some(&range), Some(&mut pointer))?;
+            let timestamps =
+                ((pointer as *const u8).add(offset) as *const [u64; 2]).read_unaligned();
+            // Nothing was written
+            self.readback.Unmap(0, Some(&D3D12_RANGE::default()));
+            timestamps
+        };
+
+        // The timestamps are meaningless if the GPU changed its clock in between, which shows as a negative duration
+        Ok(end.saturating_sub(begin) as f64 * 1000.0 / self.frequency as f64)
+    }
+}
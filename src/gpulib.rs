@@ -1,97 +1,276 @@
+use std::{sync::Arc, time::Duration};
+
 use windows::{
     Win32::Graphics::{Direct3D::*, Direct3D12::*, Dxgi::*},
     core::h,
 };
 
+use crate::{error::GimsError, fence::Fence};
+
+/// The device, queue and factory shared by all parts of the library.
+/// Direct3D 12 devices are free threaded, so resources can be created from any thread.
+/// `queue` is also used by the frame loop, so other submissions are ordered with the frames submitted before them.
 pub struct GPULib {
     pub queue: ID3D12CommandQueue,
+    /// Queue for copies that run alongside the frames, e.g. asynchronous uploads.
+    /// Work on it has to be synchronized with `queue` through fences.
+    pub copy_queue: ID3D12CommandQueue,
+    /// Queue for async compute that runs alongside the frames, synchronized with `queue` through fences
+    pub compute_queue: ID3D12CommandQueue,
     pub device: ID3D12Device9,
     pub factory: IDXGIFactory7,
+    /// The adapter the device was created on
+    pub adapter: IDXGIAdapter1,
+    /// Description of the adapter, e.g. for display or as a key for caches that depend on the GPU and driver
+    pub info: GpuInfo,
 }
 
-impl GPULib {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        #[cfg(debug_assertions)]
-        {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuInfo {
+    /// Human readable adapter name
+    pub description: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub subsystem_id: u32,
+    pub revision: u32,
+    /// Locally unique identifier of the adapter, only valid until the system restarts
+    pub luid: i64,
+    /// Dedicated video memory in bytes
+    pub dedicated_video_memory: usize,
+    /// User mode driver version, packed as four 16 bit parts (product, version, subversion, build).
+    /// `None` if the driver does not report it.
+    pub driver_version: Option<u64>,
+}
+
+impl GpuInfo {
+    fn new(adapter: &IDXGIAdapter1) -> Result<Self, GimsError> {
+        use windows::core::Interface;
+
+        let desc = unsafe { adapter.GetDesc1() }?;
+        let description_length = desc
+            .Description
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(desc.Description.len());
+
+        let driver_version = unsafe { adapter.CheckInterfaceSupport(&IDXGIDevice::IID) }
+            .ok()
+            .map(|version| version as u64);
+
+        Ok(GpuInfo {
+            description: String::from_utf16_lossy(&desc.Description[..description_length]),
+            vendor_id: desc.VendorId,
+            device_id: desc.DeviceId,
+            subsystem_id: desc.SubSysId,
+            revision: desc.Revision,
+            luid: ((desc.AdapterLuid.HighPart as i64) << 32) | desc.AdapterLuid.LowPart as i64,
+            dedicated_video_memory: desc.DedicatedVideoMemory,
+            driver_version,
+        })
+    }
+
+    /// The driver version formatted as `product.version.subversion.build`
+    pub fn driver_version_string(&self) -> Option<String> {
+        self.driver_version.map(|version| {
+            format!(
+                "{}.{}.{}.{}",
+                (version >> 48) & 0xFFFF,
+                (version >> 32) & 0xFFFF,
+                (version >> 16) & 0xFFFF,
+                version & 0xFFFF
+            )
+        })
+    }
+}
+
+/// Settings for creating a `GPULib`. `run_app` uses the builder stored in `AppConfig::gpu`,
+/// but it can also be used on its own, e.g. to query the capabilities of the device before the window exists.
+#[derive(Debug, Clone)]
+pub struct GpuLibBuilder {
+    gpu_preference: DXGI_GPU_PREFERENCE,
+    adapter_luid: Option<i64>,
+    hardware_adapters: bool,
+    debug_layer: bool,
+    gpu_based_validation: bool,
+    queue_priority: D3D12_COMMAND_QUEUE_PRIORITY,
+    warp_fallback: bool,
+}
+
+impl Default for GpuLibBuilder {
+    /// High performance hardware adapter, high priority queue and the debug layer in debug builds
+    fn default() -> Self {
+        GpuLibBuilder {
+            gpu_preference: DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE,
+            adapter_luid: None,
+            hardware_adapters: true,
+            debug_layer: cfg!(debug_assertions),
+            gpu_based_validation: cfg!(debug_assertions),
+            queue_priority: D3D12_COMMAND_QUEUE_PRIORITY_HIGH,
+            warp_fallback: false,
+        }
+    }
+}
+
+impl GpuLibBuilder {
+    pub fn new() -> Self {
+        GpuLibBuilder::default()
+    }
+
+    /// Order in which hardware adapters are tried
+    pub fn gpu_preference(mut self, gpu_preference: DXGI_GPU_PREFERENCE) -> Self {
+        self.gpu_preference = gpu_preference;
+        self
+    }
+
+    /// Only accept the adapter with this locally unique identifier, see `GpuInfo::luid`
+    pub fn adapter_luid(mut self, luid: Option<i64>) -> Self {
+        self.adapter_luid = luid;
+        self
+    }
+
+    /// Whether hardware adapters are tried. Without them, only the WARP adapter of `warp_fallback` is used,
+    /// e.g. for deterministic results in tests.
+    pub fn hardware_adapters(mut self, enabled: bool) -> Self {
+        self.hardware_adapters = enabled;
+        self
+    }
+
+    /// Enables the D3D12 debug layer, whose messages are printed to stderr
+    pub fn debug_layer(mut self, enabled: bool) -> Self {
+        self.debug_layer = enabled;
+        self
+    }
+
+    /// Enables GPU based validation of the debug layer, which is thorough but slow.
+    /// Has no effect without the debug layer.
+    pub fn gpu_based_validation(mut self, enabled: bool) -> Self {
+        self.gpu_based_validation = enabled;
+        self
+    }
+
+    pub fn queue_priority(mut self, priority: D3D12_COMMAND_QUEUE_PRIORITY) -> Self {
+        self.queue_priority = priority;
+        self
+    }
+
+    /// Falls back to the WARP software renderer if no hardware adapter can create a device
+    pub fn warp_fallback(mut self, enabled: bool) -> Self {
+        self.warp_fallback = enabled;
+        self
+    }
+
+    pub fn build(&self) -> Result<Arc<GPULib>, GimsError> {
+        Ok(Arc::new(self.create()?))
+    }
+
+    fn create(&self) -> Result<GPULib, GimsError> {
+        if self.debug_layer {
             let mut debug_option: Option<ID3D12Debug6> = None;
             unsafe { D3D12GetDebugInterface(&mut debug_option) }?;
             if let Some(debug) = debug_option.take() {
                 unsafe {
                     debug.EnableDebugLayer();
-                    debug.SetEnableGPUBasedValidation(true);
+                    debug.SetEnableGPUBasedValidation(self.gpu_based_validation);
                 }
             } else {
                 println!("Debug is active, but the debug layer could not be loaded");
             }
         }
 
-        let dxgi_factory_flags = if cfg!(debug_assertions) {
+        let dxgi_factory_flags = if self.debug_layer {
             DXGI_CREATE_FACTORY_DEBUG
         } else {
             DXGI_CREATE_FACTORY_FLAGS(0)
         };
 
-        let factory = unsafe { CreateDXGIFactory2::<IDXGIFactory7>(dxgi_factory_flags) }?;
+        let factory = unsafe { CreateDXGIFactory2::<IDXGIFactory7>(dxgi_factory_flags) }
+            .map_err(GimsError::DeviceCreation)?;
 
-        let device = Self::create_device(&factory, DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE)?;
+        let (adapter, device) = self.create_device(&factory)?;
+        let info = GpuInfo::new(&adapter)?;
 
         // Register debug callback, so messages will be printed to stderr
         // Callback will never be unregistered, so all the related variables can be dropped
-        #[cfg(debug_assertions)]
-        unsafe {
-            use windows::core::Interface;
-            // Get InfoQueue1 interface from device
-            let mut info_queue = std::mem::MaybeUninit::<ID3D12InfoQueue1>::uninit();
-            if device
-                .query(&ID3D12InfoQueue1::IID, info_queue.as_mut_ptr() as _)
-                .is_err()
-            {
-                return Err("Failed to query info queue".into());
-            }
+        if self.debug_layer {
+            unsafe {
+                use windows::core::Interface;
+                // Get InfoQueue1 interface from device
+                let mut info_queue = std::mem::MaybeUninit::<ID3D12InfoQueue1>::uninit();
+                if device
+                    .query(&ID3D12InfoQueue1::IID, info_queue.as_mut_ptr() as _)
+                    .is_err()
+                {
+                    return Err("Failed to query info queue".into());
+                }
+
+                let mut callback_cookie = std::mem::MaybeUninit::uninit();
+                info_queue.assume_init().RegisterMessageCallback(
+                    Some(debug_message_callback),
+                    D3D12_MESSAGE_CALLBACK_FLAG_NONE,
+                    std::ptr::null_mut(),
+                    callback_cookie.as_mut_ptr(),
+                )?;
 
-            let mut callback_cookie = std::mem::MaybeUninit::uninit();
-            info_queue.assume_init().RegisterMessageCallback(
-                Some(debug_message_callback),
-                D3D12_MESSAGE_CALLBACK_FLAG_NONE,
-                std::ptr::null_mut(),
-                callback_cookie.as_mut_ptr(),
-            )?;
-
-            // Secondary error check according to Microsoft docs
-            if (callback_cookie.assume_init()) == 0 {
-                return Err("Failed to register D3D12 debug layer message callback".into());
+                // Secondary error check according to Microsoft docs
+                if (callback_cookie.assume_init()) == 0 {
+                    return Err("Failed to register D3D12 debug layer message callback".into());
+                }
             }
         }
 
-        let queue: ID3D12CommandQueue = unsafe {
-            let desc = D3D12_COMMAND_QUEUE_DESC {
-                Type: D3D12_COMMAND_LIST_TYPE_DIRECT,
-                Priority: D3D12_COMMAND_QUEUE_PRIORITY_HIGH.0,
-                Flags: D3D12_COMMAND_QUEUE_FLAG_NONE,
-                NodeMask: 0,
-            };
-            device.CreateCommandQueue(&desc)
-        }?;
+        let queue = self.create_queue(&device, D3D12_COMMAND_LIST_TYPE_DIRECT)?;
+        let copy_queue = self.create_queue(&device, D3D12_COMMAND_LIST_TYPE_COPY)?;
+        let compute_queue = self.create_queue(&device, D3D12_COMMAND_LIST_TYPE_COMPUTE)?;
+
+        // Shaders are only compiled once the app starts, so warn early if that is going to fail
+        if let Err(error) = check_shader_compiler() {
+            eprintln!("Warning: {}", error);
+        }
 
         unsafe {
             device.SetName(h!("Gimslib main device")).unwrap();
             queue.SetName(h!("Gimslib main queue")).unwrap();
+            copy_queue.SetName(h!("Gimslib copy queue")).unwrap();
+            compute_queue.SetName(h!("Gimslib compute queue")).unwrap();
         }
 
         Ok(GPULib {
             factory,
             device,
             queue,
+            copy_queue,
+            compute_queue,
+            adapter,
+            info,
         })
     }
 
+    fn create_queue(
+        &self,
+        device: &ID3D12Device9,
+        command_list_type: D3D12_COMMAND_LIST_TYPE,
+    ) -> Result<ID3D12CommandQueue, GimsError> {
+        let desc = D3D12_COMMAND_QUEUE_DESC {
+            Type: command_list_type,
+            Priority: self.queue_priority.0,
+            Flags: D3D12_COMMAND_QUEUE_FLAG_NONE,
+            NodeMask: 0,
+        };
+        unsafe { device.CreateCommandQueue(&desc) }.map_err(GimsError::DeviceCreation)
+    }
+
     fn create_device(
+        &self,
         factory: &IDXGIFactory7,
-        preference: DXGI_GPU_PREFERENCE,
-    ) -> Result<ID3D12Device9, Box<dyn std::error::Error>> {
-        for i in 0.. {
-            let adapter: IDXGIAdapter1 =
-                unsafe { factory.EnumAdapterByGpuPreference(i, preference) }?;
+    ) -> Result<(IDXGIAdapter1, ID3D12Device9), GimsError> {
+        let adapter_count = if self.hardware_adapters { u32::MAX } else { 0 };
+        for i in 0..adapter_count {
+            let adapter: windows::core::Result<IDXGIAdapter1> =
+                unsafe { factory.EnumAdapterByGpuPreference(i, self.gpu_preference) };
+            let Ok(adapter) = adapter else {
+                // No more adapters
+                break;
+            };
             let desc = unsafe { adapter.GetDesc1()? };
 
             if (DXGI_ADAPTER_FLAG(desc.Flags as _) & DXGI_ADAPTER_FLAG_SOFTWARE)
@@ -101,18 +280,316 @@ impl GPULib {
                 continue;
             }
 
-            let mut device_option: Option<ID3D12Device9> = None;
-            unsafe { D3D12CreateDevice(&adapter, D3D_FEATURE_LEVEL_11_0, &mut device_option) }?;
+            let luid = ((desc.AdapterLuid.HighPart as i64) << 32) | desc.AdapterLuid.LowPart as i64;
+            if self.adapter_luid.is_some_and(|requested| requested != luid) {
+                continue;
+            }
 
-            let device = device_option.ok_or("Failed to create device")?;
+            if let Some(device) = Self::create_device_on(&adapter) {
+                return Ok((adapter, device));
+            }
+        }
 
-            return Ok(device);
+        if self.warp_fallback {
+            let adapter: IDXGIAdapter1 =
+                unsafe { factory.EnumWarpAdapter() }.map_err(GimsError::DeviceCreation)?;
+            let device = Self::create_device_on(&adapter).ok_or("Failed to create WARP device")?;
+            return Ok((adapter, device));
         }
-        unreachable!();
+
+        Err(GimsError::NoSuitableAdapter)
+    }
+
+    fn create_device_on(adapter: &IDXGIAdapter1) -> Option<ID3D12Device9> {
+        let mut device_option: Option<ID3D12Device9> = None;
+        unsafe { D3D12CreateDevice(adapter, D3D_FEATURE_LEVEL_11_0, &mut device_option) }.ok()?;
+        device_option
+    }
+}
+
+impl GPULib {
+    /// Creates the library with the default settings of `GpuLibBuilder`
+    pub fn new() -> Result<Self, GimsError> {
+        GpuLibBuilder::default().create()
+    }
+
+    /// Creates the library on a hardware adapter, on the WARP software renderer, or on WARP if no hardware adapter
+    /// can create a device. WARP lets headless tests and screenshot generation run on CI runners and VMs without a GPU.
+    pub fn new_with_preference(hardware: bool, allow_warp: bool) -> Result<Self, GimsError> {
+        GpuLibBuilder::default()
+            .hardware_adapters(hardware)
+            .warp_fallback(allow_warp)
+            .create()
     }
+
+    /// Creates the library on the adapter with this locally unique identifier, see `enumerate_adapters`
+    pub fn new_with_adapter(luid: i64) -> Result<Self, GimsError> {
+        GpuLibBuilder::default().adapter_luid(Some(luid)).create()
+    }
+
+    /// Lists the hardware adapters in order of `DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE`,
+    /// e.g. to let the user choose between the integrated and the dedicated GPU of a laptop.
+    /// Adapters that cannot create a Direct3D 12 device are listed as well.
+    pub fn enumerate_adapters() -> Result<Vec<GpuInfo>, GimsError> {
+        let factory = unsafe { CreateDXGIFactory2::<IDXGIFactory7>(DXGI_CREATE_FACTORY_FLAGS(0)) }
+            .map_err(GimsError::DeviceCreation)?;
+
+        let mut adapters = Vec::new();
+        for i in 0.. {
+            let adapter: windows::core::Result<IDXGIAdapter1> = unsafe {
+                factory.EnumAdapterByGpuPreference(i, DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE)
+            };
+            let Ok(adapter) = adapter else {
+                // No more adapters
+                break;
+            };
+            let desc = unsafe { adapter.GetDesc1()? };
+            if (DXGI_ADAPTER_FLAG(desc.Flags as _) & DXGI_ADAPTER_FLAG_SOFTWARE)
+                != DXGI_ADAPTER_FLAG_NONE
+            {
+                continue;
+            }
+            adapters.push(GpuInfo::new(&adapter)?);
+        }
+        Ok(adapters)
+    }
+
+    /// Creates a command allocator and a command list of the given type, e.g. `D3D12_COMMAND_LIST_TYPE_COPY`
+    /// for `copy_queue`. The list is open for recording, so it can be used right away and has to be closed before
+    /// it is executed.
+    pub fn create_command_list(
+        &self,
+        command_list_type: D3D12_COMMAND_LIST_TYPE,
+    ) -> Result<(ID3D12CommandAllocator, ID3D12GraphicsCommandList), GimsError> {
+        let command_allocator: ID3D12CommandAllocator =
+            unsafe { self.device.CreateCommandAllocator(command_list_type) }?;
+        let command_list: ID3D12GraphicsCommandList = unsafe {
+            self.device
+                .CreateCommandList(0, command_list_type, &command_allocator, None)
+        }?;
+        Ok((command_allocator, command_list))
+    }
+
+    /// Highest feature level supported by the device
+    pub fn feature_level(&self) -> Result<D3D_FEATURE_LEVEL, GimsError> {
+        let requested_levels = [
+            D3D_FEATURE_LEVEL_12_2,
+            D3D_FEATURE_LEVEL_12_1,
+            D3D_FEATURE_LEVEL_12_0,
+            D3D_FEATURE_LEVEL_11_1,
+            D3D_FEATURE_LEVEL_11_0,
+        ];
+        let mut feature_levels = D3D12_FEATURE_DATA_FEATURE_LEVELS {
+            NumFeatureLevels: requested_levels.len() as u32,
+            pFeatureLevelsRequested: requested_levels.as_ptr(),
+            MaxSupportedFeatureLevel: D3D_FEATURE_LEVEL_11_0,
+        };
+        unsafe {
+            self.device.CheckFeatureSupport(
+                D3D12_FEATURE_FEATURE_LEVELS,
+                &mut feature_levels as *mut _ as _,
+                size_of::<D3D12_FEATURE_DATA_FEATURE_LEVELS>() as u32,
+            )
+        }?;
+
+        Ok(feature_levels.MaxSupportedFeatureLevel)
+    }
+
+    /// Highest root signature version supported by the device, either 1.1 or 1.0
+    pub fn root_signature_version(&self) -> D3D_ROOT_SIGNATURE_VERSION {
+        let mut root_signature = D3D12_FEATURE_DATA_ROOT_SIGNATURE {
+            HighestVersion: D3D_ROOT_SIGNATURE_VERSION_1_1,
+        };
+        let result = unsafe {
+            self.device.CheckFeatureSupport(
+                D3D12_FEATURE_ROOT_SIGNATURE,
+                &mut root_signature as *mut _ as _,
+                size_of::<D3D12_FEATURE_DATA_ROOT_SIGNATURE>() as u32,
+            )
+        };
+
+        // Older runtimes fail the query because they do not know version 1.1
+        match result {
+            Ok(()) => root_signature.HighestVersion,
+            Err(_) => D3D_ROOT_SIGNATURE_VERSION_1_0,
+        }
+    }
+
+    /// Maximum width and height of 2D textures supported by the device, which depends on its feature level
+    pub fn max_texture_dimension(&self) -> Result<u32, GimsError> {
+        let feature_level = self.feature_level()?;
+        Ok(if feature_level.0 >= D3D_FEATURE_LEVEL_11_0.0 {
+            D3D12_REQ_TEXTURE2D_U_OR_V_DIMENSION
+        } else if feature_level.0 >= D3D_FEATURE_LEVEL_10_0.0 {
+            8192
+        } else {
+            4096
+        })
+    }
+
+    /// Maximum size in bytes of a single resource, limited by the GPU virtual address bits per resource
+    pub fn max_resource_size(&self) -> Result<u64, GimsError> {
+        let mut support = D3D12_FEATURE_DATA_GPU_VIRTUAL_ADDRESS_SUPPORT::default();
+        unsafe {
+            self.device.CheckFeatureSupport(
+                D3D12_FEATURE_GPU_VIRTUAL_ADDRESS_SUPPORT,
+                &mut support as *mut _ as _,
+                size_of::<D3D12_FEATURE_DATA_GPU_VIRTUAL_ADDRESS_SUPPORT>() as u32,
+            )
+        }?;
+
+        Ok(1u64
+            .checked_shl(support.MaxGPUVirtualAddressBitsPerResource)
+            .unwrap_or(u64::MAX))
+    }
+
+    /// Fails with `GimsError::ResourceTooLarge` if a buffer of `size` bytes cannot be addressed by the GPU
+    pub fn validate_buffer_size(&self, size: u64) -> Result<(), GimsError> {
+        let max = self.max_resource_size()?;
+        if size > max {
+            return Err(GimsError::ResourceTooLarge {
+                requested: size,
+                max,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Queries the current video memory budget and usage of the local (dedicated) memory segment group
+    pub fn video_memory_info(&self) -> Result<DXGI_QUERY_VIDEO_MEMORY_INFO, GimsError> {
+        use windows::core::Interface;
+
+        let adapter: IDXGIAdapter3 = self.adapter.cast()?;
+        let info = unsafe { adapter.QueryVideoMemoryInfo(0, DXGI_MEMORY_SEGMENT_GROUP_LOCAL) }?;
+
+        Ok(info)
+    }
+
+    /// Blocks until the GPU has finished all work submitted to `queue` so far, after which every resource
+    /// used by that work can be released or recreated, e.g. after changing quality settings.
+    /// This stalls the CPU and leaves the GPU idle until new work is submitted, so use it sparingly
+    /// and never once per frame. Work submitted to other queues is not waited for.
+    pub fn wait_idle(&self) -> Result<(), GimsError> {
+        let mut fence = Fence::new(self)?;
+        fence.signal(&self.queue)?;
+        fence.wait_latest()
+    }
+
+    /// Like `wait_idle`, but gives up after `timeout`, e.g. when the GPU hangs. Returns whether the queue became idle.
+    pub fn wait_idle_timeout(&self, timeout: Duration) -> Result<bool, GimsError> {
+        let mut fence = Fence::new(self)?;
+        let value = fence.signal(&self.queue)?;
+        fence.wait_for_timeout(value, timeout)
+    }
+}
+
+/// Environment variable naming a directory into which `compile_shader` writes every compiled shader
+/// as `.dxil` blob and `.txt` disassembly, e.g. to inspect the output of the compiler without a separate toolchain
+pub const SHADER_DUMP_DIR_VAR: &str = "GIMSLIB_SHADER_DUMP_DIR";
+
+/// Compiles HLSL source code with DXC and signs the resulting DXIL, so it can be passed to pipeline creation
+pub fn compile_shader(source: &str, entry_point: &str, target: &str) -> Result<Vec<u8>, GimsError> {
+    compile_shader_with_defines(source, entry_point, target, &[], None)
+}
+
+/// Like `compile_shader`, with preprocessor `defines` as name and value pairs, e.g. `("SAMPLE_COUNT", "4")`.
+/// `#include "file.hlsli"` is resolved relative to `include_dir`, or to the working directory if it is `None`.
+pub fn compile_shader_with_defines(
+    source: &str,
+    entry_point: &str,
+    target: &str,
+    defines: &[(&str, &str)],
+    include_dir: Option<&std::path::Path>,
+) -> Result<Vec<u8>, GimsError> {
+    let include_dir = include_dir
+        .map(|include_dir| {
+            include_dir.to_str().ok_or_else(|| {
+                format!(
+                    "Shader include directory {} is not valid UTF-8",
+                    include_dir.display()
+                )
+            })
+        })
+        .transpose()?;
+    let args: Vec<&str> = include_dir
+        .into_iter()
+        .flat_map(|dir| ["-I", dir])
+        .collect();
+    let defines: Vec<(&str, Option<&str>)> = defines
+        .iter()
+        .map(|&(name, value)| (name, Some(value)))
+        .collect();
+
+    let mut bytecode =
+        hassle_rs::compile_hlsl("shader.hlsl", source, entry_point, target, &args, &defines)?;
+    if !hassle_rs::fake_sign_dxil_in_place(&mut bytecode) {
+        return Err(format!("Failed to sign shader {}", entry_point).into());
+    }
+
+    if let Some(directory) = std::env::var_os(SHADER_DUMP_DIR_VAR)
+        && let Err(error) = dump_shader(directory.as_ref(), source, entry_point, target, &bytecode)
+    {
+        eprintln!("Failed to dump shader {}: {}", entry_point, error);
+    }
+
+    Ok(bytecode)
+}
+
+/// Like `compile_shader`, but also returns the human readable disassembly of the DXIL
+pub fn compile_shader_with_disassembly(
+    source: &str,
+    entry_point: &str,
+    target: &str,
+) -> Result<(Vec<u8>, String), GimsError> {
+    let bytecode = compile_shader(source, entry_point, target)?;
+    let disassembly = disassemble_shader(&bytecode)?;
+
+    Ok((bytecode, disassembly))
+}
+
+/// Disassembles compiled DXIL with DXC
+pub fn disassemble_shader(bytecode: &[u8]) -> Result<String, GimsError> {
+    let dxc = hassle_rs::Dxc::new(None)?;
+    let compiler = dxc.create_compiler()?;
+    let library = dxc.create_library()?;
+
+    let blob = library.create_blob_with_encoding(bytecode)?;
+    let disassembly = compiler.disassemble(&blob.into())?;
+
+    Ok(library.get_blob_as_string(&disassembly.into())?)
+}
+
+/// Writes the DXIL blob and its disassembly, named after the entry point, target and a hash of the source
+fn dump_shader(
+    directory: &std::path::Path,
+    source: &str,
+    entry_point: &str,
+    target: &str,
+    bytecode: &[u8],
+) -> Result<(), GimsError> {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    source.hash(&mut hasher);
+    let name = format!("{}_{}_{:016x}", entry_point, target, hasher.finish());
+
+    std::fs::create_dir_all(directory)?;
+    std::fs::write(directory.join(format!("{}.dxil", name)), bytecode)?;
+    std::fs::write(
+        directory.join(format!("{}.txt", name)),
+        disassemble_shader(bytecode)?,
+    )?;
+
+    Ok(())
+}
+
+/// Checks whether the DirectX Shader Compiler library can be loaded, without compiling anything
+pub fn check_shader_compiler() -> Result<(), GimsError> {
+    hassle_rs::Dxc::new(None)?;
+    Ok(())
 }
 
-#[cfg(debug_assertions)]
 unsafe extern "system" fn debug_message_callback(
     _category_code: D3D12_MESSAGE_CATEGORY,
     severity_code: D3D12_MESSAGE_SEVERITY,
@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+
+use winit::{
+    dpi::PhysicalPosition,
+    event::{DeviceEvent, ElementState, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+    window::{CursorGrabMode, Window},
+};
+
+use crate::error::GimsError;
+
+/// Scroll distance of one line, used to convert the pixel deltas of touchpads
+const PIXELS_PER_LINE: f64 = 20.0;
+
+/// Keyboard and mouse state for apps that react to input directly, e.g. a fly camera.
+/// Only events that egui did not consume are applied, so typing into a text field does not move the camera.
+/// Releases are applied regardless, so keys do not get stuck when egui takes focus while they are held.
+///
+/// Keys are identified by their physical position, so WASD controls work on every keyboard layout.
+#[derive(Debug, Default)]
+pub struct InputState {
+    keys_held: HashSet<KeyCode>,
+    keys_pressed: HashSet<KeyCode>,
+    buttons_held: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    cursor_position: Option<PhysicalPosition<f64>>,
+    cursor_delta: [f64; 2],
+    mouse_motion: [f64; 2],
+    scroll_delta: [f32; 2],
+    /// Mouse motion is reported for all windows, so it is ignored while the window has no focus
+    unfocused: bool,
+}
+
+impl InputState {
+    /// Whether `key` is held down
+    pub fn key_held(&self, key: KeyCode) -> bool {
+        self.keys_held.contains(&key)
+    }
+
+    /// Whether `key` went down since the previous frame. Key repeats do not count.
+    pub fn key_pressed(&self, key: KeyCode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    /// Whether `button` is held down
+    pub fn button_held(&self, button: MouseButton) -> bool {
+        self.buttons_held.contains(&button)
+    }
+
+    /// Whether `button` went down since the previous frame
+    pub fn button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    /// Cursor position in physical pixels relative to the top left corner of the drawing area,
+    /// `None` while the cursor is outside the window
+    pub fn cursor_position(&self) -> Option<PhysicalPosition<f64>> {
+        self.cursor_position
+    }
+
+    /// Cursor movement in physical pixels since the previous frame
+    pub fn cursor_delta(&self) -> [f64; 2] {
+        self.cursor_delta
+    }
+
+    /// Raw mouse movement since the previous frame, e.g. to rotate a camera. Unlike `cursor_delta`,
+    /// it keeps being reported when the cursor is locked with `lock_cursor` or hits the border of the screen.
+    /// The unit depends on the mouse and its settings and is not affected by pointer acceleration.
+    pub fn mouse_motion(&self) -> [f64; 2] {
+        self.mouse_motion
+    }
+
+    /// Horizontal and vertical scrolling in lines since the previous frame, positive when scrolling up or right
+    pub fn scroll_delta(&self) -> [f32; 2] {
+        self.scroll_delta
+    }
+
+    /// Applies `event`. Events consumed by egui only release keys and buttons.
+    pub(crate) fn handle_event(&mut self, event: &WindowEvent, consumed: bool) {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => {
+                let PhysicalKey::Code(key) = event.physical_key else {
+                    return;
+                };
+                match event.state {
+                    ElementState::Pressed if !consumed && !event.repeat => {
+                        self.keys_held.insert(key);
+                        self.keys_pressed.insert(key);
+                    }
+                    ElementState::Released => {
+                        self.keys_held.remove(&key);
+                    }
+                    _ => {}
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed if !consumed => {
+                    self.buttons_held.insert(*button);
+                    self.buttons_pressed.insert(*button);
+                }
+                ElementState::Released => {
+                    self.buttons_held.remove(button);
+                }
+                _ => {}
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some(previous) = self.cursor_position
+                    && !consumed
+                {
+                    self.cursor_delta[0] += position.x - previous.x;
+                    self.cursor_delta[1] += position.y - previous.y;
+                }
+                self.cursor_position = Some(*position);
+            }
+            WindowEvent::CursorLeft { .. } => self.cursor_position = None,
+            WindowEvent::MouseWheel { delta, .. } if !consumed => {
+                let [x, y] = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => [*x, *y],
+                    MouseScrollDelta::PixelDelta(delta) => [
+                        (delta.x / PIXELS_PER_LINE) as f32,
+                        (delta.y / PIXELS_PER_LINE) as f32,
+                    ],
+                };
+                self.scroll_delta[0] += x;
+                self.scroll_delta[1] += y;
+            }
+            // Releases are not reported to unfocused windows
+            WindowEvent::Focused(false) => {
+                self.keys_held.clear();
+                self.buttons_held.clear();
+                self.unfocused = true;
+            }
+            WindowEvent::Focused(true) => self.unfocused = false,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (x, y) } = event
+            && !self.unfocused
+        {
+            self.mouse_motion[0] += x;
+            self.mouse_motion[1] += y;
+        }
+    }
+
+    /// Resets the per-frame state after the app has drawn
+    pub(crate) fn end_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.buttons_pressed.clear();
+        self.cursor_delta = [0.0; 2];
+        self.mouse_motion = [0.0; 2];
+        self.scroll_delta = [0.0; 2];
+    }
+}
+
+/// Hides the cursor and keeps it in place for camera controls, which read `InputState::mouse_motion` instead.
+/// Platforms that cannot lock the cursor confine it to the window instead. `false` releases and shows it again.
+pub fn lock_cursor(window: &Window, locked: bool) -> Result<(), GimsError> {
+    if locked {
+        window
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))?;
+    } else {
+        window.set_cursor_grab(CursorGrabMode::None)?;
+    }
+    window.set_cursor_visible(!locked);
+    Ok(())
+}
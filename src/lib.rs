@@ -1,40 +1,363 @@
+pub mod asset_loader;
+pub mod barrier;
+pub mod camera;
+pub mod error;
+pub mod fence;
 pub mod frame_data;
+pub mod gpu_timer;
 pub mod gpulib;
+pub mod input;
+pub mod loading;
+pub mod mesh;
+pub mod pipeline;
+pub mod pipeline_cache;
+mod render_thread;
+pub mod root_signature;
 mod running_state;
+pub mod sampler;
+pub mod settings;
+#[cfg(feature = "shader-reload")]
+pub mod shader_reload;
+pub mod texture;
+pub mod upload_arena;
 pub mod vector_constant_buffer;
 
-use std::{cell::OnceCell, sync::Arc};
+pub use running_state::{
+    egui_renderer::{PaintCallback, PaintCallbackInfo},
+    texture_manager::EguiUserTextures,
+};
+
+use std::{
+    cell::{Cell, OnceCell},
+    path::PathBuf,
+    sync::Arc,
+};
 
 use windows::{
     Win32::{
-        Foundation::RECT,
-        Graphics::Direct3D12::*,
+        Foundation::{HWND, RECT},
+        Graphics::{
+            Direct3D12::*,
+            Dxgi::{
+                Common::{
+                    DXGI_ALPHA_MODE, DXGI_ALPHA_MODE_IGNORE, DXGI_ALPHA_MODE_PREMULTIPLIED,
+                    DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM,
+                    DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT,
+                },
+                DXGI_MAX_SWAP_CHAIN_BUFFERS, DXGI_PRESENT, DXGI_SCALING, DXGI_SCALING_STRETCH,
+                DXGI_SWAP_EFFECT, DXGI_SWAP_EFFECT_FLIP_DISCARD, DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL,
+            },
+        },
         UI::WindowsAndMessaging::{MB_ICONERROR, MessageBoxW},
     },
     core::{HSTRING, h},
 };
 use winit::{
-    event::WindowEvent,
-    event_loop::{ActiveEventLoop, EventLoop},
-    window::WindowAttributes,
+    event::{DeviceEvent, DeviceId, StartCause, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    raw_window_handle::{HasWindowHandle, RawWindowHandle},
+    window::{Fullscreen, WindowAttributes},
 };
 
+use error::GimsError;
 use frame_data::FrameData;
-use gpulib::GPULib;
-use running_state::RunningState;
+use gpulib::{GPULib, GpuLibBuilder};
+use input::InputState;
+use loading::{LoadingApp, LoadingProgress};
+use render_thread::RenderThreadRunner;
+use running_state::{
+    RunningState,
+    blitter::Blitter,
+    capture::{self, CaptureRequest},
+};
+use upload_arena::UploadArena;
 
+/// Everything the app needs to record a frame.
+///
+/// Interop with other renderers, e.g. a video decoder drawing into the same window, goes through `window`,
+/// `hwnd` and the public fields of `GPULib`. Work recorded into `command_list` or submitted to `GPULib::queue`
+/// during `App::draw` is ordered before the present of the frame. Other threads may use the device at any time,
+/// but must not submit to the queue or touch the swapchain while a frame is recorded.
 pub struct FrameResources<'a> {
     pub command_list: &'a ID3D12GraphicsCommandList10,
+    /// The window the frame is drawn into
+    pub window: &'a winit::window::Window,
     pub render_target: &'a ID3D12Resource,
+    /// UNORM view of the render target, which stores shader output unchanged. See `clear` and `clear_srgb`.
     pub render_target_handle: D3D12_CPU_DESCRIPTOR_HANDLE,
+    /// UNORM_SRGB view of the render target, which gamma encodes linear shader output when writing it.
+    /// HDR formats have no sRGB variant, so with them this view stores the output unchanged as well.
     pub render_target_handle_srgb: D3D12_CPU_DESCRIPTOR_HANDLE,
+    /// Format of `render_target_handle`, which pipelines drawing through it have to use, see `AppConfig::swapchain_format`
+    pub render_target_format: DXGI_FORMAT,
+    /// Format of `render_target_handle_srgb`
+    pub render_target_format_srgb: DXGI_FORMAT,
     pub viewport: D3D12_VIEWPORT,
     pub scissor: RECT,
+    /// The egui context used for the UI, `None` if egui is disabled with `AppConfig::enable_egui`.
+    /// It can be cloned and stored, for example to call `request_repaint` from a background thread once async work completes.
+    /// `egui::Context` is internally reference counted and locked, so clones are `Send + Sync`.
+    pub egui_context: Option<&'a egui::Context>,
+    /// Flags passed to `IDXGISwapChain::Present` at the end of this frame, e.g. `DXGI_PRESENT_DO_NOT_SEQUENCE`.
+    /// They are reset to the default after every frame. While the window is occluded, rendering pauses
+    /// and the library polls with `DXGI_PRESENT_TEST` until the window is visible again.
+    pub present_flags: &'a Cell<DXGI_PRESENT>,
+    /// Vsync and tearing settings, initialized with `AppConfig::present` and kept across frames.
+    /// Changes take effect with the present at the end of this frame, e.g. to toggle vsync from a checkbox:
+    /// ```ignore
+    /// let mut present = res.present_config.get();
+    /// present.sync_interval = self.vsync as u32;
+    /// res.present_config.set(present);
+    /// ```
+    pub present_config: &'a Cell<PresentConfig>,
+    /// Scratch upload memory for data that is only used in this frame, reset once the GPU has finished the frame
+    pub upload_arena: &'a UploadArena,
+    /// CPU handles of the RTV slots reserved with `AppConfig::extra_render_target_views`.
+    /// Write the view of an offscreen render target with `CreateRenderTargetView` into one of these slots.
+    /// RTV heaps are not shader visible, so rewriting a slot every frame before binding it is fine.
+    pub extra_render_target_handles: &'a [D3D12_CPU_DESCRIPTOR_HANDLE],
+    /// Multisampled color target if `AppConfig::sample_count` is larger than 1. It is resolved into `render_target`
+    /// after `App::draw`, so the app draws into it instead of the render target.
+    pub msaa_target: Option<&'a MsaaTarget>,
+    /// CPU handles of the DSV slots reserved with `AppConfig::depth_stencil_views`, usable like the RTV slots
+    pub depth_stencil_handles: &'a [D3D12_CPU_DESCRIPTOR_HANDLE],
+    /// Textures of the UI including this frame's updates, `None` if egui is disabled.
+    /// To show them in the UI, store them for the next call of `App::record_ui`.
+    pub egui_texture_stats: Option<EguiTextureStats>,
+    /// Registry of app textures shown in the UI, `None` if egui is disabled
+    pub egui_user_textures: Option<&'a EguiUserTextures>,
+    /// Whether the window is minimized, detected from a zero client area
+    pub minimized: bool,
+    /// Whether the window is fully hidden, e.g. by other windows, as reported by `WindowEvent::Occluded`
+    pub occluded: bool,
+    /// Modifier keys held down at the start of the frame. Tracked even while egui has keyboard focus,
+    /// so app shortcuts can check `egui::Context::wants_keyboard_input` themselves.
+    pub modifiers: winit::keyboard::ModifiersState,
+    /// Keys, mouse buttons, cursor and scrolling since the previous frame, without the input consumed by egui
+    pub input: &'a InputState,
+    /// Time since the previous frame and since the start of the app, to drive animations
+    pub timing: FrameTiming,
+    /// GPU time of the frame command list from `frame_count` frames ago in milliseconds, see `gpu_timer::GpuTimer`.
+    /// Command lists the app submits itself are not included.
+    pub gpu_frame_ms: f64,
+    /// Counts the command lists, draws and dispatches of the frame for the frame stats of the settings panel
+    pub submission_counter: &'a settings::SubmissionCounter,
+    blitter: &'a Blitter,
+    capture: &'a CaptureRequest,
+}
+
+/// Multisampled color target created by the swapchain, see `AppConfig::sample_count`.
+/// It has the size of the window and is recreated when the window is resized.
+pub struct MsaaTarget {
+    pub resource: ID3D12Resource,
+    /// UNORM view, which stores shader output unchanged
+    pub render_target_handle: D3D12_CPU_DESCRIPTOR_HANDLE,
+    /// UNORM_SRGB view, which gamma encodes linear shader output when writing it
+    pub render_target_handle_srgb: D3D12_CPU_DESCRIPTOR_HANDLE,
+    /// Samples per pixel, which pipelines drawing into the target have to use,
+    /// see `GraphicsPipelineBuilder::sample_count`. May be lower than requested if the GPU does not support it.
+    pub sample_count: u32,
+    /// Holds both views
+    pub(crate) _heap: ID3D12DescriptorHeap,
+    /// State at the end of the last recorded frame
+    pub(crate) state: D3D12_RESOURCE_STATES,
+}
+
+/// Timing of the frame being recorded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameTiming {
+    /// Time between the start of the previous frame and this one, zero for the first frame
+    pub delta: std::time::Duration,
+    /// Time since the running state was created, measured at the start of this frame
+    pub elapsed: std::time::Duration,
+    /// Number of frames drawn before this one
+    pub frame_index: u64,
+}
+
+/// Textures owned by the egui renderer, e.g. for a debug overlay watching the font atlas grow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EguiTextureStats {
+    /// Textures created by egui itself, including the font atlas
+    pub managed_textures: usize,
+    /// Textures registered by the app
+    pub user_textures: usize,
+    /// Width and height of the font atlas, if it has been uploaded yet
+    pub font_atlas_size: Option<[usize; 2]>,
+    /// Video memory occupied by all UI textures
+    pub allocated_bytes: u64,
+}
+
+impl FrameResources<'_> {
+    /// The Win32 handle of the window, e.g. to create additional swapchains for it
+    pub fn hwnd(&self) -> Result<HWND, GimsError> {
+        window_hwnd(self.window)
+    }
+
+    /// Hides and locks the cursor for camera controls, or releases it again, see `input::lock_cursor`
+    pub fn lock_cursor(&self, locked: bool) -> Result<(), GimsError> {
+        input::lock_cursor(self.window, locked)
+    }
+
+    /// Switches between a borderless fullscreen window on the current monitor and the normal window.
+    /// The swapchain is resized once the window reports its new size, before `App::resized` is called.
+    pub fn toggle_fullscreen(&self) {
+        toggle_fullscreen(self.window);
+    }
+
+    /// Clears the render target through the UNORM view, so `color` is stored unchanged.
+    /// With multisampling, the multisampled target is cleared instead.
+    /// Use this for colors that are already gamma encoded, like hex colors copied from an image editor
+    /// or the `Color32` values of egui divided by 255.
+    ///
+    /// ```ignore
+    /// // Both fill the window with the same mid gray
+    /// res.clear([0.5, 0.5, 0.5, 1.0]);
+    /// res.clear_srgb([0.214, 0.214, 0.214, 1.0]);
+    /// ```
+    pub fn clear(&self, color: [f32; 4]) {
+        unsafe {
+            self.command_list.ClearRenderTargetView(
+                self.msaa_target
+                    .map_or(self.render_target_handle, |msaa| msaa.render_target_handle),
+                &color,
+                None,
+            )
+        };
+    }
+
+    /// Clears the render target through the UNORM_SRGB view, so the linear `color` is gamma encoded
+    /// like the output of shaders that render through `render_target_handle_srgb`.
+    /// Use this for colors in linear space, e.g. computed by lighting code or edited with egui's
+    /// `color_edit_button_rgba_unmultiplied`, whose `[f32; 4]` values are linear.
+    pub fn clear_srgb(&self, color: [f32; 4]) {
+        unsafe {
+            self.command_list.ClearRenderTargetView(
+                self.msaa_target
+                    .map_or(self.render_target_handle_srgb, |msaa| {
+                        msaa.render_target_handle_srgb
+                    }),
+                &color,
+                None,
+            )
+        };
+    }
+
+    /// Draws `source`, e.g. an offscreen render target, scaled over the whole render target of the frame.
+    /// `source` is transitioned from `state` to `D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE` and back to `state`
+    /// afterwards, so "render offscreen, then present" needs no manual barriers. Its view is created without a
+    /// description, so typeless formats are not supported. Render target, viewport, pipeline and descriptor heaps
+    /// of `command_list` are changed. With multisampling, the render target is overwritten by the resolve
+    /// of `msaa_target` after `App::draw`, so blits only show if they draw into the multisampled target themselves.
+    pub fn blit_to_render_target(
+        &self,
+        source: &ID3D12Resource,
+        state: D3D12_RESOURCE_STATES,
+    ) -> Result<(), GimsError> {
+        self.blitter.blit(self, source, state)
+    }
+
+    /// Binds a compute pipeline, e.g. from `pipeline::ComputePipelineBuilder`, to the command list of the frame.
+    /// Compute and graphics share the pipeline state, so set a graphics pipeline again before drawing.
+    /// The root signatures are separate, so graphics root arguments are kept, but root arguments of the compute
+    /// root signature have to be set after this call with the `SetComputeRoot*` methods.
+    pub fn set_compute_pipeline(
+        &self,
+        root_signature: &ID3D12RootSignature,
+        pipeline: &ID3D12PipelineState,
+    ) {
+        unsafe {
+            self.command_list.SetComputeRootSignature(root_signature);
+            self.command_list.SetPipelineState(pipeline);
+        }
+    }
+
+    /// Runs the bound compute pipeline with `thread_groups` groups along x, y and z, and counts the dispatch.
+    /// UAVs in descriptor tables have to be in a shader visible `D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV` heap
+    /// set with `SetDescriptorHeaps` beforehand, buffers can also be bound without a heap as root UAVs with
+    /// `SetComputeRootUnorderedAccessView`. Buffers in the common state are promoted to unordered access implicitly.
+    /// Record `barrier::uav` before later passes read what the dispatch wrote, and a transition to
+    /// e.g. `D3D12_RESOURCE_STATE_VERTEX_AND_CONSTANT_BUFFER` before drawing with it.
+    pub fn dispatch(&self, thread_groups: [u32; 3]) {
+        let [x, y, z] = thread_groups;
+        unsafe { self.command_list.Dispatch(x, y, z) };
+        self.submission_counter.add_dispatches(1);
+    }
+
+    /// Copies the render target into CPU memory once the frame is complete, including the UI,
+    /// and passes it to `App::frame_captured` before the next frame starts.
+    /// Fails if the render target format does not fit into an 8 bit RGBA image, e.g. with HDR formats.
+    pub fn capture_frame(&self) -> Result<(), GimsError> {
+        capture::is_bgra(self.render_target_format)?;
+        self.capture.request();
+        Ok(())
+    }
+
+    /// Saves the finished frame, including the UI, to `path` once it is complete.
+    /// The image format follows the extension, e.g. `.png`. Failing to write the file is reported on the console.
+    /// Fails for render target formats not supported by `capture_frame`.
+    pub fn save_screenshot(&self, path: impl Into<PathBuf>) -> Result<(), GimsError> {
+        capture::is_bgra(self.render_target_format)?;
+        self.capture.request_file(path.into());
+        Ok(())
+    }
+}
+
+/// Switches `window` between borderless fullscreen and windowed
+pub(crate) fn toggle_fullscreen(window: &winit::window::Window) {
+    let fullscreen = match window.fullscreen() {
+        Some(_) => None,
+        None => Some(Fullscreen::Borderless(None)),
+    };
+    window.set_fullscreen(fullscreen);
+}
+
+/// Extracts the Win32 handle of a winit window
+pub(crate) fn window_hwnd(window: &winit::window::Window) -> Result<HWND, GimsError> {
+    let RawWindowHandle::Win32(window_handle) = window.window_handle()?.as_raw() else {
+        return Err("Failed to get Win32 window handle".into());
+    };
+
+    Ok(HWND(window_handle.hwnd.get() as *mut std::ffi::c_void))
 }
 
 pub trait App {
-    fn record_ui(&mut self, ctx: &egui::Context);
+    /// Builds the UI of the frame. Not called if egui is disabled with `AppConfig::enable_egui`.
+    /// The mouse cursor follows the icon requested by egui. To show a custom cursor, e.g. a crosshair over the scene,
+    /// request it every frame with `ctx.set_cursor_icon`, otherwise egui resets it to the default.
+    fn record_ui(&mut self, _ctx: &egui::Context) {}
     fn draw(&mut self, frame_resources: &FrameResources) -> Result<(), Box<dyn std::error::Error>>;
+    /// Called when the window gets hidden by minimizing or occlusion, or becomes visible again.
+    /// Frames are not drawn while the swapchain reports the window as occluded, so apps that pause
+    /// simulation or audio in the background should do so here instead of waiting for the next `draw`.
+    /// `FrameResources::minimized` and `FrameResources::occluded` hold the individual states.
+    fn visibility_changed(&mut self, _hidden: bool) {}
+    /// Called after the swapchain has been resized to the new client area in physical pixels,
+    /// e.g. to recreate depth buffers or other size dependent resources. The GPU is idle at this point,
+    /// so resources used by previous frames can be replaced right away. Not called while the window is minimized.
+    fn resized(&mut self, _width: u32, _height: u32) {}
+    /// Called once when the GPU the app runs on can no longer be used, e.g. because an external GPU was unplugged
+    /// or the driver was reset after a timeout (`GimsError::DeviceRemoved`).
+    /// No further frames are drawn and the event loop exits afterwards, so this is the place to save unsaved work.
+    /// GPU resources of the app can still be dropped, but any other use of the device fails.
+    fn device_lost(&mut self) {}
+    /// Receives the frame requested with `FrameResources::capture_frame` after it has been presented.
+    /// The pixels are gamma encoded like the window shows them, and opaque unless the swapchain blends
+    /// with premultiplied alpha, see `AppConfig::swapchain_alpha_mode`.
+    fn frame_captured(&mut self, _image: image::RgbaImage) {}
+}
+
+/// Events sent to the event loop from outside of winit
+#[derive(Debug)]
+enum UserEvent {
+    #[cfg(feature = "accesskit")]
+    AccessKit(egui_winit::accesskit_winit::Event),
+}
+
+#[cfg(feature = "accesskit")]
+impl From<egui_winit::accesskit_winit::Event> for UserEvent {
+    fn from(event: egui_winit::accesskit_winit::Event) -> Self {
+        UserEvent::AccessKit(event)
+    }
 }
 
 /// The winit application struct
@@ -45,29 +368,38 @@ struct AppRunner<T, F> {
     running_state: OnceCell<RunningState<T>>,
     /// Settings like window title
     app_config: AppConfig,
+    /// Library created by the user before the event loop started. If `None`, it is created from `AppConfig::gpu`.
+    lib: Option<Arc<GPULib>>,
+    /// Used by the AccessKit adapter to send its events to the event loop
+    #[cfg(feature = "accesskit")]
+    event_loop_proxy: winit::event_loop::EventLoopProxy<UserEvent>,
 }
 
 impl<T, F> AppRunner<T, F>
 where
     T: App,
-    F: FnOnce(Arc<GPULib>) -> T,
+    F: FnOnce(Arc<GPULib>) -> Result<T, Box<dyn std::error::Error>>,
 {
-    fn try_initialize_app(
-        &mut self,
-        event_loop: &ActiveEventLoop,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    fn try_initialize_app(&mut self, event_loop: &ActiveEventLoop) -> Result<(), GimsError> {
         let window = event_loop.create_window(
-            WindowAttributes::default()
-                .with_title(self.app_config.window_title.as_str())
-                .with_inner_size(self.app_config.window_size),
+            self.app_config
+                .window_attributes()
+                // The AccessKit adapter has to be created before the window becomes visible
+                .with_visible(!cfg!(feature = "accesskit")),
         )?;
-        let lib = Arc::new(GPULib::new()?);
+        let lib = match self.lib.take() {
+            Some(lib) => lib,
+            None => self.app_config.gpu.build()?,
+        };
         let app_creator = self
             .app_creator
             .take()
             .ok_or("Application cannot be initialized twice")?;
-        let app = (app_creator)(lib.clone());
-        let running_state = RunningState::new(window, lib, app, self.app_config.frame_count)?;
+        let app = (app_creator)(lib.clone())?;
+        #[allow(unused_mut)]
+        let mut running_state = RunningState::new(window, lib, app, &self.app_config)?;
+        #[cfg(feature = "accesskit")]
+        running_state.init_accesskit(event_loop, self.event_loop_proxy.clone());
 
         self.running_state
             .set(running_state)
@@ -77,10 +409,24 @@ where
     }
 }
 
-impl<T, F> winit::application::ApplicationHandler for AppRunner<T, F>
+/// Prints the error and shows it in a message box, since the window may not exist yet
+fn report_initialization_error(error: &dyn std::error::Error) {
+    let error_message = format!("{}", error);
+    println!("Error while initializing application:\n{}", error_message);
+    unsafe {
+        MessageBoxW(
+            None,
+            &HSTRING::from(error_message),
+            h!("Error while initializing application"),
+            MB_ICONERROR,
+        )
+    };
+}
+
+impl<T, F> winit::application::ApplicationHandler<UserEvent> for AppRunner<T, F>
 where
     T: App,
-    F: FnOnce(Arc<GPULib>) -> T,
+    F: FnOnce(Arc<GPULib>) -> Result<T, Box<dyn std::error::Error>>,
 {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         // Only create the running state once
@@ -89,20 +435,55 @@ where
         }
 
         if let Err(error) = self.try_initialize_app(event_loop) {
-            let error_message = format!("{}", error);
-            println!("Error while initializing application:\n{}", error_message);
-            unsafe {
-                MessageBoxW(
-                    None,
-                    &HSTRING::from(error_message),
-                    h!("Error while initializing application"),
-                    MB_ICONERROR,
-                )
-            };
+            report_initialization_error(&error);
             event_loop.exit();
         }
     }
 
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: UserEvent) {
+        match event {
+            #[cfg(feature = "accesskit")]
+            UserEvent::AccessKit(event) => {
+                if let Some(running_state) = self.running_state.get_mut() {
+                    running_state.accesskit_event(&event);
+                }
+            }
+        }
+    }
+
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        // The repaint delay requested by egui has elapsed
+        if let StartCause::ResumeTimeReached { .. } = cause
+            && let Some(running_state) = self.running_state.get()
+        {
+            running_state.request_redraw();
+        }
+    }
+
+    fn device_event(
+        &mut self,
+        _event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
+        event: DeviceEvent,
+    ) {
+        if let Some(running_state) = self.running_state.get_mut() {
+            running_state.device_event(&event);
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        // Continuous rendering requests its redraws itself, the wakeup time is only needed
+        // in reactive mode or to poll an occluded window
+        match self
+            .running_state
+            .get()
+            .and_then(|state| state.repaint_at())
+        {
+            Some(repaint_at) => event_loop.set_control_flow(ControlFlow::WaitUntil(repaint_at)),
+            None => event_loop.set_control_flow(ControlFlow::Wait),
+        }
+    }
+
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
@@ -110,15 +491,51 @@ where
         event: WindowEvent,
     ) {
         match event {
-            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::CloseRequested => {
+                if let Some(running_state) = self.running_state.get_mut()
+                    && let Err(error) = running_state.shutdown()
+                {
+                    println!(
+                        "Error while waiting for the GPU during shutdown:\n{}",
+                        error
+                    );
+                }
+                event_loop.exit();
+            }
             WindowEvent::RedrawRequested => {
                 if let Some(running_state) = self.running_state.get_mut() {
-                    running_state.draw().unwrap();
+                    match running_state.draw() {
+                        // The app has been notified through `App::device_lost`
+                        Err(error @ GimsError::DeviceRemoved(_)) => println!("{}", error),
+                        // The GPU may still finish, but the app cannot continue with a hung frame
+                        Err(error @ GimsError::GpuTimeout(_)) => {
+                            println!("{}", error);
+                            event_loop.exit();
+                        }
+                        result => result.unwrap(),
+                    }
+                    if running_state.is_device_lost() {
+                        event_loop.exit();
+                    }
                 }
             }
             event => {
                 if let Some(running_state) = self.running_state.get_mut() {
-                    running_state.event(&event);
+                    match running_state.event(&event) {
+                        Ok(()) => {}
+                        // The app has been notified through `App::device_lost`
+                        Err(error @ GimsError::DeviceRemoved(_)) => {
+                            println!("{}", error);
+                            event_loop.exit();
+                        }
+                        Err(error) => {
+                            println!("Error while handling a window event:\n{}", error);
+                            event_loop.exit();
+                        }
+                    }
+                    if running_state.is_device_lost() {
+                        event_loop.exit();
+                    }
                 }
             }
         }
@@ -144,14 +561,110 @@ impl From<WindowSize> for winit::dpi::Size {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Redraw as fast as presentation allows
+    Continuous,
+    /// Only redraw after input or when egui requests a repaint, e.g. for animations.
+    /// Background threads can wake the UI with `egui::Context::request_repaint`.
+    Reactive,
+}
+
+/// Presentation settings of a window. Every window has its own swapchain, so windows can use different settings,
+/// e.g. a vsynced editor window next to an uncapped benchmark window. Presents of all windows are serialized
+/// on the queue of the shared `GPULib`. No frame latency waitable object is used, so the latency of each window
+/// is bounded by `AppConfig::frame_count` and `AppConfig::swapchain_buffer_count`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresentConfig {
+    /// Number of vertical blanks to wait for per present, between 0 and 4. 0 disables vsync.
+    pub sync_interval: u32,
+    /// Allows tearing for presents without vsync, which variable refresh rate displays need to refresh immediately.
+    /// Ignored if the system does not support it.
+    pub allow_tearing: bool,
+}
+
+impl Default for PresentConfig {
+    /// Vsync without tearing
+    fn default() -> Self {
+        PresentConfig {
+            sync_interval: 1,
+            allow_tearing: false,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     /// Window title
     pub window_title: String,
     /// Width and height of the drawing area
     pub window_size: WindowSize,
-    /// Number of swapchain frames in flight
+    /// Whether the window has a title bar and border. Windows without them can be moved with `Window::drag_window`.
+    pub window_decorations: bool,
+    /// Whether the window starts as a borderless fullscreen window on its monitor, see `FrameResources::toggle_fullscreen`
+    pub fullscreen: bool,
+    /// Key that toggles borderless fullscreen, F11 by default. `None` leaves all keys to the app.
+    /// Presses consumed by egui, e.g. while typing into a text field, do not toggle.
+    pub fullscreen_key: Option<winit::keyboard::KeyCode>,
+    /// Number of frames the CPU may record ahead of the GPU, each with its own command list,
+    /// between 2 and `DXGI_MAX_SWAP_CHAIN_BUFFERS`.
     pub frame_count: usize,
+    /// Number of buffers in the swapchain, between 2 and `DXGI_MAX_SWAP_CHAIN_BUFFERS`.
+    /// Independent of `frame_count`, but with fewer buffers than `frame_count + 1` presenting blocks
+    /// before all frames in flight can be used. Double buffering has less latency,
+    /// triple buffering keeps the GPU busy when frame times vary.
+    pub swapchain_buffer_count: u32,
+    /// Samples per pixel of the multisampled target the app draws into, 1, 2, 4 or 8.
+    /// 1 disables multisampling. Counts the GPU does not support fall back to the highest supported count.
+    /// See `FrameResources::msaa_target`.
+    pub sample_count: u32,
+    /// Whether to redraw continuously or only when needed
+    pub render_mode: RenderMode,
+    /// Vsync and tearing settings of the window
+    pub present: PresentConfig,
+    /// Whether to create the egui overlay. Disabling it saves the UI pipeline, the font atlas and the per-frame UI work
+    /// for apps that draw no UI. The loading screen of `run_app_with_loading_screen` needs egui.
+    pub enable_egui: bool,
+    /// Whether to show the built-in settings panel of `settings::show_settings` on top of the app's UI.
+    /// Requires egui.
+    pub settings_panel: bool,
+    /// Maximum width and height of egui textures like the font atlas.
+    /// `None` uses the maximum supported by the GPU. Larger values are clamped to that maximum.
+    pub egui_max_texture_side: Option<usize>,
+    /// How the back buffer is stretched when its size differs from the window, e.g. `DXGI_SCALING_NONE`
+    /// to keep it anchored to the top left corner. Must be `DXGI_SCALING_STRETCH` with premultiplied alpha.
+    pub swapchain_scaling: DXGI_SCALING,
+    /// How the alpha channel of the back buffer is interpreted by the desktop compositor.
+    /// `DXGI_ALPHA_MODE_PREMULTIPLIED` creates a transparent window, whose swapchain is presented
+    /// through DirectComposition instead of being bound to the window directly. Other modes than
+    /// `DXGI_ALPHA_MODE_IGNORE` and `DXGI_ALPHA_MODE_PREMULTIPLIED` are not supported.
+    /// The alpha written by the app reaches the compositor unchanged, so clear with a premultiplied color
+    /// like `[0.0, 0.0, 0.0, 0.0]` to see the desktop through the window.
+    pub swapchain_alpha_mode: DXGI_ALPHA_MODE,
+    /// Format of the back buffers: `DXGI_FORMAT_R8G8B8A8_UNORM` (default), `DXGI_FORMAT_B8G8R8A8_UNORM`,
+    /// or for HDR displays `DXGI_FORMAT_R10G10B10A2_UNORM` with the HDR10 color space, which expects PQ encoded
+    /// BT.2020 colors, and `DXGI_FORMAT_R16G16B16A16_FLOAT` with the scRGB color space, which expects linear BT.709
+    /// colors where 1.0 is 80 nits. Displays without HDR support show HDR formats in the sRGB color space.
+    /// The UI is drawn with gamma encoded colors, which look too dark in both HDR color spaces.
+    pub swapchain_format: DXGI_FORMAT,
+    /// Presentation model of the swapchain. Only the flip model effects are supported.
+    /// `DXGI_SWAP_EFFECT_FLIP_DISCARD` (default) leaves the back buffer contents undefined after presenting,
+    /// which lets the compositor skip copies. `DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL` preserves the contents
+    /// of each buffer, e.g. to read back or capture a presented frame, at the cost of some presentation
+    /// optimizations. Apps still have to redraw every frame, as the next frame uses a different buffer.
+    pub swap_effect: DXGI_SWAP_EFFECT,
+    /// Number of additional RTV descriptors reserved for offscreen render targets of the app
+    pub extra_render_target_views: u32,
+    /// Number of DSV descriptors reserved for depth buffers of the app
+    pub depth_stencil_views: u32,
+    /// Initial size in bytes of the upload arena of each frame in flight. It grows if a frame needs more.
+    pub upload_arena_size: u64,
+    /// Settings for adapter selection and device creation
+    pub gpu: GpuLibBuilder,
+    /// Longest time to wait for the GPU to finish a frame before failing with `GimsError::GpuTimeout`,
+    /// e.g. to get an error instead of a frozen window while debugging shaders that hang the GPU.
+    /// `None` waits indefinitely.
+    pub frame_timeout: Option<std::time::Duration>,
 }
 
 impl Default for AppConfig {
@@ -159,23 +672,227 @@ impl Default for AppConfig {
         AppConfig {
             window_title: "gimslib-rs window".to_string(),
             window_size: WindowSize::Logical(1024, 768),
+            window_decorations: true,
+            fullscreen: false,
+            fullscreen_key: Some(winit::keyboard::KeyCode::F11),
             frame_count: 2,
+            swapchain_buffer_count: 3,
+            sample_count: 1,
+            render_mode: RenderMode::Continuous,
+            present: PresentConfig::default(),
+            swapchain_scaling: DXGI_SCALING_STRETCH,
+            swapchain_alpha_mode: DXGI_ALPHA_MODE_IGNORE,
+            swap_effect: DXGI_SWAP_EFFECT_FLIP_DISCARD,
+            swapchain_format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            enable_egui: true,
+            settings_panel: false,
+            egui_max_texture_side: None,
+            extra_render_target_views: 0,
+            depth_stencil_views: 0,
+            upload_arena_size: 1 << 20,
+            gpu: GpuLibBuilder::default(),
+            frame_timeout: None,
         }
     }
 }
 
+impl AppConfig {
+    /// Checks the settings before any window or GPU resource is created, so misconfigurations
+    /// result in a descriptive error instead of a failure deep inside swapchain creation
+    pub fn validate(&self) -> Result<(), GimsError> {
+        if !(2..=DXGI_MAX_SWAP_CHAIN_BUFFERS as usize).contains(&self.frame_count) {
+            return Err(format!(
+                "AppConfig::frame_count must be between 2 and {}, but is {}",
+                DXGI_MAX_SWAP_CHAIN_BUFFERS, self.frame_count
+            )
+            .into());
+        }
+
+        let (WindowSize::Physical(width, height) | WindowSize::Logical(width, height)) =
+            self.window_size;
+        if width == 0 || height == 0 {
+            return Err(format!(
+                "AppConfig::window_size must not be zero, but is {}x{}",
+                width, height
+            )
+            .into());
+        }
+
+        if !(2..=DXGI_MAX_SWAP_CHAIN_BUFFERS).contains(&self.swapchain_buffer_count) {
+            return Err(format!(
+                "AppConfig::swapchain_buffer_count must be between 2 and {} for flip model swapchains, but is {}",
+                DXGI_MAX_SWAP_CHAIN_BUFFERS, self.swapchain_buffer_count
+            )
+            .into());
+        }
+
+        if ![1, 2, 4, 8].contains(&self.sample_count) {
+            return Err(format!(
+                "AppConfig::sample_count must be 1, 2, 4 or 8, but is {}",
+                self.sample_count
+            )
+            .into());
+        }
+
+        if self.swap_effect != DXGI_SWAP_EFFECT_FLIP_DISCARD
+            && self.swap_effect != DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL
+        {
+            return Err(format!(
+                "AppConfig::swap_effect must be DXGI_SWAP_EFFECT_FLIP_DISCARD or DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL, but is {:?}",
+                self.swap_effect
+            )
+            .into());
+        }
+
+        if ![
+            DXGI_FORMAT_R8G8B8A8_UNORM,
+            DXGI_FORMAT_B8G8R8A8_UNORM,
+            DXGI_FORMAT_R10G10B10A2_UNORM,
+            DXGI_FORMAT_R16G16B16A16_FLOAT,
+        ]
+        .contains(&self.swapchain_format)
+        {
+            return Err(format!(
+                "AppConfig::swapchain_format must be DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_R10G10B10A2_UNORM or DXGI_FORMAT_R16G16B16A16_FLOAT, but is {:?}",
+                self.swapchain_format
+            )
+            .into());
+        }
+
+        if self.settings_panel && !self.enable_egui {
+            return Err("AppConfig::settings_panel requires AppConfig::enable_egui".into());
+        }
+
+        if self.present.sync_interval > 4 {
+            return Err(format!(
+                "AppConfig::present.sync_interval must be between 0 and 4, but is {}",
+                self.present.sync_interval
+            )
+            .into());
+        }
+
+        if self.swapchain_alpha_mode == DXGI_ALPHA_MODE_PREMULTIPLIED {
+            // Composition swapchains can only be stretched
+            if self.swapchain_scaling != DXGI_SCALING_STRETCH {
+                return Err(
+                    "AppConfig::swapchain_scaling must be DXGI_SCALING_STRETCH with premultiplied alpha"
+                        .into(),
+                );
+            }
+        } else if self.swapchain_alpha_mode != DXGI_ALPHA_MODE_IGNORE {
+            return Err(format!(
+                "AppConfig::swapchain_alpha_mode must be DXGI_ALPHA_MODE_IGNORE or DXGI_ALPHA_MODE_PREMULTIPLIED, but is {:?}",
+                self.swapchain_alpha_mode
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Whether the window is transparent and presented through DirectComposition
+    pub fn is_transparent(&self) -> bool {
+        self.swapchain_alpha_mode == DXGI_ALPHA_MODE_PREMULTIPLIED
+    }
+
+    /// Attributes of the window created for the app
+    pub(crate) fn window_attributes(&self) -> WindowAttributes {
+        WindowAttributes::default()
+            .with_title(self.window_title.as_str())
+            .with_inner_size(self.window_size)
+            .with_decorations(self.window_decorations)
+            .with_fullscreen(self.fullscreen.then_some(Fullscreen::Borderless(None)))
+            .with_transparent(self.is_transparent())
+    }
+}
+
 /// The app_creator function creates the user-defined application struct using a GPULib object,
 /// which contains the basic Direct3D 12 structs.
 pub fn run_app<T: App>(
     app_config: AppConfig,
     app_creator: impl FnOnce(Arc<GPULib>) -> T,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let event_loop = EventLoop::new()?;
+) -> Result<(), GimsError> {
+    run_app_fallible(app_config, move |lib| Ok(app_creator(lib)))
+}
+
+/// Like `run_app`, but the app_creator function can fail, e.g. when shaders or assets cannot be loaded.
+/// The error is reported in the same message box as a failed device creation, after which the event loop exits.
+pub fn run_app_fallible<T: App>(
+    app_config: AppConfig,
+    app_creator: impl FnOnce(Arc<GPULib>) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<(), GimsError> {
+    app_config.validate()?;
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
     event_loop.run_app(&mut AppRunner {
         app_creator: Some(app_creator),
         running_state: OnceCell::new(),
         app_config,
+        lib: None,
+        #[cfg(feature = "accesskit")]
+        event_loop_proxy: event_loop.create_proxy(),
+    })?;
+
+    Ok(())
+}
+
+/// Like `run_app`, but uses a `GPULib` created beforehand, e.g. with `GpuLibBuilder`
+/// to inspect the device before opening the window. `AppConfig::gpu` is ignored.
+pub fn run_app_with_lib<T: App>(
+    lib: Arc<GPULib>,
+    app_config: AppConfig,
+    app_creator: impl FnOnce(Arc<GPULib>) -> T,
+) -> Result<(), GimsError> {
+    app_config.validate()?;
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    event_loop.run_app(&mut AppRunner {
+        app_creator: Some(move |lib| Ok(app_creator(lib))),
+        running_state: OnceCell::new(),
+        app_config,
+        lib: Some(lib),
+        #[cfg(feature = "accesskit")]
+        event_loop_proxy: event_loop.create_proxy(),
     })?;
 
     Ok(())
 }
+
+/// Like `run_app`, but runs the app_creator function on a worker thread while the window shows a loading screen.
+/// The creator can report its progress through the `LoadingProgress` handle, which is displayed as a progress bar.
+/// This keeps the window responsive while many assets are loaded. The loading screen is drawn with egui,
+/// so `AppConfig::enable_egui` has to be set.
+pub fn run_app_with_loading_screen<T: App + Send + 'static>(
+    app_config: AppConfig,
+    app_creator: impl FnOnce(Arc<GPULib>, LoadingProgress) -> T + Send + 'static,
+) -> Result<(), GimsError> {
+    if !app_config.enable_egui {
+        return Err("run_app_with_loading_screen requires AppConfig::enable_egui".into());
+    }
+    run_app(app_config, move |lib| LoadingApp::new(lib, app_creator))
+}
+
+/// Like `run_app`, but the app is created, updated and drawn on a dedicated render thread.
+/// The winit event thread only forwards window events, so frame pacing does not suffer when event processing stalls,
+/// e.g. while the window is moved or resized.
+///
+/// Constraints compared to `run_app`:
+/// - The app has to be `Send`, since it is created and lives on the render thread. `record_ui` and `draw` are called there.
+/// - Events reach the app asynchronously, so input can be applied one frame later than in `run_app`.
+/// - In `RenderMode::Continuous`, frames are drawn back to back and only throttled by presentation
+///   and the frame rate limit of the settings panel.
+///   Redraw requests of the window are ignored.
+/// - The `accesskit` feature has no effect, since the adapter has to live on the event loop thread.
+///
+/// Presenting flip model swapchains from a thread other than the window thread is supported by DXGI.
+/// The window thread must not be blocked on the render thread while it presents,
+/// which is why events are forwarded through a channel instead of being processed in place.
+pub fn run_app_on_render_thread<T: App + Send + 'static>(
+    app_config: AppConfig,
+    app_creator: impl FnOnce(Arc<GPULib>) -> T + Send + 'static,
+) -> Result<(), GimsError> {
+    app_config.validate()?;
+    let event_loop = EventLoop::new()?;
+    let mut runner = RenderThreadRunner::new(app_config, app_creator, event_loop.create_proxy());
+    event_loop.run_app(&mut runner)?;
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+};
+
+use crate::{App, FrameResources, gpulib::GPULib};
+
+/// Handle through which an app creator running on a worker thread reports its progress to the loading screen
+#[derive(Clone, Default)]
+pub struct LoadingProgress {
+    state: Arc<Mutex<(f32, String)>>,
+}
+
+impl LoadingProgress {
+    /// Sets the progress as a fraction between 0 and 1 together with a message describing the current step
+    pub fn set(&self, fraction: f32, message: impl Into<String>) {
+        *self.state.lock().unwrap() = (fraction.clamp(0.0, 1.0), message.into());
+    }
+
+    fn get(&self) -> (f32, String) {
+        self.state.lock().unwrap().clone()
+    }
+}
+
+/// App which shows a loading screen until the actual app has been created on a worker thread
+pub(crate) struct LoadingApp<T> {
+    loader: Option<JoinHandle<T>>,
+    progress: LoadingProgress,
+    app: Option<T>,
+}
+
+impl<T: App + Send + 'static> LoadingApp<T> {
+    pub fn new(
+        lib: Arc<GPULib>,
+        app_creator: impl FnOnce(Arc<GPULib>, LoadingProgress) -> T + Send + 'static,
+    ) -> Self {
+        let progress = LoadingProgress::default();
+        let worker_progress = progress.clone();
+        let loader = std::thread::spawn(move || app_creator(lib, worker_progress));
+
+        LoadingApp {
+            loader: Some(loader),
+            progress,
+            app: None,
+        }
+    }
+
+    /// Moves the app out of the worker thread once it has finished.
+    /// A panic of the app creator is resumed on this thread, so it ends the app like a panic in `run_app` would.
+    fn poll_loader(&mut self) {
+        if self
+            .loader
+            .as_ref()
+            .is_some_and(|loader| loader.is_finished())
+        {
+            let loader = self.loader.take().unwrap();
+            match loader.join() {
+                Ok(app) => self.app = Some(app),
+                Err(payload) => std::panic::resume_unwind(payload),
+            }
+        }
+    }
+}
+
+impl<T: App + Send + 'static> App for LoadingApp<T> {
+    fn record_ui(&mut self, ctx: &egui::Context) {
+        if let Some(app) = &mut self.app {
+            app.record_ui(ctx);
+            return;
+        }
+
+        let (fraction, message) = self.progress.get();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 2.0 - 40.0);
+                ui.add(egui::Spinner::new().size(32.0));
+                ui.label(message);
+                ui.add(egui::ProgressBar::new(fraction).show_percentage());
+            });
+        });
+    }
+
+    fn draw(&mut self, frame_resources: &FrameResources) -> Result<(), Box<dyn std::error::Error>> {
+        self.poll_loader();
+        if let Some(app) = &mut self.app {
+            return app.draw(frame_resources);
+        }
+
+        unsafe {
+            frame_resources.command_list.ClearRenderTargetView(
+                frame_resources.render_target_handle_srgb,
+                &[0.0, 0.0, 0.0, 1.0],
+                None,
+            )
+        };
+        Ok(())
+    }
+
+    fn resized(&mut self, width: u32, height: u32) {
+        if let Some(app) = &mut self.app {
+            app.resized(width, height);
+        }
+    }
+
+    fn visibility_changed(&mut self, hidden: bool) {
+        if let Some(app) = &mut self.app {
+            app.visibility_changed(hidden);
+        }
+    }
+
+    fn device_lost(&mut self) {
+        if let Some(app) = &mut self.app {
+            app.device_lost();
+        }
+    }
+
+    fn frame_captured(&mut self, image: image::RgbaImage) {
+        if let Some(app) = &mut self.app {
+            app.frame_captured(image);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use nalgebra_glm::Vec3;
+use windows::{
+    Win32::Graphics::{
+        Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+        Direct3D12::{D3D12_INPUT_ELEMENT_DESC, ID3D12GraphicsCommandList},
+        Dxgi::Common::{
+            DXGI_FORMAT_R32_UINT, DXGI_FORMAT_R32G32_FLOAT, DXGI_FORMAT_R32G32B32_FLOAT,
+        },
+    },
+    core::s,
+};
+
+use crate::{
+    error::GimsError,
+    gpulib::GPULib,
+    pipeline::vertex_element,
+    vector_constant_buffer::{BufferLocation, VectorConstantBuffer},
+};
+
+/// Vertex of meshes loaded with `load_obj`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    /// Texture coordinates with the origin in the top left corner, as sampled by Direct3D
+    pub uv: [f32; 2],
+}
+
+impl MeshVertex {
+    /// Input layout with the semantics `POSITION`, `NORMAL` and `TEXCOORD` in input slot 0
+    pub fn input_layout() -> [D3D12_INPUT_ELEMENT_DESC; 3] {
+        [
+            vertex_element(s!("POSITION"), 0, DXGI_FORMAT_R32G32B32_FLOAT, 0),
+            vertex_element(s!("NORMAL"), 0, DXGI_FORMAT_R32G32B32_FLOAT, 0),
+            vertex_element(s!("TEXCOORD"), 0, DXGI_FORMAT_R32G32_FLOAT, 0),
+        ]
+    }
+}
+
+/// Indexed triangle list in GPU memory
+pub struct Mesh {
+    pub vertices: VectorConstantBuffer<MeshVertex>,
+    pub indices: VectorConstantBuffer<u32>,
+}
+
+impl Mesh {
+    /// Uploads the triangle list into `BufferLocation::GpuDefault` buffers
+    pub fn new(
+        lib: Arc<GPULib>,
+        vertices: &[MeshVertex],
+        indices: &[u32],
+        name: Option<&str>,
+    ) -> Result<Self, GimsError> {
+        if indices.is_empty() {
+            return Err("Mesh has no triangles".into());
+        }
+
+        let mut vertex_buffer = VectorConstantBuffer::new(
+            lib.clone(),
+            vertices.len(),
+            BufferLocation::GpuDefault,
+            name.map(|name| format!("{} vertices", name)),
+        )?;
+        vertex_buffer.upload(vertices)?;
+        let mut index_buffer = VectorConstantBuffer::new(
+            lib,
+            indices.len(),
+            BufferLocation::GpuDefault,
+            name.map(|name| format!("{} indices", name)),
+        )?;
+        index_buffer.upload(indices)?;
+
+        Ok(Mesh {
+            vertices: vertex_buffer,
+            indices: index_buffer,
+        })
+    }
+
+    /// Binds the buffers and draws all triangles. The pipeline needs the layout of `MeshVertex::input_layout`.
+    pub fn draw(&self, command_list: &ID3D12GraphicsCommandList) {
+        unsafe {
+            command_list.IASetVertexBuffers(0, Some(&[self.vertices.vertex_buffer_view()]));
+            command_list
+                .IASetIndexBuffer(Some(&self.indices.index_buffer_view(DXGI_FORMAT_R32_UINT)));
+            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            command_list.DrawIndexedInstanced(self.indices.len() as u32, 1, 0, 0, 0);
+        }
+    }
+}
+
+/// Loads the Wavefront OBJ file at `path` into GPU memory, see `parse_obj`
+pub fn load_obj(lib: Arc<GPULib>, path: &Path) -> Result<Mesh, GimsError> {
+    let source = std::fs::read_to_string(path)?;
+    let (vertices, indices) = parse_obj(&source)
+        .map_err(|error| format!("Failed to load {}: {}", path.display(), error))?;
+    Mesh::new(lib, &vertices, &indices, Some(&path.display().to_string()))
+}
+
+/// Parses the positions, texture coordinates, normals and faces of a Wavefront OBJ file into an indexed triangle list.
+/// Polygons are split into triangle fans, and vertices without a normal get the average normal of their faces.
+/// Faces are expected in counterclockwise order. Objects, groups and materials are ignored.
+pub fn parse_obj(source: &str) -> Result<(Vec<MeshVertex>, Vec<u32>), GimsError> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+
+    let mut vertices: Vec<MeshVertex> = Vec::new();
+    let mut indices = Vec::new();
+    let mut vertex_indices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    // Vertices whose normals are accumulated from their faces
+    let mut generated_normals: Vec<Option<Vec3>> = Vec::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_error = |message: String| format!("Line {}: {}", line_index + 1, message);
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => positions.push(parse_floats::<3>(tokens).map_err(line_error)?),
+            "vt" => {
+                let [u, v] = parse_floats::<2>(tokens).map_err(line_error)?;
+                // OBJ places the origin in the bottom left corner
+                uvs.push([u, 1.0 - v]);
+            }
+            "vn" => normals.push(parse_floats::<3>(tokens).map_err(line_error)?),
+            "f" => {
+                let mut face = Vec::new();
+                for token in tokens {
+                    let key = parse_face_vertex(token, positions.len(), uvs.len(), normals.len())
+                        .map_err(line_error)?;
+                    let index = match vertex_indices.get(&key) {
+                        Some(&index) => index,
+                        None => {
+                            let (position, uv, normal) = key;
+                            let index = u32::try_from(vertices.len())
+                                .map_err(|_| line_error("Too many vertices".to_string()))?;
+                            vertices.push(MeshVertex {
+                                position: positions[position],
+                                normal: normal.map(|normal| normals[normal]).unwrap_or_default(),
+                                uv: uv.map(|uv| uvs[uv]).unwrap_or_default(),
+                            });
+                            generated_normals.push(normal.is_none().then(Vec3::zeros));
+                            vertex_indices.insert(key, index);
+                            index
+                        }
+                    };
+                    face.push(index);
+                }
+                if face.len() < 3 {
+                    return Err(line_error(format!(
+                        "Face has {} vertices, at least 3 are needed",
+                        face.len()
+                    ))
+                    .into());
+                }
+
+                for i in 1..face.len() - 1 {
+                    let triangle = [face[0], face[i], face[i + 1]];
+                    let [a, b, c] =
+                        triangle.map(|index| Vec3::from(vertices[index as usize].position));
+                    // Weighted by area, since the cross product is twice the area of the triangle
+                    let face_normal = (b - a).cross(&(c - a));
+                    for index in triangle {
+                        if let Some(normal) = &mut generated_normals[index as usize] {
+                            *normal += face_normal;
+                        }
+                    }
+                    indices.extend(triangle);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (vertex, normal) in vertices.iter_mut().zip(generated_normals) {
+        if let Some(normal) = normal {
+            vertex.normal = normal
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vec3::y)
+                .into();
+        }
+    }
+
+    Ok((vertices, indices))
+}
+
+fn parse_floats<'a, const N: usize>(
+    mut tokens: impl Iterator<Item = &'a str>,
+) -> Result<[f32; N], String> {
+    let mut values = [0.0; N];
+    for value in &mut values {
+        let token = tokens
+            .next()
+            .ok_or_else(|| format!("Expected {} numbers", N))?;
+        *value = token
+            .parse()
+            .map_err(|_| format!("Invalid number {}", token))?;
+    }
+    Ok(values)
+}
+
+/// Parses `position/uv/normal` with optional uv and normal into zero based indices.
+/// Negative indices count back from the last element read so far.
+fn parse_face_vertex(
+    token: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let resolve = |index: &str, count: usize| -> Result<usize, String> {
+        let value: i64 = index
+            .parse()
+            .map_err(|_| format!("Invalid index {} in face vertex {}", index, token))?;
+        let resolved = match value {
+            1.. => value - 1,
+            ..0 => count as i64 + value,
+            0 => -1,
+        };
+        if resolved < 0 || resolved >= count as i64 {
+            return Err(format!(
+                "Index {} in face vertex {} is out of range",
+                index, token
+            ));
+        }
+        Ok(resolved as usize)
+    };
+    let optional = |index: Option<&str>, count| match index {
+        Some(index) if !index.is_empty() => resolve(index, count).map(Some),
+        _ => Ok(None),
+    };
+
+    let mut parts = token.split('/');
+    let position = resolve(parts.next().unwrap_or_default(), position_count)?;
+    let uv = optional(parts.next(), uv_count)?;
+    let normal = optional(parts.next(), normal_count)?;
+    Ok((position, uv, normal))
+}
@@ -0,0 +1,518 @@
+use std::mem::ManuallyDrop;
+
+use windows::{
+    Win32::Graphics::{
+        Direct3D::{
+            D3D_PRIMITIVE_TOPOLOGY_LINELIST, D3D_PRIMITIVE_TOPOLOGY_POINTLIST,
+            D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+        },
+        Direct3D12::*,
+        Dxgi::Common::{DXGI_FORMAT, DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_UNKNOWN},
+    },
+    core::PCSTR,
+};
+
+use crate::{
+    error::GimsError,
+    gpulib::{GPULib, compile_shader},
+    pipeline_cache::PipelineCache,
+    texture::ClearValue,
+};
+
+/// Mapping of view distance to stored depth, which the depth test, the depth clear and the projection have to agree on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthRange {
+    /// Near plane at depth 0 and far plane at 1, tested with `LESS`
+    Standard,
+    /// Near plane at depth 1 and far plane at 0, tested with `GREATER`. Floating point values are densest
+    /// around 0, which cancels out the hyperbolic distribution of perspective depth and makes z-fighting
+    /// at large distances disappear. Only works with a float depth format like `DXGI_FORMAT_D32_FLOAT`,
+    /// with a UNORM format precision is not better than `Standard`. Use a projection like `reversed_z_perspective`;
+    /// flipping `MinDepth` and `MaxDepth` of the viewport instead reverses the range without the precision gain.
+    Reversed,
+}
+
+impl DepthRange {
+    /// Comparison that lets nearer surfaces pass
+    pub fn comparison_func(self) -> D3D12_COMPARISON_FUNC {
+        match self {
+            DepthRange::Standard => D3D12_COMPARISON_FUNC_LESS,
+            DepthRange::Reversed => D3D12_COMPARISON_FUNC_GREATER,
+        }
+    }
+
+    /// Depth of the far plane, which the depth buffer is cleared to
+    pub fn clear_depth(self) -> f32 {
+        match self {
+            DepthRange::Standard => 1.0,
+            DepthRange::Reversed => 0.0,
+        }
+    }
+
+    /// Optimized clear value for `TextureDesc::clear_value` of the depth buffer
+    pub fn clear_value(self) -> ClearValue {
+        ClearValue::DepthStencil {
+            depth: self.clear_depth(),
+            stencil: 0,
+        }
+    }
+}
+
+/// Right handed perspective projection for `DepthRange::Reversed` with an infinite far plane,
+/// as column major matrix. Points at distance `near` get depth 1, the depth approaches 0 towards infinity.
+pub fn reversed_z_perspective(fov_y_radians: f32, aspect_ratio: f32, near: f32) -> [f32; 16] {
+    let focal_length = 1.0 / (fov_y_radians / 2.0).tan();
+    #[rustfmt::skip]
+    let matrix = [
+        focal_length / aspect_ratio, 0.0, 0.0, 0.0,
+        0.0, focal_length, 0.0, 0.0,
+        0.0, 0.0, 0.0, -1.0,
+        0.0, 0.0, near, 0.0,
+    ];
+    matrix
+}
+
+/// Creates a graphics pipeline from `desc` using the specified root signature.
+/// The `pRootSignature` field of `desc` is ignored. The root signature reference stored in the description
+/// is released again after creation, so neither a leak nor a double release can happen.
+pub fn create_graphics_pipeline(
+    lib: &GPULib,
+    root_signature: &ID3D12RootSignature,
+    mut desc: D3D12_GRAPHICS_PIPELINE_STATE_DESC,
+) -> Result<ID3D12PipelineState, GimsError> {
+    desc.pRootSignature = ManuallyDrop::new(Some(root_signature.clone()));
+    let pipeline: windows::core::Result<ID3D12PipelineState> =
+        unsafe { lib.device.CreateGraphicsPipelineState(&desc) };
+    unsafe { ManuallyDrop::drop(&mut desc.pRootSignature) };
+
+    Ok(pipeline?)
+}
+
+/// Creates a compute pipeline from a compiled compute shader
+pub fn create_compute_pipeline(
+    lib: &GPULib,
+    root_signature: &ID3D12RootSignature,
+    compute_shader: &[u8],
+) -> Result<ID3D12PipelineState, GimsError> {
+    let mut desc = D3D12_COMPUTE_PIPELINE_STATE_DESC {
+        pRootSignature: ManuallyDrop::new(Some(root_signature.clone())),
+        CS: shader_bytecode(compute_shader),
+        ..Default::default()
+    };
+    let pipeline: windows::core::Result<ID3D12PipelineState> =
+        unsafe { lib.device.CreateComputePipelineState(&desc) };
+    unsafe { ManuallyDrop::drop(&mut desc.pRootSignature) };
+
+    Ok(pipeline?)
+}
+
+/// Builder for compute pipelines, the counterpart of `GraphicsPipelineBuilder`.
+/// Bind the result with `FrameResources::set_compute_pipeline` and run it with `FrameResources::dispatch`.
+#[derive(Clone)]
+pub struct ComputePipelineBuilder<'a> {
+    root_signature: &'a ID3D12RootSignature,
+    compute_shader: &'a [u8],
+}
+
+impl<'a> ComputePipelineBuilder<'a> {
+    /// `compute_shader` is compiled for a `cs_6_x` target, e.g. with `compile_shader`
+    pub fn new(root_signature: &'a ID3D12RootSignature, compute_shader: &'a [u8]) -> Self {
+        ComputePipelineBuilder {
+            root_signature,
+            compute_shader,
+        }
+    }
+
+    pub fn build(&self, lib: &GPULib) -> Result<ID3D12PipelineState, GimsError> {
+        create_compute_pipeline(lib, self.root_signature, self.compute_shader)
+    }
+}
+
+/// Builder for graphics pipelines with the defaults used throughout the examples:
+/// solid triangle lists without culling, no blending, no depth buffer and a single RGBA8 render target.
+#[derive(Clone)]
+pub struct GraphicsPipelineBuilder<'a> {
+    root_signature: &'a ID3D12RootSignature,
+    vertex_shader: &'a [u8],
+    pixel_shader: Option<&'a [u8]>,
+    input_layout: &'a [D3D12_INPUT_ELEMENT_DESC],
+    rasterizer_state: D3D12_RASTERIZER_DESC,
+    blend_state: D3D12_BLEND_DESC,
+    depth_stencil_state: D3D12_DEPTH_STENCIL_DESC,
+    render_target_formats: Vec<DXGI_FORMAT>,
+    depth_stencil_format: DXGI_FORMAT,
+    primitive_topology_type: D3D12_PRIMITIVE_TOPOLOGY_TYPE,
+    sample_count: u32,
+}
+
+impl<'a> GraphicsPipelineBuilder<'a> {
+    pub fn new(root_signature: &'a ID3D12RootSignature, vertex_shader: &'a [u8]) -> Self {
+        let mut blend_state = D3D12_BLEND_DESC::default();
+        blend_state.RenderTarget[0].RenderTargetWriteMask = D3D12_COLOR_WRITE_ENABLE_ALL.0 as u8;
+
+        GraphicsPipelineBuilder {
+            root_signature,
+            vertex_shader,
+            pixel_shader: None,
+            input_layout: &[],
+            rasterizer_state: D3D12_RASTERIZER_DESC {
+                FillMode: D3D12_FILL_MODE_SOLID,
+                CullMode: D3D12_CULL_MODE_NONE,
+                FrontCounterClockwise: false.into(),
+                DepthBias: D3D12_DEFAULT_DEPTH_BIAS,
+                DepthBiasClamp: D3D12_DEFAULT_DEPTH_BIAS_CLAMP,
+                SlopeScaledDepthBias: D3D12_DEFAULT_SLOPE_SCALED_DEPTH_BIAS,
+                DepthClipEnable: true.into(),
+                MultisampleEnable: false.into(),
+                AntialiasedLineEnable: false.into(),
+                ForcedSampleCount: 0,
+                ConservativeRaster: D3D12_CONSERVATIVE_RASTERIZATION_MODE_OFF,
+            },
+            blend_state,
+            depth_stencil_state: D3D12_DEPTH_STENCIL_DESC {
+                DepthEnable: false.into(),
+                DepthFunc: D3D12_COMPARISON_FUNC_ALWAYS,
+                ..Default::default()
+            },
+            render_target_formats: vec![DXGI_FORMAT_R8G8B8A8_UNORM],
+            depth_stencil_format: DXGI_FORMAT_UNKNOWN,
+            primitive_topology_type: D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE,
+            sample_count: 1,
+        }
+    }
+
+    /// Pipelines without a pixel shader only write depth
+    pub fn pixel_shader(mut self, pixel_shader: &'a [u8]) -> Self {
+        self.pixel_shader = Some(pixel_shader);
+        self
+    }
+
+    pub fn input_layout(mut self, input_layout: &'a [D3D12_INPUT_ELEMENT_DESC]) -> Self {
+        self.input_layout = input_layout;
+        self
+    }
+
+    pub fn rasterizer_state(mut self, rasterizer_state: D3D12_RASTERIZER_DESC) -> Self {
+        self.rasterizer_state = rasterizer_state;
+        self
+    }
+
+    /// Which faces are discarded: `D3D12_CULL_MODE_BACK` skips triangles facing away from the camera,
+    /// which halves the shading work of closed meshes. Defaults to `D3D12_CULL_MODE_NONE`.
+    pub fn cull_mode(mut self, cull_mode: D3D12_CULL_MODE) -> Self {
+        self.rasterizer_state.CullMode = cull_mode;
+        self
+    }
+
+    /// Whether triangles with counter-clockwise vertices are front faces. Defaults to clockwise, the Direct3D convention.
+    /// The winding is judged on screen after projection, where y points up. Meshes authored for right-handed,
+    /// counter-clockwise conventions like glTF and most OBJ exporters need `true` if the view and projection keep
+    /// their handedness. Transforms with a negative determinant, e.g. mirroring or switching handedness, flip the winding.
+    pub fn front_counter_clockwise(mut self, front_counter_clockwise: bool) -> Self {
+        self.rasterizer_state.FrontCounterClockwise = front_counter_clockwise.into();
+        self
+    }
+
+    pub fn blend_state(mut self, blend_state: D3D12_BLEND_DESC) -> Self {
+        self.blend_state = blend_state;
+        self
+    }
+
+    pub fn depth_stencil(
+        mut self,
+        format: DXGI_FORMAT,
+        depth_stencil_state: D3D12_DEPTH_STENCIL_DESC,
+    ) -> Self {
+        self.depth_stencil_format = format;
+        self.depth_stencil_state = depth_stencil_state;
+        self
+    }
+
+    /// Preset for regular depth testing and writing with the comparison of `range`.
+    /// Clear the depth buffer to `range.clear_depth()`.
+    pub fn depth_test(self, format: DXGI_FORMAT, range: DepthRange) -> Self {
+        self.depth_stencil(
+            format,
+            D3D12_DEPTH_STENCIL_DESC {
+                DepthEnable: true.into(),
+                DepthWriteMask: D3D12_DEPTH_WRITE_MASK_ALL,
+                DepthFunc: range.comparison_func(),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Preset for the first pass of a depth prepass: depth is tested with the comparison of `range` and written,
+    /// while no pixel shader runs and no render targets are bound.
+    pub fn depth_only(mut self, format: DXGI_FORMAT, range: DepthRange) -> Self {
+        self.pixel_shader = None;
+        self.render_target_formats.clear();
+        self.depth_stencil(
+            format,
+            D3D12_DEPTH_STENCIL_DESC {
+                DepthEnable: true.into(),
+                DepthWriteMask: D3D12_DEPTH_WRITE_MASK_ALL,
+                DepthFunc: range.comparison_func(),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Preset for the shading pass after a depth prepass: only the visible surface passes the `EQUAL` test,
+    /// so every pixel is shaded once. Depth is not written again. The vertex shader has to compute exactly the
+    /// same positions as in the prepass, so use the same shader and input layout for both.
+    pub fn depth_equal(self, format: DXGI_FORMAT) -> Self {
+        self.depth_stencil(
+            format,
+            D3D12_DEPTH_STENCIL_DESC {
+                DepthEnable: true.into(),
+                DepthWriteMask: D3D12_DEPTH_WRITE_MASK_ZERO,
+                DepthFunc: D3D12_COMPARISON_FUNC_EQUAL,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Primitive class the pipeline rasterizes, e.g. `D3D12_PRIMITIVE_TOPOLOGY_TYPE_LINE` for debug lines.
+    /// The topology set with `IASetPrimitiveTopology` at draw time has to match it.
+    pub fn primitive_topology_type(
+        mut self,
+        primitive_topology_type: D3D12_PRIMITIVE_TOPOLOGY_TYPE,
+    ) -> Self {
+        self.primitive_topology_type = primitive_topology_type;
+        self
+    }
+
+    /// Draws lines with a width of one pixel and smoothed edges, e.g. for debug visualizations.
+    /// Only has an effect on line topologies and without multisampling, so `MultisampleEnable` is turned off
+    /// when enabling it. The coverage of the edge pixels is written to alpha, so the lines only look smooth
+    /// with alpha blending enabled in `blend_state`.
+    pub fn antialiased_lines(mut self, enable: bool) -> Self {
+        self.rasterizer_state.AntialiasedLineEnable = enable.into();
+        if enable {
+            self.rasterizer_state.MultisampleEnable = false.into();
+        }
+        self
+    }
+
+    /// Samples per pixel of the render targets, e.g. `MsaaTarget::sample_count`. Enables `MultisampleEnable`
+    /// for counts larger than 1, which makes lines use the quadrilateral algorithm that suits multisampling.
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self.rasterizer_state.MultisampleEnable = (sample_count > 1).into();
+        self
+    }
+
+    /// Sets the formats of all render targets. At most `D3D12_SIMULTANEOUS_RENDER_TARGET_COUNT` are supported.
+    pub fn render_target_formats(mut self, formats: &[DXGI_FORMAT]) -> Self {
+        self.render_target_formats = formats.to_vec();
+        self
+    }
+
+    pub fn build(&self, lib: &GPULib) -> Result<ID3D12PipelineState, GimsError> {
+        create_graphics_pipeline(lib, self.root_signature, self.desc()?)
+    }
+
+    /// Like `build`, but loads the pipeline from `cache` if it was stored under `key` in an earlier run
+    pub fn build_cached(
+        &self,
+        cache: &mut PipelineCache,
+        key: &str,
+    ) -> Result<ID3D12PipelineState, GimsError> {
+        cache.get_or_create_graphics(key, self.root_signature, self.desc()?)
+    }
+
+    /// The description points into the builder, so it must not outlive it
+    fn desc(&self) -> Result<D3D12_GRAPHICS_PIPELINE_STATE_DESC, GimsError> {
+        if self.render_target_formats.len() > D3D12_SIMULTANEOUS_RENDER_TARGET_COUNT as usize {
+            return Err("Too many render target formats for a graphics pipeline".into());
+        }
+        if let Some(element) = self
+            .input_layout
+            .iter()
+            .find(|element| element.InputSlot >= D3D12_IA_VERTEX_INPUT_RESOURCE_SLOT_COUNT)
+        {
+            return Err(format!(
+                "Input slot {} exceeds the maximum of {} vertex buffers",
+                element.InputSlot,
+                D3D12_IA_VERTEX_INPUT_RESOURCE_SLOT_COUNT - 1
+            )
+            .into());
+        }
+        let mut rtv_formats = [DXGI_FORMAT_UNKNOWN; 8];
+        rtv_formats[..self.render_target_formats.len()]
+            .copy_from_slice(&self.render_target_formats);
+
+        Ok(D3D12_GRAPHICS_PIPELINE_STATE_DESC {
+            InputLayout: D3D12_INPUT_LAYOUT_DESC {
+                pInputElementDescs: self.input_layout.as_ptr(),
+                NumElements: self.input_layout.len() as u32,
+            },
+            VS: shader_bytecode(self.vertex_shader),
+            PS: self.pixel_shader.map(shader_bytecode).unwrap_or_default(),
+            RasterizerState: self.rasterizer_state,
+            BlendState: self.blend_state,
+            DepthStencilState: self.depth_stencil_state,
+            SampleMask: u32::MAX,
+            PrimitiveTopologyType: self.primitive_topology_type,
+            NumRenderTargets: self.render_target_formats.len() as u32,
+            RTVFormats: rtv_formats,
+            DSVFormat: self.depth_stencil_format,
+            SampleDesc: windows::Win32::Graphics::Dxgi::Common::DXGI_SAMPLE_DESC {
+                Count: self.sample_count,
+                Quality: 0,
+            },
+            ..Default::default()
+        })
+    }
+}
+
+/// The two pipelines of a depth prepass, which avoids shading pixels that are later covered by closer geometry.
+/// The geometry is drawn twice: first into the depth buffer only, then shaded where its depth matches.
+pub struct DepthPrepass {
+    pub depth_pipeline: ID3D12PipelineState,
+    pub shading_pipeline: ID3D12PipelineState,
+}
+
+impl DepthPrepass {
+    /// Creates both pipelines from the builder of the shading pipeline, using the presets `depth_only`
+    /// and `depth_equal` with the depth buffer `format` and the depth comparison of `range`
+    pub fn new(
+        lib: &GPULib,
+        builder: &GraphicsPipelineBuilder,
+        format: DXGI_FORMAT,
+        range: DepthRange,
+    ) -> Result<Self, GimsError> {
+        Ok(DepthPrepass {
+            depth_pipeline: builder.clone().depth_only(format, range).build(lib)?,
+            shading_pipeline: builder.clone().depth_equal(format).build(lib)?,
+        })
+    }
+
+    /// Records both passes, calling `draw_geometry` once per pass to issue the same draw calls.
+    /// The root signature, its parameters, viewport and scissor have to be set beforehand,
+    /// and the depth buffer has to be cleared. Afterwards, the render targets and the depth buffer stay bound.
+    pub fn record(
+        &self,
+        command_list: &ID3D12GraphicsCommandList,
+        render_target_handles: &[D3D12_CPU_DESCRIPTOR_HANDLE],
+        depth_stencil_handle: D3D12_CPU_DESCRIPTOR_HANDLE,
+        mut draw_geometry: impl FnMut(&ID3D12GraphicsCommandList),
+    ) {
+        unsafe {
+            command_list.OMSetRenderTargets(0, None, false, Some(&depth_stencil_handle));
+            command_list.SetPipelineState(&self.depth_pipeline);
+        }
+        draw_geometry(command_list);
+
+        unsafe {
+            command_list.OMSetRenderTargets(
+                render_target_handles.len() as u32,
+                Some(render_target_handles.as_ptr()),
+                false,
+                Some(&depth_stencil_handle),
+            );
+            command_list.SetPipelineState(&self.shading_pipeline);
+        }
+        draw_geometry(command_list);
+    }
+}
+
+/// Compiles the built-in vertex shader for full screen passes. It needs no vertex buffer and outputs
+/// `float4 position : SV_Position` and `float2 uv : TEXCOORD` with (0, 0) in the top left corner of the screen.
+/// Use it with `GraphicsPipelineBuilder` if the defaults of `create_fullscreen_pipeline` do not fit.
+pub fn fullscreen_triangle_vertex_shader() -> Result<Vec<u8>, GimsError> {
+    compile_shader(include_str!("fullscreen_triangle.hlsl"), "main", "vs_6_5")
+}
+
+/// Creates a pipeline for screen space passes like post processing from just a pixel shader,
+/// which receives the outputs of `fullscreen_triangle_vertex_shader`. Draw it with `draw_fullscreen`.
+pub fn create_fullscreen_pipeline(
+    lib: &GPULib,
+    root_signature: &ID3D12RootSignature,
+    pixel_shader: &[u8],
+    render_target_formats: &[DXGI_FORMAT],
+) -> Result<ID3D12PipelineState, GimsError> {
+    let vertex_shader = fullscreen_triangle_vertex_shader()?;
+    GraphicsPipelineBuilder::new(root_signature, &vertex_shader)
+        .pixel_shader(pixel_shader)
+        .render_target_formats(render_target_formats)
+        .build(lib)
+}
+
+/// Per vertex input element read from the vertex buffer bound to `input_slot`, placed right after
+/// the previous element of the same slot. Elements with different slots form a layout over several vertex
+/// buffers, e.g. positions and attributes kept in separate streams.
+pub fn vertex_element(
+    semantic_name: PCSTR,
+    semantic_index: u32,
+    format: DXGI_FORMAT,
+    input_slot: u32,
+) -> D3D12_INPUT_ELEMENT_DESC {
+    D3D12_INPUT_ELEMENT_DESC {
+        SemanticName: semantic_name,
+        SemanticIndex: semantic_index,
+        Format: format,
+        InputSlot: input_slot,
+        AlignedByteOffset: D3D12_APPEND_ALIGNED_ELEMENT,
+        InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
+        InstanceDataStepRate: 0,
+    }
+}
+
+/// Like `vertex_element`, but advanced once per `step_rate` instances instead of per vertex
+pub fn instance_element(
+    semantic_name: PCSTR,
+    semantic_index: u32,
+    format: DXGI_FORMAT,
+    input_slot: u32,
+    step_rate: u32,
+) -> D3D12_INPUT_ELEMENT_DESC {
+    D3D12_INPUT_ELEMENT_DESC {
+        InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_INSTANCE_DATA,
+        InstanceDataStepRate: step_rate,
+        ..vertex_element(semantic_name, semantic_index, format, input_slot)
+    }
+}
+
+/// Binds `views` to the consecutive input slots starting at `start_slot`
+pub fn set_vertex_buffers(
+    command_list: &ID3D12GraphicsCommandList,
+    start_slot: u32,
+    views: &[D3D12_VERTEX_BUFFER_VIEW],
+) {
+    unsafe { command_list.IASetVertexBuffers(start_slot, Some(views)) };
+}
+
+/// Draws the full screen triangle of a pipeline created with `create_fullscreen_pipeline`
+pub fn draw_fullscreen(command_list: &ID3D12GraphicsCommandList) {
+    unsafe {
+        command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+        command_list.DrawInstanced(3, 1, 0, 0);
+    }
+}
+
+/// Draws `vertex_count` vertices of the bound vertex buffers as a line list.
+/// The pipeline needs the topology type `D3D12_PRIMITIVE_TOPOLOGY_TYPE_LINE`.
+pub fn draw_lines(command_list: &ID3D12GraphicsCommandList, vertex_count: u32) {
+    unsafe {
+        command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_LINELIST);
+        command_list.DrawInstanced(vertex_count, 1, 0, 0);
+    }
+}
+
+/// Draws `vertex_count` vertices of the bound vertex buffers as points.
+/// The pipeline needs the topology type `D3D12_PRIMITIVE_TOPOLOGY_TYPE_POINT`.
+pub fn draw_points(command_list: &ID3D12GraphicsCommandList, vertex_count: u32) {
+    unsafe {
+        command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_POINTLIST);
+        command_list.DrawInstanced(vertex_count, 1, 0, 0);
+    }
+}
+
+fn shader_bytecode(bytecode: &[u8]) -> D3D12_SHADER_BYTECODE {
+    D3D12_SHADER_BYTECODE {
+        pShaderBytecode: bytecode.as_ptr() as _,
+        BytecodeLength: bytecode.len(),
+    }
+}
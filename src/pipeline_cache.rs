@@ -0,0 +1,163 @@
+use std::{mem::ManuallyDrop, path::PathBuf, sync::Arc};
+
+use windows::{
+    Win32::{
+        Foundation::E_INVALIDARG,
+        Graphics::Direct3D12::{
+            D3D12_ERROR_ADAPTER_NOT_FOUND, D3D12_ERROR_DRIVER_VERSION_MISMATCH,
+            D3D12_GRAPHICS_PIPELINE_STATE_DESC, ID3D12PipelineLibrary, ID3D12PipelineState,
+            ID3D12RootSignature,
+        },
+    },
+    core::HSTRING,
+};
+
+use crate::{error::GimsError, gpulib::GPULib, pipeline::create_graphics_pipeline};
+
+/// Stores compiled pipelines in a file, so later runs skip the driver compilation.
+/// The shaders still have to be compiled from HLSL, since the description has to match the stored pipeline.
+///
+/// The key identifies a pipeline and must change whenever its description does. A pipeline whose description
+/// no longer matches its key is created again and the file is rewritten on `save` without the stale entry.
+/// ```ignore
+/// let mut cache = PipelineCache::open(lib.clone(), "pipelines.bin")?;
+/// let pipeline = GraphicsPipelineBuilder::new(&root_signature, &vertex_shader)
+///     .pixel_shader(&pixel_shader)
+///     .build_cached(&mut cache, "cube")?;
+/// cache.save()?;
+/// ```
+pub struct PipelineCache {
+    lib: Arc<GPULib>,
+    path: PathBuf,
+    /// `None` if the driver does not support pipeline libraries, in which case nothing is cached
+    library: Option<ID3D12PipelineLibrary>,
+    /// The library reads from this memory instead of copying it, so it has to be dropped after the library
+    _data: Vec<u8>,
+    /// Pipelines requested since opening, used to rebuild the library when an entry is stale
+    used: Vec<(HSTRING, ID3D12PipelineState)>,
+    modified: bool,
+    stale: bool,
+}
+
+impl PipelineCache {
+    /// Opens the cache stored at `path`. A missing file starts an empty cache, and a file written by another
+    /// driver version or adapter is discarded.
+    pub fn open(lib: Arc<GPULib>, path: impl Into<PathBuf>) -> Result<Self, GimsError> {
+        let path = path.into();
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error.into()),
+        };
+
+        let (library, data) = match create_library(&lib, &data) {
+            Ok(library) => (Some(library), data),
+            Err(error)
+                if !data.is_empty()
+                    && [
+                        D3D12_ERROR_DRIVER_VERSION_MISMATCH,
+                        D3D12_ERROR_ADAPTER_NOT_FOUND,
+                        E_INVALIDARG,
+                    ]
+                    .contains(&error.code()) =>
+            {
+                eprintln!(
+                    "Discarding the pipeline cache {}: {}",
+                    path.display(),
+                    error.message()
+                );
+                (create_library(&lib, &[]).ok(), Vec::new())
+            }
+            Err(_) => (None, Vec::new()),
+        };
+
+        Ok(PipelineCache {
+            lib,
+            path,
+            library,
+            _data: data,
+            used: Vec::new(),
+            modified: false,
+            stale: false,
+        })
+    }
+
+    /// Loads the pipeline stored under `key`, or creates it from `desc` and stores it.
+    /// The `pRootSignature` field of `desc` is ignored, as in `create_graphics_pipeline`.
+    pub fn get_or_create_graphics(
+        &mut self,
+        key: &str,
+        root_signature: &ID3D12RootSignature,
+        mut desc: D3D12_GRAPHICS_PIPELINE_STATE_DESC,
+    ) -> Result<ID3D12PipelineState, GimsError> {
+        let Some(library) = &self.library else {
+            return create_graphics_pipeline(&self.lib, root_signature, desc);
+        };
+        let key = HSTRING::from(key);
+
+        desc.pRootSignature = ManuallyDrop::new(Some(root_signature.clone()));
+        let loaded: windows::core::Result<ID3D12PipelineState> =
+            unsafe { library.LoadGraphicsPipeline(&key, &desc) };
+        unsafe { ManuallyDrop::drop(&mut desc.pRootSignature) };
+
+        let pipeline = match loaded {
+            Ok(pipeline) => pipeline,
+            Err(_) => {
+                let pipeline = create_graphics_pipeline(&self.lib, root_signature, desc)?;
+                // Storing fails if the key is taken by a pipeline with a different description
+                match unsafe { library.StorePipeline(&key, &pipeline) } {
+                    Ok(()) => {}
+                    Err(error) if error.code() == E_INVALIDARG => self.stale = true,
+                    Err(error) => return Err(error.into()),
+                }
+                self.modified = true;
+                pipeline
+            }
+        };
+        self.used.push((key, pipeline.clone()));
+        Ok(pipeline)
+    }
+
+    /// Writes the cache to its file if pipelines were added since opening
+    pub fn save(&mut self) -> Result<(), GimsError> {
+        if !self.modified {
+            return Ok(());
+        }
+        let Some(library) = &self.library else {
+            return Ok(());
+        };
+
+        // Entries cannot be replaced, so a library with stale entries is rebuilt from the pipelines in use
+        let library = if self.stale {
+            let library = create_library(&self.lib, &[])?;
+            for (key, pipeline) in &self.used {
+                unsafe { library.StorePipeline(key, pipeline) }.or_else(|error| {
+                    // The same key may have been requested twice
+                    if error.code() == E_INVALIDARG {
+                        Ok(())
+                    } else {
+                        Err(error)
+                    }
+                })?;
+            }
+            library
+        } else {
+            library.clone()
+        };
+
+        let mut data = vec![0u8; unsafe { library.GetSerializedSize() }];
+        unsafe { library.Serialize(data.as_mut_ptr().cast(), data.len()) }?;
+        std::fs::write(&self.path, &data)?;
+        self.library = Some(library);
+        self.modified = false;
+        self.stale = false;
+        Ok(())
+    }
+}
+
+fn create_library(lib: &GPULib, data: &[u8]) -> windows::core::Result<ID3D12PipelineLibrary> {
+    unsafe {
+        lib.device
+            .CreatePipelineLibrary(data.as_ptr().cast(), data.len())
+    }
+}
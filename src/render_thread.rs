@@ -0,0 +1,210 @@
+use std::{
+    marker::PhantomData,
+    sync::{
+        Arc,
+        mpsc::{Receiver, RecvTimeoutError, Sender, channel},
+    },
+    thread::JoinHandle,
+    time::Instant,
+};
+
+use winit::{
+    event::{DeviceEvent, DeviceId, WindowEvent},
+    event_loop::{ActiveEventLoop, EventLoopProxy},
+    window::{Window, WindowId},
+};
+
+use crate::{
+    App, AppConfig, error::GimsError, gpulib::GPULib, report_initialization_error,
+    running_state::RunningState,
+};
+
+/// Messages from the window thread to the render thread
+enum Message {
+    Event(WindowEvent),
+    DeviceEvent(DeviceEvent),
+    Redraw,
+    Exit,
+}
+
+struct RenderThread {
+    sender: Sender<Message>,
+    handle: JoinHandle<()>,
+}
+
+/// The winit application struct for `run_app_on_render_thread`, which forwards all events to the render thread
+pub(crate) struct RenderThreadRunner<T, F> {
+    app_creator: Option<F>,
+    render_thread: Option<RenderThread>,
+    app_config: AppConfig,
+    /// Wakes the window thread once the render thread has finished
+    proxy: EventLoopProxy<()>,
+    app_type: PhantomData<fn() -> T>,
+}
+
+impl<T, F> RenderThreadRunner<T, F>
+where
+    T: App + Send + 'static,
+    F: FnOnce(Arc<GPULib>) -> T + Send + 'static,
+{
+    pub fn new(app_config: AppConfig, app_creator: F, proxy: EventLoopProxy<()>) -> Self {
+        RenderThreadRunner {
+            app_creator: Some(app_creator),
+            render_thread: None,
+            app_config,
+            proxy,
+            app_type: PhantomData,
+        }
+    }
+
+    fn try_start_render_thread(&mut self, event_loop: &ActiveEventLoop) -> Result<(), GimsError> {
+        let window = event_loop.create_window(self.app_config.window_attributes())?;
+        let lib = self.app_config.gpu.build()?;
+        let app_creator = self
+            .app_creator
+            .take()
+            .ok_or("Application cannot be initialized twice")?;
+
+        let (sender, receiver) = channel();
+        let app_config = self.app_config.clone();
+        let proxy = self.proxy.clone();
+        let handle = std::thread::Builder::new()
+            .name("gimslib render thread".to_string())
+            .spawn(move || {
+                if let Err(error) = render_loop(window, lib, app_creator, &app_config, &receiver) {
+                    println!("Error on render thread:\n{}", error);
+                }
+                // The event loop may already be gone during shutdown
+                let _ = proxy.send_event(());
+            })?;
+
+        self.render_thread = Some(RenderThread { sender, handle });
+
+        Ok(())
+    }
+
+    fn send(&self, message: Message) {
+        if let Some(render_thread) = &self.render_thread {
+            // Sending fails only if the render thread has already stopped
+            let _ = render_thread.sender.send(message);
+        }
+    }
+
+    fn stop_render_thread(&mut self) {
+        if let Some(render_thread) = self.render_thread.take() {
+            let _ = render_thread.sender.send(Message::Exit);
+            if render_thread.handle.join().is_err() {
+                println!("Render thread panicked");
+            }
+        }
+    }
+}
+
+impl<T, F> winit::application::ApplicationHandler for RenderThreadRunner<T, F>
+where
+    T: App + Send + 'static,
+    F: FnOnce(Arc<GPULib>) -> T + Send + 'static,
+{
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        // Only start the render thread once
+        if self.render_thread.is_some() {
+            return;
+        }
+
+        if let Err(error) = self.try_start_render_thread(event_loop) {
+            report_initialization_error(&error);
+            event_loop.exit();
+        }
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, _event: ()) {
+        // The render thread has stopped on its own, e.g. because drawing failed
+        self.stop_render_thread();
+        event_loop.exit();
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        match event {
+            WindowEvent::CloseRequested => {
+                self.stop_render_thread();
+                event_loop.exit();
+            }
+            WindowEvent::RedrawRequested => self.send(Message::Redraw),
+            event => self.send(Message::Event(event)),
+        }
+    }
+
+    fn device_event(
+        &mut self,
+        _event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
+        event: DeviceEvent,
+    ) {
+        // Only mouse motion is used, the other device events would flood the channel
+        if let DeviceEvent::MouseMotion { .. } = event {
+            self.send(Message::DeviceEvent(event));
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        self.stop_render_thread();
+    }
+}
+
+/// Creates the app on the render thread and draws until the window thread asks to exit
+fn render_loop<T: App>(
+    window: Window,
+    lib: Arc<GPULib>,
+    app_creator: impl FnOnce(Arc<GPULib>) -> T,
+    app_config: &AppConfig,
+    receiver: &Receiver<Message>,
+) -> Result<(), GimsError> {
+    let app = app_creator(lib.clone());
+    let mut running_state = RunningState::new(window, lib, app, app_config)?;
+    let mut redraw_requested = true;
+
+    loop {
+        let draw_continuously = running_state.draws_continuously();
+
+        // Only block on the channel if there is nothing to draw
+        let first_message = if redraw_requested || draw_continuously {
+            receiver.try_recv().ok()
+        } else if let Some(repaint_at) = running_state.repaint_at() {
+            match receiver.recv_timeout(repaint_at.saturating_duration_since(Instant::now())) {
+                Ok(message) => Some(message),
+                Err(RecvTimeoutError::Timeout) => {
+                    redraw_requested = true;
+                    None
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        } else {
+            match receiver.recv() {
+                Ok(message) => Some(message),
+                Err(_) => return Ok(()),
+            }
+        };
+
+        for message in first_message.into_iter().chain(receiver.try_iter()) {
+            match message {
+                Message::Event(event) => running_state.event(&event)?,
+                Message::DeviceEvent(event) => running_state.device_event(&event),
+                Message::Redraw => redraw_requested = true,
+                Message::Exit => return running_state.shutdown(),
+            }
+        }
+
+        if redraw_requested || draw_continuously {
+            redraw_requested = false;
+            running_state.draw()?;
+            if running_state.is_device_lost() {
+                return running_state.shutdown();
+            }
+        }
+    }
+}
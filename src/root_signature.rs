@@ -0,0 +1,370 @@
+use windows::Win32::Graphics::{Direct3D::ID3DBlob, Direct3D12::*};
+
+use crate::{error::GimsError, gpulib::GPULib};
+
+/// Creates a root signature from a version 1.1 description, whose descriptor range and root descriptor flags
+/// (e.g. `D3D12_DESCRIPTOR_RANGE_FLAG_DATA_STATIC`) let the driver optimize descriptor access.
+/// On devices that only support version 1.0, the description is converted and the flags are dropped.
+pub fn create_root_signature(
+    lib: &GPULib,
+    desc: &D3D12_ROOT_SIGNATURE_DESC1,
+) -> Result<ID3D12RootSignature, GimsError> {
+    if lib.root_signature_version().0 >= D3D_ROOT_SIGNATURE_VERSION_1_1.0 {
+        return serialize_and_create(
+            lib,
+            &D3D12_VERSIONED_ROOT_SIGNATURE_DESC {
+                Version: D3D_ROOT_SIGNATURE_VERSION_1_1,
+                Anonymous: D3D12_VERSIONED_ROOT_SIGNATURE_DESC_0 { Desc_1_1: *desc },
+            },
+        );
+    }
+
+    let parameters_1_1 = unsafe { raw_slice(desc.pParameters, desc.NumParameters) };
+
+    // Version 1.0 descriptor ranges without flags, kept alive until serialization
+    let ranges: Vec<Vec<D3D12_DESCRIPTOR_RANGE>> = parameters_1_1
+        .iter()
+        .map(|parameter| {
+            if parameter.ParameterType != D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE {
+                return Vec::new();
+            }
+            let table = unsafe { parameter.Anonymous.DescriptorTable };
+            unsafe { raw_slice(table.pDescriptorRanges, table.NumDescriptorRanges) }
+                .iter()
+                .map(|range| D3D12_DESCRIPTOR_RANGE {
+                    RangeType: range.RangeType,
+                    NumDescriptors: range.NumDescriptors,
+                    BaseShaderRegister: range.BaseShaderRegister,
+                    RegisterSpace: range.RegisterSpace,
+                    OffsetInDescriptorsFromTableStart: range.OffsetInDescriptorsFromTableStart,
+                })
+                .collect()
+        })
+        .collect();
+
+    let parameters: Vec<D3D12_ROOT_PARAMETER> = parameters_1_1
+        .iter()
+        .zip(&ranges)
+        .map(|(parameter, ranges)| D3D12_ROOT_PARAMETER {
+            ParameterType: parameter.ParameterType,
+            ShaderVisibility: parameter.ShaderVisibility,
+            Anonymous: match parameter.ParameterType {
+                D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE => D3D12_ROOT_PARAMETER_0 {
+                    DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE {
+                        NumDescriptorRanges: ranges.len() as u32,
+                        pDescriptorRanges: ranges.as_ptr(),
+                    },
+                },
+                D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS => D3D12_ROOT_PARAMETER_0 {
+                    Constants: unsafe { parameter.Anonymous.Constants },
+                },
+                _ => {
+                    let descriptor = unsafe { parameter.Anonymous.Descriptor };
+                    D3D12_ROOT_PARAMETER_0 {
+                        Descriptor: D3D12_ROOT_DESCRIPTOR {
+                            ShaderRegister: descriptor.ShaderRegister,
+                            RegisterSpace: descriptor.RegisterSpace,
+                        },
+                    }
+                }
+            },
+        })
+        .collect();
+
+    serialize_and_create(
+        lib,
+        &D3D12_VERSIONED_ROOT_SIGNATURE_DESC {
+            Version: D3D_ROOT_SIGNATURE_VERSION_1_0,
+            Anonymous: D3D12_VERSIONED_ROOT_SIGNATURE_DESC_0 {
+                Desc_1_0: D3D12_ROOT_SIGNATURE_DESC {
+                    NumParameters: parameters.len() as u32,
+                    pParameters: parameters.as_ptr(),
+                    NumStaticSamplers: desc.NumStaticSamplers,
+                    pStaticSamplers: desc.pStaticSamplers,
+                    Flags: desc.Flags,
+                },
+            },
+        },
+    )
+}
+
+/// Collects the parameters of a root signature without spelling out the unions of `D3D12_ROOT_PARAMETER1`.
+/// Parameters get their root parameter index in the order they are added.
+/// ```ignore
+/// let root_signature = RootSignatureBuilder::new()
+///     .add_root_constants(0, root_constant_count::<Constants>())
+///     .add_cbv(1, 0, D3D12_SHADER_VISIBILITY_VERTEX)
+///     .add_descriptor_table(&[sampler_heap.descriptor_range(0, 0)], D3D12_SHADER_VISIBILITY_PIXEL)
+///     .add_static_sampler(SamplerDesc::linear().static_sampler(0, 0, D3D12_SHADER_VISIBILITY_PIXEL))
+///     .build(&lib)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct RootSignatureBuilder {
+    parameters: Vec<RootParameter>,
+    static_samplers: Vec<D3D12_STATIC_SAMPLER_DESC>,
+    flags: D3D12_ROOT_SIGNATURE_FLAGS,
+}
+
+#[derive(Debug, Clone)]
+enum RootParameter {
+    Descriptor {
+        parameter_type: D3D12_ROOT_PARAMETER_TYPE,
+        descriptor: D3D12_ROOT_DESCRIPTOR1,
+        visibility: D3D12_SHADER_VISIBILITY,
+    },
+    Constants {
+        constants: D3D12_ROOT_CONSTANTS,
+        visibility: D3D12_SHADER_VISIBILITY,
+    },
+    Table {
+        ranges: Vec<D3D12_DESCRIPTOR_RANGE1>,
+        visibility: D3D12_SHADER_VISIBILITY,
+    },
+}
+
+impl Default for RootSignatureBuilder {
+    /// No parameters, with the input assembler enabled for vertex buffers
+    fn default() -> Self {
+        RootSignatureBuilder {
+            parameters: Vec::new(),
+            static_samplers: Vec::new(),
+            flags: D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT,
+        }
+    }
+}
+
+impl RootSignatureBuilder {
+    pub fn new() -> Self {
+        RootSignatureBuilder::default()
+    }
+
+    /// Adds a root constant buffer view bound to `b<register>`
+    pub fn add_cbv(self, register: u32, space: u32, visibility: D3D12_SHADER_VISIBILITY) -> Self {
+        self.add_descriptor(D3D12_ROOT_PARAMETER_TYPE_CBV, register, space, visibility)
+    }
+
+    /// Adds a root shader resource view bound to `t<register>`, which only works for buffers
+    pub fn add_srv(self, register: u32, space: u32, visibility: D3D12_SHADER_VISIBILITY) -> Self {
+        self.add_descriptor(D3D12_ROOT_PARAMETER_TYPE_SRV, register, space, visibility)
+    }
+
+    /// Adds a root unordered access view bound to `u<register>`, which only works for buffers
+    pub fn add_uav(self, register: u32, space: u32, visibility: D3D12_SHADER_VISIBILITY) -> Self {
+        self.add_descriptor(D3D12_ROOT_PARAMETER_TYPE_UAV, register, space, visibility)
+    }
+
+    fn add_descriptor(
+        mut self,
+        parameter_type: D3D12_ROOT_PARAMETER_TYPE,
+        register: u32,
+        space: u32,
+        visibility: D3D12_SHADER_VISIBILITY,
+    ) -> Self {
+        self.parameters.push(RootParameter::Descriptor {
+            parameter_type,
+            descriptor: D3D12_ROOT_DESCRIPTOR1 {
+                ShaderRegister: register,
+                RegisterSpace: space,
+                Flags: D3D12_ROOT_DESCRIPTOR_FLAG_NONE,
+            },
+            visibility,
+        });
+        self
+    }
+
+    /// Adds `num_32bit` root constants bound to `b<register>` in space 0 and visible to all stages,
+    /// see `root_constant_count` and `set_graphics_root_32bit_constants`
+    pub fn add_root_constants(mut self, register: u32, num_32bit: u32) -> Self {
+        self.parameters.push(RootParameter::Constants {
+            constants: D3D12_ROOT_CONSTANTS {
+                ShaderRegister: register,
+                RegisterSpace: 0,
+                Num32BitValues: num_32bit,
+            },
+            visibility: D3D12_SHADER_VISIBILITY_ALL,
+        });
+        self
+    }
+
+    /// Adds a descriptor table consisting of `ranges`, e.g. from `SamplerHeap::descriptor_range`
+    pub fn add_descriptor_table(
+        mut self,
+        ranges: &[D3D12_DESCRIPTOR_RANGE1],
+        visibility: D3D12_SHADER_VISIBILITY,
+    ) -> Self {
+        self.parameters.push(RootParameter::Table {
+            ranges: ranges.to_vec(),
+            visibility,
+        });
+        self
+    }
+
+    /// Adds a static sampler, e.g. from `SamplerDesc::static_sampler`
+    pub fn add_static_sampler(mut self, sampler: D3D12_STATIC_SAMPLER_DESC) -> Self {
+        self.static_samplers.push(sampler);
+        self
+    }
+
+    /// Replaces the flags, e.g. to deny shader stages access to the root signature
+    pub fn flags(mut self, flags: D3D12_ROOT_SIGNATURE_FLAGS) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Serializes and creates the root signature with `create_root_signature`
+    pub fn build(&self, lib: &GPULib) -> Result<ID3D12RootSignature, GimsError> {
+        let parameters: Vec<D3D12_ROOT_PARAMETER1> = self
+            .parameters
+            .iter()
+            .map(|parameter| match parameter {
+                RootParameter::Descriptor {
+                    parameter_type,
+                    descriptor,
+                    visibility,
+                } => D3D12_ROOT_PARAMETER1 {
+                    ParameterType: *parameter_type,
+                    Anonymous: D3D12_ROOT_PARAMETER1_0 {
+                        Descriptor: *descriptor,
+                    },
+                    ShaderVisibility: *visibility,
+                },
+                RootParameter::Constants {
+                    constants,
+                    visibility,
+                } => D3D12_ROOT_PARAMETER1 {
+                    ParameterType: D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS,
+                    Anonymous: D3D12_ROOT_PARAMETER1_0 {
+                        Constants: *constants,
+                    },
+                    ShaderVisibility: *visibility,
+                },
+                RootParameter::Table { ranges, visibility } => D3D12_ROOT_PARAMETER1 {
+                    ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+                    Anonymous: D3D12_ROOT_PARAMETER1_0 {
+                        DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE1 {
+                            NumDescriptorRanges: ranges.len() as u32,
+                            pDescriptorRanges: ranges.as_ptr(),
+                        },
+                    },
+                    ShaderVisibility: *visibility,
+                },
+            })
+            .collect();
+
+        create_root_signature(
+            lib,
+            &D3D12_ROOT_SIGNATURE_DESC1 {
+                NumParameters: parameters.len() as u32,
+                pParameters: parameters.as_ptr(),
+                NumStaticSamplers: self.static_samplers.len() as u32,
+                pStaticSamplers: self.static_samplers.as_ptr(),
+                Flags: self.flags,
+            },
+        )
+    }
+}
+
+fn serialize_and_create(
+    lib: &GPULib,
+    desc: &D3D12_VERSIONED_ROOT_SIGNATURE_DESC,
+) -> Result<ID3D12RootSignature, GimsError> {
+    let mut root_blob_option = None;
+    let mut error_blob_option: Option<ID3DBlob> = None;
+    let result = unsafe {
+        D3D12SerializeVersionedRootSignature(
+            desc,
+            &mut root_blob_option,
+            Some(&mut error_blob_option),
+        )
+    };
+    if let Err(error) = result {
+        // The error blob explains what is wrong with the description
+        return Err(match error_blob_option {
+            Some(error_blob) => format!(
+                "Failed to serialize root signature: {}",
+                String::from_utf8_lossy(unsafe { blob_data(&error_blob) })
+            )
+            .into(),
+            None => error.into(),
+        });
+    }
+
+    let root_blob = root_blob_option.ok_or("Failed to create root signature")?;
+    let root_signature = unsafe { lib.device.CreateRootSignature(0, blob_data(&root_blob)) }?;
+
+    Ok(root_signature)
+}
+
+unsafe fn blob_data(blob: &ID3DBlob) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize())
+    }
+}
+
+/// Slice from a pointer and count of a D3D12 description, which may be null if the count is 0
+unsafe fn raw_slice<'a, T>(pointer: *const T, count: u32) -> &'a [T] {
+    if count == 0 || pointer.is_null() {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(pointer, count as usize) }
+    }
+}
+
+/// Sets the graphics root constants at `root_parameter_index` to the contents of `value`,
+/// starting `offset` 32 bit values into the parameter.
+/// The size of `T` has to be a multiple of 4 bytes, which is checked at compile time.
+pub fn set_graphics_root_32bit_constants<T: bytemuck::Pod>(
+    command_list: &ID3D12GraphicsCommandList,
+    root_parameter_index: u32,
+    value: &T,
+    offset: u32,
+) {
+    let bytes = root_constant_bytes(value);
+    unsafe {
+        command_list.SetGraphicsRoot32BitConstants(
+            root_parameter_index,
+            (bytes.len() / 4) as u32,
+            bytes.as_ptr() as _,
+            offset,
+        )
+    };
+}
+
+/// Like `set_graphics_root_32bit_constants`, but for the compute root signature
+pub fn set_compute_root_32bit_constants<T: bytemuck::Pod>(
+    command_list: &ID3D12GraphicsCommandList,
+    root_parameter_index: u32,
+    value: &T,
+    offset: u32,
+) {
+    let bytes = root_constant_bytes(value);
+    unsafe {
+        command_list.SetComputeRoot32BitConstants(
+            root_parameter_index,
+            (bytes.len() / 4) as u32,
+            bytes.as_ptr() as _,
+            offset,
+        )
+    };
+}
+
+/// Number of 32 bit values `T` occupies in a root signature, e.g. for `D3D12_ROOT_CONSTANTS::Num32BitValues`
+pub const fn root_constant_count<T: bytemuck::Pod>() -> u32 {
+    const {
+        assert!(
+            size_of::<T>() % 4 == 0,
+            "Root constants must be a multiple of 4 bytes"
+        )
+    };
+    (size_of::<T>() / 4) as u32
+}
+
+fn root_constant_bytes<T: bytemuck::Pod>(value: &T) -> &[u8] {
+    const {
+        assert!(
+            size_of::<T>() % 4 == 0,
+            "Root constants must be a multiple of 4 bytes"
+        )
+    };
+    bytemuck::bytes_of(value)
+}
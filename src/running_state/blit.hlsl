@@ -0,0 +1,8 @@
+Texture2D<float4> source_texture : register(t0);
+SamplerState      source_sampler : register(s0);
+
+// Receives the output of the full screen triangle vertex shader
+float4 main(float4 position : SV_Position, float2 uv : TEXCOORD) : SV_Target
+{
+    return source_texture.SampleLevel(source_sampler, uv, 0);
+}
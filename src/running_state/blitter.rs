@@ -0,0 +1,177 @@
+use std::{cell::Cell, sync::Arc};
+
+use windows::{
+    Win32::Graphics::{Direct3D12::*, Dxgi::Common::DXGI_FORMAT},
+    core::h,
+};
+
+use crate::{
+    FrameResources,
+    barrier::{StateTracker, Transitions},
+    error::GimsError,
+    frame_data::FrameData,
+    gpulib::{GPULib, compile_shader},
+    pipeline::{create_fullscreen_pipeline, draw_fullscreen},
+    root_signature::create_root_signature,
+    sampler::SamplerDesc,
+};
+
+/// Number of blits per frame, each of which needs its own SRV slot until the frame has finished on the GPU
+const BLITS_PER_FRAME: u32 = 8;
+
+/// Copies textures into the back buffer with a full screen triangle, scaling them to the back buffer size
+pub struct Blitter {
+    lib: Arc<GPULib>,
+    root_signature: ID3D12RootSignature,
+    pipeline: ID3D12PipelineState,
+    /// Shader visible heap with `BLITS_PER_FRAME` SRV slots per frame
+    descriptor_heaps: FrameData<ID3D12DescriptorHeap>,
+    descriptor_size: usize,
+    /// Number of blits recorded in the current frame
+    blit_count: Cell<u32>,
+}
+
+impl Blitter {
+    /// `render_target_format` is the format of `FrameResources::render_target_handle`
+    pub fn new(
+        lib: Arc<GPULib>,
+        frame_count: usize,
+        render_target_format: DXGI_FORMAT,
+    ) -> Result<Self, GimsError> {
+        let sampler = SamplerDesc::linear().static_sampler(0, 0, D3D12_SHADER_VISIBILITY_PIXEL);
+        let source_range = D3D12_DESCRIPTOR_RANGE1 {
+            RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+            NumDescriptors: 1,
+            BaseShaderRegister: 0,
+            RegisterSpace: 0,
+            // The slot is written before the blit is recorded and never changed until the frame has finished
+            Flags: D3D12_DESCRIPTOR_RANGE_FLAG_DESCRIPTORS_STATIC_KEEPING_BUFFER_BOUNDS_CHECKS,
+            OffsetInDescriptorsFromTableStart: 0,
+        };
+        let parameter = D3D12_ROOT_PARAMETER1 {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+            Anonymous: D3D12_ROOT_PARAMETER1_0 {
+                DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE1 {
+                    NumDescriptorRanges: 1,
+                    pDescriptorRanges: &source_range,
+                },
+            },
+        };
+        let root_signature = create_root_signature(
+            &lib,
+            &D3D12_ROOT_SIGNATURE_DESC1 {
+                NumParameters: 1,
+                pParameters: &parameter,
+                NumStaticSamplers: 1,
+                pStaticSamplers: &sampler,
+                Flags: D3D12_ROOT_SIGNATURE_FLAG_NONE,
+            },
+        )?;
+
+        let pixel_shader = compile_shader(include_str!("blit.hlsl"), "main", "ps_6_5")?;
+        let pipeline = create_fullscreen_pipeline(
+            &lib,
+            &root_signature,
+            &pixel_shader,
+            &[render_target_format],
+        )?;
+
+        let descriptor_heaps = FrameData::try_from_fn(frame_count, |_| unsafe {
+            lib.device
+                .CreateDescriptorHeap::<ID3D12DescriptorHeap>(&D3D12_DESCRIPTOR_HEAP_DESC {
+                    Type: D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+                    NumDescriptors: BLITS_PER_FRAME,
+                    Flags: D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+                    NodeMask: 0,
+                })
+        })?;
+        let descriptor_size = unsafe {
+            lib.device
+                .GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV)
+        } as usize;
+
+        unsafe {
+            pipeline.SetName(h!("Blit pipeline"))?;
+            root_signature.SetName(h!("Blit root signature"))?;
+        }
+
+        Ok(Blitter {
+            lib,
+            root_signature,
+            pipeline,
+            descriptor_heaps,
+            descriptor_size,
+            blit_count: Cell::new(0),
+        })
+    }
+
+    /// Draws `source` over the whole back buffer. `source` is transitioned from `state` to
+    /// `D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE` for the copy and back to `state` afterwards.
+    pub fn blit(
+        &self,
+        frame_resources: &FrameResources,
+        source: &ID3D12Resource,
+        state: D3D12_RESOURCE_STATES,
+    ) -> Result<(), GimsError> {
+        let slot = self.blit_count.get();
+        if slot >= BLITS_PER_FRAME {
+            return Err(
+                format!("At most {} blits per frame are supported", BLITS_PER_FRAME).into(),
+            );
+        }
+        self.blit_count.set(slot + 1);
+
+        let descriptor_heap = self.descriptor_heaps.get_current();
+        let offset = slot as usize * self.descriptor_size;
+        let command_list = frame_resources.command_list;
+
+        // The state of the source is owned by the app, so it is not checked against the frame's tracker
+        let mut tracker = StateTracker::default();
+
+        unsafe {
+            let mut cpu_handle = descriptor_heap.GetCPUDescriptorHandleForHeapStart();
+            cpu_handle.ptr += offset;
+            self.lib
+                .device
+                .CreateShaderResourceView(source, None, cpu_handle);
+
+            if state != D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE {
+                Transitions::new(&mut tracker)
+                    .add(source, state, D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE)
+                    .record(command_list);
+            }
+
+            let mut gpu_handle = descriptor_heap.GetGPUDescriptorHandleForHeapStart();
+            gpu_handle.ptr += offset as u64;
+            command_list.OMSetRenderTargets(
+                1,
+                Some(&frame_resources.render_target_handle),
+                false,
+                None,
+            );
+            command_list.RSSetViewports(&[frame_resources.viewport]);
+            command_list.RSSetScissorRects(&[frame_resources.scissor]);
+            command_list.SetDescriptorHeaps(&[Some(descriptor_heap.clone())]);
+            command_list.SetGraphicsRootSignature(&self.root_signature);
+            command_list.SetPipelineState(&self.pipeline);
+            command_list.SetGraphicsRootDescriptorTable(0, gpu_handle);
+            draw_fullscreen(command_list);
+            frame_resources.submission_counter.add_draws(1);
+
+            if state != D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE {
+                Transitions::new(&mut tracker)
+                    .add(source, D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE, state)
+                    .record(command_list);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves on to the SRV slots of the next frame
+    pub fn end_frame(&self) {
+        self.descriptor_heaps.increment_frame();
+        self.blit_count.set(0);
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+};
+
+use image::RgbaImage;
+use windows::Win32::Graphics::{
+    Direct3D12::*,
+    Dxgi::{
+        Common::{
+            DXGI_ALPHA_MODE_PREMULTIPLIED, DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_UNORM,
+            DXGI_FORMAT_B8G8R8A8_UNORM_SRGB, DXGI_FORMAT_R8G8B8A8_UNORM,
+            DXGI_FORMAT_R8G8B8A8_UNORM_SRGB, DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC,
+        },
+        IDXGISwapChain4,
+    },
+};
+
+use crate::{
+    barrier::{StateTracker, Transitions},
+    error::GimsError,
+    gpulib::GPULib,
+    texture::{CopyableFootprint, copyable_footprint},
+};
+
+/// Captures the app requested for the current frame through `FrameResources`
+#[derive(Default)]
+pub(crate) struct CaptureRequest {
+    /// Whether the image is passed to `App::frame_captured`
+    deliver: Cell<bool>,
+    path: RefCell<Option<PathBuf>>,
+}
+
+impl CaptureRequest {
+    pub(crate) fn request(&self) {
+        self.deliver.set(true);
+    }
+
+    pub(crate) fn request_file(&self, path: PathBuf) {
+        *self.path.borrow_mut() = Some(path);
+    }
+
+    pub(crate) fn is_requested(&self) -> bool {
+        self.deliver.get() || self.path.borrow().is_some()
+    }
+
+    /// Resets the request, returning whether to deliver the image and where to save it
+    pub(crate) fn take(&self) -> (bool, Option<PathBuf>) {
+        (self.deliver.take(), self.path.take())
+    }
+}
+
+/// Whether back buffers of `format` are stored in BGRA order. Fails for formats that cannot be captured,
+/// which are those that do not fit into an 8 bit RGBA image.
+pub(crate) fn is_bgra(format: DXGI_FORMAT) -> Result<bool, GimsError> {
+    match format {
+        DXGI_FORMAT_R8G8B8A8_UNORM | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => Ok(false),
+        DXGI_FORMAT_B8G8R8A8_UNORM | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB => Ok(true),
+        format => Err(format!(
+            "Capturing back buffers of format {:?} is not supported",
+            format
+        )
+        .into()),
+    }
+}
+
+/// Copy of a back buffer in a readback buffer, recorded into the frame before it is presented.
+/// Flip model swapchains may discard the contents on present, so the copy cannot happen afterwards.
+pub(crate) struct FrameCapture {
+    readback: ID3D12Resource,
+    footprint: CopyableFootprint,
+    /// Whether the alpha channel is ignored by the compositor and saved as opaque
+    opaque: bool,
+}
+
+impl FrameCapture {
+    /// Records the copy of `render_target`, which is left in `D3D12_RESOURCE_STATE_COPY_SOURCE`
+    pub(crate) fn record(
+        lib: &GPULib,
+        command_list: &ID3D12GraphicsCommandList,
+        resource_states: &mut StateTracker,
+        swapchain: &IDXGISwapChain4,
+        render_target: &ID3D12Resource,
+    ) -> Result<Self, GimsError> {
+        let footprint = copyable_footprint(lib, &unsafe { render_target.GetDesc() }, 0)?;
+        let readback = create_readback_buffer(lib, footprint.total_bytes)?;
+        let opaque = unsafe { swapchain.GetDesc1() }?.AlphaMode != DXGI_ALPHA_MODE_PREMULTIPLIED;
+
+        Transitions::new(resource_states)
+            .add(
+                render_target,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+                D3D12_RESOURCE_STATE_COPY_SOURCE,
+            )
+            .record(command_list);
+
+        let mut destination = D3D12_TEXTURE_COPY_LOCATION {
+            pResource: std::mem::ManuallyDrop::new(Some(readback.clone())),
+            Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
+            Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                PlacedFootprint: footprint.layout,
+            },
+        };
+        let mut source = D3D12_TEXTURE_COPY_LOCATION {
+            pResource: std::mem::ManuallyDrop::new(Some(render_target.clone())),
+            Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+            Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+                SubresourceIndex: 0,
+            },
+        };
+        unsafe {
+            command_list.CopyTextureRegion(&destination, 0, 0, 0, &source, None);
+            // Release the references taken for the copy locations
+            std::mem::ManuallyDrop::drop(&mut destination.pResource);
+            std::mem::ManuallyDrop::drop(&mut source.pResource);
+        }
+
+        Ok(FrameCapture {
+            readback,
+            footprint,
+            opaque,
+        })
+    }
+
+    /// Reads the copy once the frame has finished on the GPU. The bytes are taken over unchanged:
+    /// the back buffer holds gamma encoded colors no matter whether they were written through the UNORM
+    /// or the UNORM_SRGB view, which is what PNG files store as well.
+    pub(crate) fn read(&self) -> Result<RgbaImage, GimsError> {
+        let footprint = &self.footprint.layout.Footprint;
+        let bgra = is_bgra(footprint.Format)?;
+        let row_bytes = self.footprint.row_bytes as usize;
+        let row_pitch = footprint.RowPitch as usize;
+        let height = footprint.Height as usize;
+
+        let mut pixels = Vec::with_capacity(row_bytes * height);
+        unsafe {
+            let mut pointer = std::ptr::null_mut();
+            self.readback.Map(
+                0,
+                Some(&D3D12_RANGE {
+                    Begin: 0,
+                    End: self.footprint.total_bytes as usize,
+                }),
+                Some(&mut pointer),
+            )?;
+            let data = std::slice::from_raw_parts(
+                (pointer as *const u8).add(self.footprint.layout.Offset as usize),
+                row_pitch * (height - 1) + row_bytes,
+            );
+            for row in 0..height {
+                pixels.extend_from_slice(&data[row * row_pitch..row * row_pitch + row_bytes]);
+            }
+            // Nothing was written
+            self.readback.Unmap(0, Some(&D3D12_RANGE::default()));
+        }
+
+        for pixel in pixels.chunks_exact_mut(4) {
+            if bgra {
+                pixel.swap(0, 2);
+            }
+            if self.opaque {
+                pixel[3] = u8::MAX;
+            }
+        }
+
+        Ok(
+            RgbaImage::from_raw(footprint.Width, footprint.Height, pixels)
+                .ok_or("Captured image has an unexpected size")?,
+        )
+    }
+}
+
+fn create_readback_buffer(lib: &GPULib, size: u64) -> Result<ID3D12Resource, GimsError> {
+    let mut resource_option = None;
+    unsafe {
+        lib.device.CreateCommittedResource(
+            &D3D12_HEAP_PROPERTIES {
+                Type: D3D12_HEAP_TYPE_READBACK,
+                ..Default::default()
+            },
+            D3D12_HEAP_FLAGS::default(),
+            &D3D12_RESOURCE_DESC {
+                Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+                Width: size,
+                Height: 1,
+                DepthOrArraySize: 1,
+                Alignment: 0,
+                MipLevels: 1,
+                Format: DXGI_FORMAT_UNKNOWN,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+                Flags: D3D12_RESOURCE_FLAGS::default(),
+            },
+            D3D12_RESOURCE_STATE_COPY_DEST,
+            None,
+            &mut resource_option,
+        )
+    }
+    .map_err(GimsError::resource_creation)?;
+
+    Ok(resource_option.ok_or("Failed to create frame capture readback buffer")?)
+}
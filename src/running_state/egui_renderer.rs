@@ -2,14 +2,20 @@ use egui::{
     ClippedPrimitive, TextureId,
     epaint::{Primitive, Vertex},
 };
-use std::{ffi::c_void, mem::ManuallyDrop, sync::Arc};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 use windows::{
-    Win32::Graphics::{
-        Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
-        Direct3D12::*,
-        Dxgi::Common::{
-            DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_R32_UINT, DXGI_FORMAT_R32G32_FLOAT,
-            DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC,
+    Win32::{
+        Foundation::RECT,
+        Graphics::{
+            Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+            Direct3D12::*,
+            Dxgi::Common::{
+                DXGI_FORMAT, DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_R32_UINT,
+                DXGI_FORMAT_R32G32_FLOAT,
+            },
         },
     },
     core::{h, s},
@@ -17,20 +23,27 @@ use windows::{
 use winit::{event::WindowEvent, window::Window};
 
 use crate::{
-    FrameResources,
+    EguiTextureStats, FrameResources,
+    error::GimsError,
     frame_data::FrameData,
-    gpulib::GPULib,
-    running_state::texture_manager::TextureManager,
+    gpulib::{GPULib, compile_shader},
+    pipeline::GraphicsPipelineBuilder,
+    root_signature::{root_constant_count, set_graphics_root_32bit_constants},
+    running_state::texture_manager::{EguiUserTextures, TextureManager},
+    sampler::SamplerDesc,
     vector_constant_buffer::{BufferLocation, VectorConstantBuffer},
 };
 
 struct EguiMesh {
     index_buffer: VectorConstantBuffer<u32>,
     vertex_buffer: VectorConstantBuffer<Vertex>,
-    texture: u64,
+    texture: TextureId,
+    /// Clip rectangle in physical pixels, not yet clamped to the render target
+    clip_rect: RECT,
 }
 
 /// Transforms from pixel values to D3D12 doordinate system
+#[derive(Clone, Copy)]
 #[repr(C)]
 #[repr(align(4))]
 struct RootConstants {
@@ -39,10 +52,79 @@ struct RootConstants {
     offset: [f32; 2],
 }
 
+unsafe impl bytemuck::Zeroable for RootConstants {}
+unsafe impl bytemuck::Pod for RootConstants {}
+
 #[derive(Default)]
 struct EguiFrameData {
     texture_free_queue: Vec<TextureId>,
+    /// User textures retired in this frame, released once the frame data is reused
+    retired_user_textures: Vec<(ID3D12Resource, ID3D12DescriptorHeap)>,
     meshes: Vec<EguiMesh>,
+    /// What to draw in this frame, in the order of the UI
+    primitives: Vec<EguiPrimitive>,
+}
+
+enum EguiPrimitive {
+    /// Index into the meshes of the frame
+    Mesh(usize),
+    Callback {
+        callback: Arc<PaintCallback>,
+        /// Rectangle of the callback in physical pixels
+        viewport: D3D12_VIEWPORT,
+        /// Clip rectangle in physical pixels, not yet clamped to the render target
+        clip_rect: RECT,
+    },
+}
+
+/// Custom rendering inside the UI, e.g. a small 3D preview in a window. Store it in `egui::PaintCallback::callback`:
+/// ```ignore
+/// let (rect, _) = ui.allocate_exact_size(egui::vec2(256.0, 256.0), egui::Sense::drag());
+/// ui.painter().add(egui::PaintCallback {
+///     rect,
+///     callback: Arc::new(PaintCallback::new(move |info, res| {
+///         // Viewport and scissor are already set, the render target is the one of the UI
+///         res.command_list.SetPipelineState(&pipeline);
+///         res.command_list.DrawInstanced(3, 1, 0, 0);
+///     })),
+/// });
+/// ```
+/// The callback runs while the UI is drawn at the end of the frame, between the meshes before and after it.
+/// It may change any state of the command list, the UI restores its own state afterwards.
+pub struct PaintCallback {
+    callback: Box<dyn Fn(&PaintCallbackInfo, &FrameResources) + Send + Sync>,
+}
+
+impl PaintCallback {
+    pub fn new(
+        callback: impl Fn(&PaintCallbackInfo, &FrameResources) + Send + Sync + 'static,
+    ) -> Self {
+        PaintCallback {
+            callback: Box::new(callback),
+        }
+    }
+}
+
+/// Where a `PaintCallback` draws
+#[derive(Debug, Clone, Copy)]
+pub struct PaintCallbackInfo {
+    /// Rectangle of the callback in physical pixels, may extend beyond the render target
+    pub viewport: D3D12_VIEWPORT,
+    /// Visible part of the rectangle, e.g. inside a scroll area
+    pub scissor: RECT,
+    /// Scale from egui points to physical pixels
+    pub pixels_per_point: f32,
+}
+
+/// Intersection of `rect` and `bounds`, `None` if it has no area
+fn clamp_rect(rect: RECT, bounds: &RECT) -> Option<RECT> {
+    let clamped = RECT {
+        left: rect.left.max(bounds.left),
+        top: rect.top.max(bounds.top),
+        right: rect.right.min(bounds.right),
+        bottom: rect.bottom.min(bounds.bottom),
+    };
+    (clamped.left < clamped.right && clamped.top < clamped.bottom).then_some(clamped)
 }
 
 pub struct EguiRenderer {
@@ -52,12 +134,15 @@ pub struct EguiRenderer {
     window: Arc<Window>,
     viewport_info: egui::ViewportInfo,
     texture_manager: TextureManager,
-    // Current count of meshes to draw.
-    // Old buffers will not get discarded in case the next draw needs less of them.
-    draw_count: usize,
     root_signature: ID3D12RootSignature,
     pipeline: ID3D12PipelineState,
     frame_data: FrameData<EguiFrameData>,
+    repaint_delay: Duration,
+    /// Earliest point in time at which a delayed repaint was requested through the context outside of a frame,
+    /// e.g. with `request_repaint_after` from another thread
+    requested_repaint: Arc<Mutex<Option<Instant>>>,
+    /// Scale from egui points to physical pixels of the last recorded UI
+    pixels_per_point: f32,
 }
 
 impl EguiRenderer {
@@ -65,23 +150,41 @@ impl EguiRenderer {
         lib: Arc<GPULib>,
         window: Arc<Window>,
         frame_count: usize,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
+        max_texture_side: usize,
+        render_target_format: DXGI_FORMAT,
+    ) -> Result<Self, GimsError> {
         let context = egui::Context::default();
 
+        // Wake up the event loop when a repaint is requested outside of the UI function, e.g. from another thread.
+        // Delayed requests are picked up through `requested_repaint_at` the next time the event loop waits.
+        let repaint_window = window.clone();
+        let requested_repaint = Arc::new(Mutex::new(None));
+        let callback_requested_repaint = requested_repaint.clone();
+        context.set_request_repaint_callback(move |info| {
+            if info.delay.is_zero() {
+                repaint_window.request_redraw();
+            } else if let Some(repaint_at) = Instant::now().checked_add(info.delay) {
+                let mut requested_repaint = callback_requested_repaint.lock().unwrap();
+                *requested_repaint = Some(
+                    requested_repaint.map_or(repaint_at, |requested| requested.min(repaint_at)),
+                );
+            }
+        });
+
         let egui_winit_state = egui_winit::State::new(
             context.clone(),
             egui::ViewportId::ROOT,
             &window,
             Some(window.scale_factor() as f32),
             window.theme(),
-            Some(16384),
+            Some(max_texture_side),
         );
 
         let mut viewport_info = egui::ViewportInfo::default();
         egui_winit::update_viewport_info(&mut viewport_info, &context, &window, true);
 
         let root_signature = Self::create_root_signature(&lib)?;
-        let pipeline = Self::create_pipeline(&lib, root_signature.clone())?;
+        let pipeline = Self::create_pipeline(&lib, &root_signature, render_target_format)?;
 
         let texture_manager = TextureManager::new(lib.clone())?;
 
@@ -101,25 +204,85 @@ impl EguiRenderer {
             window,
             viewport_info,
             texture_manager,
-            draw_count: 0,
             root_signature,
             pipeline,
             frame_data,
+            repaint_delay: Duration::MAX,
+            requested_repaint,
+            pixels_per_point: 1.0,
         })
     }
 
-    /// If this function returns true, the event should be excluded from further processing
-    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+    pub fn context(&self) -> &egui::Context {
+        &self.context
+    }
+
+    /// If `consumed` is true in the response, the event should be excluded from further processing.
+    /// If `repaint` is true, the UI changed and should be redrawn.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> egui_winit::EventResponse {
+        self.egui_winit_state.on_window_event(&self.window, event)
+    }
+
+    /// Creates the AccessKit adapter, which forwards the accessibility tree of the UI to screen readers.
+    /// The window must not have been visible before.
+    #[cfg(feature = "accesskit")]
+    pub fn init_accesskit<T: From<egui_winit::accesskit_winit::Event> + Send>(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        event_loop_proxy: winit::event_loop::EventLoopProxy<T>,
+    ) {
         self.egui_winit_state
-            .on_window_event(&self.window, event)
-            .consumed
+            .init_accesskit(event_loop, &self.window, event_loop_proxy);
+    }
+
+    /// Handles requests of assistive technologies. Returns true if the UI should be redrawn.
+    #[cfg(feature = "accesskit")]
+    pub fn handle_accesskit_event(
+        &mut self,
+        event: &egui_winit::accesskit_winit::WindowEvent,
+    ) -> bool {
+        use egui_winit::accesskit_winit::WindowEvent;
+
+        match event {
+            WindowEvent::InitialTreeRequested => {
+                self.context.enable_accesskit();
+                true
+            }
+            WindowEvent::ActionRequested(request) => {
+                self.egui_winit_state
+                    .on_accesskit_action_request(request.clone());
+                true
+            }
+            WindowEvent::AccessibilityDeactivated => {
+                self.context.disable_accesskit();
+                false
+            }
+        }
+    }
+
+    pub fn texture_stats(&self) -> EguiTextureStats {
+        self.texture_manager.stats()
+    }
+
+    pub fn user_textures(&self) -> &EguiUserTextures {
+        self.texture_manager.user_textures()
+    }
+
+    /// Delay after which egui wants the UI to be redrawn. `Duration::MAX` means no repaint was requested.
+    pub fn repaint_delay(&self) -> Duration {
+        self.repaint_delay
+    }
+
+    /// Point in time of the earliest delayed repaint requested outside of a frame that has not been drawn yet
+    pub fn requested_repaint_at(&self) -> Option<Instant> {
+        *self.requested_repaint.lock().unwrap()
     }
 
     /// Records the buffer state currently needed by the UI and applies it to the GPU buffers.
     pub fn record_and_apply(
         &mut self,
         ui_function: impl FnMut(&egui::Context),
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), GimsError> {
         egui_winit::update_viewport_info(
             &mut self.viewport_info,
             &self.context,
@@ -127,10 +290,25 @@ impl EguiRenderer {
             false,
         );
 
+        // This frame serves all delayed requests that are due, later ones stay pending
+        {
+            let mut requested_repaint = self.requested_repaint.lock().unwrap();
+            if requested_repaint.is_some_and(|repaint_at| repaint_at <= Instant::now()) {
+                *requested_repaint = None;
+            }
+        }
+
         let mut raw_input = self.egui_winit_state.take_egui_input(&self.window);
         raw_input.viewport_id = egui::ViewportId::ROOT;
 
         let full_output = self.context.run(raw_input, ui_function);
+        self.repaint_delay = full_output
+            .viewport_output
+            .get(&egui::ViewportId::ROOT)
+            .map(|output| output.repaint_delay)
+            .unwrap_or(Duration::MAX);
+        // Applies the cursor icon, clipboard and IME state requested by egui. The cursor icon is only
+        // set on the window when it changes, and egui requests the default cursor whenever no widget is hovered.
         self.egui_winit_state
             .handle_platform_output(&self.window, full_output.platform_output);
 
@@ -138,6 +316,7 @@ impl EguiRenderer {
             .context
             .tessellate(full_output.shapes, full_output.pixels_per_point);
 
+        self.pixels_per_point = full_output.pixels_per_point;
         self.update_primitives(&primitives)?;
 
         let textures_delta = full_output.textures_delta;
@@ -146,139 +325,198 @@ impl EguiRenderer {
 
         self.texture_manager.free(&frame_data.texture_free_queue);
         self.texture_manager.set(&textures_delta.set)?;
+        // Drops the textures retired when this frame data was last used, which the GPU has finished with
+        frame_data.retired_user_textures = self.texture_manager.take_retired_user_textures();
 
         frame_data.texture_free_queue = textures_delta.free;
         Ok(())
     }
 
-    pub fn draw(
+    pub fn draw(&self, _lib: &GPULib, frame_resources: &FrameResources) {
+        let FrameResources {
+            command_list,
+            scissor,
+            submission_counter,
+            ..
+        } = frame_resources;
+
+        self.bind_pipeline(frame_resources);
+        let frame_data = self.frame_data.get_current();
+        let mut draw_count = 0;
+        for primitive in &frame_data.primitives {
+            match primitive {
+                EguiPrimitive::Mesh(index) => {
+                    let mesh = &frame_data.meshes[*index];
+                    let Some(clip_rect) = clamp_rect(mesh.clip_rect, scissor) else {
+                        // Nothing of the mesh is visible
+                        continue;
+                    };
+                    let Some(texture) = self.texture_manager.get_descriptor_heap(mesh.texture)
+                    else {
+                        // The app has unregistered the user texture while the UI still showed it
+                        continue;
+                    };
+
+                    unsafe {
+                        command_list.RSSetScissorRects(&[clip_rect]);
+                        command_list.SetDescriptorHeaps(&[Some(texture.clone())]);
+                        command_list.SetGraphicsRootDescriptorTable(
+                            1,
+                            texture.GetGPUDescriptorHandleForHeapStart(),
+                        );
+                        command_list.IASetVertexBuffers(
+                            0,
+                            Some(&[mesh.vertex_buffer.vertex_buffer_view()]),
+                        );
+                        command_list.IASetIndexBuffer(Some(
+                            &mesh.index_buffer.index_buffer_view(DXGI_FORMAT_R32_UINT),
+                        ));
+                        command_list.DrawIndexedInstanced(
+                            mesh.index_buffer.len() as u32,
+                            1,
+                            0,
+                            0,
+                            0,
+                        );
+                    }
+                    draw_count += 1;
+                }
+                EguiPrimitive::Callback {
+                    callback,
+                    viewport,
+                    clip_rect,
+                } => {
+                    let Some(scissor) = clamp_rect(*clip_rect, scissor) else {
+                        continue;
+                    };
+                    unsafe {
+                        command_list.RSSetViewports(&[*viewport]);
+                        command_list.RSSetScissorRects(&[scissor]);
+                    }
+                    let info = PaintCallbackInfo {
+                        viewport: *viewport,
+                        scissor,
+                        pixels_per_point: self.pixels_per_point,
+                    };
+                    (callback.callback)(&info, frame_resources);
+                    // The callback may have changed any state
+                    self.bind_pipeline(frame_resources);
+                }
+            }
+        }
+        submission_counter.add_draws(draw_count);
+        self.frame_data.increment_frame();
+    }
+
+    /// Sets the render target, pipeline and transform used by all meshes of the UI
+    fn bind_pipeline(
         &self,
-        _lib: &GPULib,
         FrameResources {
             command_list,
-            render_target: _,
             render_target_handle,
-            render_target_handle_srgb: _,
             viewport,
-            scissor,
+            ..
         }: &FrameResources,
     ) {
+        // Vertices are in points, which are scaled to pixels along with the clip rectangles
         let root_constants = RootConstants {
             offset: [-1.0, 1.0],
-            scale: [2.0 / viewport.Width, -2.0 / viewport.Height],
+            scale: [
+                2.0 * self.pixels_per_point / viewport.Width,
+                -2.0 * self.pixels_per_point / viewport.Height,
+            ],
         };
-        let pointer: *const RootConstants = &root_constants;
 
         unsafe {
             command_list.OMSetRenderTargets(1, Some(render_target_handle), false, None);
             command_list.RSSetViewports(&[*viewport]);
-            command_list.RSSetScissorRects(&[*scissor]);
             command_list.SetGraphicsRootSignature(&self.root_signature);
             command_list.SetPipelineState(&self.pipeline);
             command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
-            command_list.SetGraphicsRoot32BitConstants(
-                0,
-                size_of::<RootConstants>() as u32 / 4,
-                pointer as *const c_void,
-                0,
-            );
-        }
-        let meshes = self.frame_data.get_current().meshes.as_slice();
-        for mesh in &meshes[0..self.draw_count] {
-            let texture = self
-                .texture_manager
-                .get_descriptor_heap(mesh.texture)
-                .unwrap();
-
-            unsafe {
-                command_list.SetDescriptorHeaps(&[Some(texture.clone())]);
-                command_list.SetGraphicsRootDescriptorTable(
-                    1,
-                    texture.GetGPUDescriptorHandleForHeapStart(),
-                );
-                command_list
-                    .IASetVertexBuffers(0, Some(&[mesh.vertex_buffer.vertex_buffer_view()]));
-                command_list.IASetIndexBuffer(Some(
-                    &mesh.index_buffer.index_buffer_view(DXGI_FORMAT_R32_UINT),
-                ));
-                command_list.DrawIndexedInstanced(mesh.index_buffer.len() as u32, 1, 0, 0, 0);
-            }
         }
-        self.frame_data.increment_frame();
+        set_graphics_root_32bit_constants(command_list, 0, &root_constants, 0);
     }
 
-    fn update_primitives(
-        &mut self,
-        primitives: &[ClippedPrimitive],
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let meshes = &mut self.frame_data.get_current_mut().meshes;
-        for (
-            index,
-            ClippedPrimitive {
-                clip_rect: _,
-                primitive,
-            },
-        ) in primitives.iter().enumerate()
+    fn update_primitives(&mut self, primitives: &[ClippedPrimitive]) -> Result<(), GimsError> {
+        let pixels_per_point = self.pixels_per_point;
+        let to_pixels = |rect: egui::Rect| RECT {
+            left: (rect.min.x * pixels_per_point).round() as i32,
+            top: (rect.min.y * pixels_per_point).round() as i32,
+            right: (rect.max.x * pixels_per_point).round() as i32,
+            bottom: (rect.max.y * pixels_per_point).round() as i32,
+        };
+
+        let frame_data = self.frame_data.get_current_mut();
+        frame_data.primitives.clear();
+        // Mesh buffers are reused across frames, so they only grow when a frame needs more of them
+        let mut mesh_count = 0;
+        for ClippedPrimitive {
+            clip_rect,
+            primitive,
+        } in primitives
         {
-            let Primitive::Mesh(mesh) = primitive else {
-                // Paint callbacks are not supported yet
-                continue;
-            };
-            if meshes.len() < index + 1 {
-                let index_buffer = VectorConstantBuffer::new(
-                    self.lib.clone(),
-                    mesh.indices.len(),
-                    BufferLocation::GpuUpload,
-                    Some("Egui index Buffer".to_string()),
-                )?;
-                let vertex_buffer = VectorConstantBuffer::new(
-                    self.lib.clone(),
-                    mesh.vertices.len(),
-                    BufferLocation::GpuUpload,
-                    Some("Egui vertex Buffer".to_string()),
-                )?;
-                let egui::TextureId::Managed(texture) = mesh.texture_id else {
-                    // User textures are not supported yet
-                    continue;
-                };
-
-                meshes.push(EguiMesh {
-                    index_buffer,
-                    vertex_buffer,
-                    texture,
-                });
+            match primitive {
+                Primitive::Mesh(mesh) => {
+                    if frame_data.meshes.len() < mesh_count + 1 {
+                        let index_buffer = VectorConstantBuffer::new(
+                            self.lib.clone(),
+                            mesh.indices.len(),
+                            BufferLocation::GpuUpload,
+                            Some("Egui index Buffer".to_string()),
+                        )?;
+                        let vertex_buffer = VectorConstantBuffer::new(
+                            self.lib.clone(),
+                            mesh.vertices.len(),
+                            BufferLocation::GpuUpload,
+                            Some("Egui vertex Buffer".to_string()),
+                        )?;
+                        frame_data.meshes.push(EguiMesh {
+                            index_buffer,
+                            vertex_buffer,
+                            texture: mesh.texture_id,
+                            clip_rect: RECT::default(),
+                        });
+                    }
+                    let egui_mesh = &mut frame_data.meshes[mesh_count];
+                    egui_mesh.texture = mesh.texture_id;
+                    egui_mesh.clip_rect = to_pixels(*clip_rect);
+                    egui_mesh.index_buffer.upload(&mesh.indices)?;
+                    egui_mesh.vertex_buffer.upload(&mesh.vertices)?;
+                    frame_data.primitives.push(EguiPrimitive::Mesh(mesh_count));
+                    mesh_count += 1;
+                }
+                Primitive::Callback(callback) => {
+                    let Ok(paint_callback) = callback.callback.clone().downcast::<PaintCallback>()
+                    else {
+                        // Callbacks meant for other renderers are ignored
+                        continue;
+                    };
+                    let rect = callback.rect;
+                    frame_data.primitives.push(EguiPrimitive::Callback {
+                        callback: paint_callback,
+                        viewport: D3D12_VIEWPORT {
+                            TopLeftX: rect.min.x * pixels_per_point,
+                            TopLeftY: rect.min.y * pixels_per_point,
+                            Width: rect.width() * pixels_per_point,
+                            Height: rect.height() * pixels_per_point,
+                            MinDepth: D3D12_MIN_DEPTH,
+                            MaxDepth: D3D12_MAX_DEPTH,
+                        },
+                        clip_rect: to_pixels(*clip_rect),
+                    });
+                }
             }
-            let egui_mesh = &mut meshes[index];
-            egui_mesh.index_buffer.upload(&mesh.indices)?;
-            egui_mesh.vertex_buffer.upload(&mesh.vertices)?;
         }
-        self.draw_count = primitives.len();
 
         Ok(())
     }
 
-    fn create_root_signature(
-        lib: &GPULib,
-    ) -> Result<ID3D12RootSignature, Box<dyn std::error::Error>> {
+    fn create_root_signature(lib: &GPULib) -> Result<ID3D12RootSignature, GimsError> {
         let mut root_blob_option = None;
 
-        let sampler = D3D12_STATIC_SAMPLER_DESC {
-            Filter: D3D12_FILTER_MIN_MAG_LINEAR_MIP_POINT,
-            AddressU: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
-            AddressV: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
-            AddressW: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
-            MipLODBias: 0.0,
-            MaxAnisotropy: 0,
-            ComparisonFunc: D3D12_COMPARISON_FUNC_NEVER,
-            BorderColor: D3D12_STATIC_BORDER_COLOR_OPAQUE_BLACK,
-            MinLOD: 0.0,
-            MaxLOD: f32::MAX,
-            ShaderRegister: 0,
-            RegisterSpace: 0,
-            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
-        };
-
-        let root_constant_size_32_bits = size_of::<RootConstants>() / 4;
+        let sampler = SamplerDesc::linear()
+            .with_filter(D3D12_FILTER_MIN_MAG_LINEAR_MIP_POINT)
+            .static_sampler(0, 0, D3D12_SHADER_VISIBILITY_PIXEL);
 
         let texture_range = D3D12_DESCRIPTOR_RANGE {
             RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
@@ -297,7 +535,7 @@ impl EguiRenderer {
                     Constants: D3D12_ROOT_CONSTANTS {
                         ShaderRegister: 0,
                         RegisterSpace: 0,
-                        Num32BitValues: root_constant_size_32_bits as u32,
+                        Num32BitValues: root_constant_count::<RootConstants>(),
                     },
                 },
             },
@@ -343,30 +581,11 @@ impl EguiRenderer {
 
     fn create_pipeline(
         lib: &GPULib,
-        root_signature: ID3D12RootSignature,
-    ) -> Result<ID3D12PipelineState, Box<dyn std::error::Error>> {
-        let mut vertex_shader = hassle_rs::compile_hlsl(
-            "egui.hlsl",
-            include_str!("egui.hlsl"),
-            "vertex_main",
-            "vs_6_5",
-            &[],
-            &[],
-        )?;
-        if !hassle_rs::fake_sign_dxil_in_place(&mut vertex_shader) {
-            return Err("Failed to sign vertex shader".into());
-        }
-        let mut pixel_shader = hassle_rs::compile_hlsl(
-            "egui.hlsl",
-            include_str!("egui.hlsl"),
-            "pixel_main",
-            "ps_6_5",
-            &[],
-            &[],
-        )?;
-        if !hassle_rs::fake_sign_dxil_in_place(&mut pixel_shader) {
-            return Err("Failed to sign pixel shader".into());
-        }
+        root_signature: &ID3D12RootSignature,
+        render_target_format: DXGI_FORMAT,
+    ) -> Result<ID3D12PipelineState, GimsError> {
+        let vertex_shader = compile_shader(include_str!("egui.hlsl"), "vertex_main", "vs_6_5")?;
+        let pixel_shader = compile_shader(include_str!("egui.hlsl"), "pixel_main", "ps_6_5")?;
 
         let input_element_descs = [
             D3D12_INPUT_ELEMENT_DESC {
@@ -398,83 +617,29 @@ impl EguiRenderer {
             },
         ];
 
-        let pipeline_desc = D3D12_GRAPHICS_PIPELINE_STATE_DESC {
-            InputLayout: D3D12_INPUT_LAYOUT_DESC {
-                pInputElementDescs: input_element_descs.as_ptr(),
-                NumElements: input_element_descs.len() as u32,
-            },
-            pRootSignature: ManuallyDrop::new(Some(root_signature)),
-            VS: D3D12_SHADER_BYTECODE {
-                pShaderBytecode: vertex_shader.as_ptr() as _,
-                BytecodeLength: vertex_shader.len(),
-            },
-            PS: D3D12_SHADER_BYTECODE {
-                pShaderBytecode: pixel_shader.as_ptr() as _,
-                BytecodeLength: pixel_shader.len(),
-            },
-            RasterizerState: D3D12_RASTERIZER_DESC {
-                FillMode: D3D12_FILL_MODE_SOLID,
-                CullMode: D3D12_CULL_MODE_NONE,
-                FrontCounterClockwise: false.into(),
-                DepthBias: D3D12_DEFAULT_DEPTH_BIAS,
-                DepthBiasClamp: D3D12_DEFAULT_DEPTH_BIAS_CLAMP,
-                SlopeScaledDepthBias: D3D12_DEFAULT_SLOPE_SCALED_DEPTH_BIAS,
-                DepthClipEnable: true.into(),
-                MultisampleEnable: false.into(),
-                AntialiasedLineEnable: false.into(),
-                ForcedSampleCount: 0,
-                ConservativeRaster: D3D12_CONSERVATIVE_RASTERIZATION_MODE_OFF,
-            },
-            BlendState: D3D12_BLEND_DESC {
-                AlphaToCoverageEnable: false.into(),
-                IndependentBlendEnable: false.into(),
-                RenderTarget: [
-                    D3D12_RENDER_TARGET_BLEND_DESC {
-                        BlendEnable: true.into(),
-                        LogicOpEnable: false.into(),
-                        SrcBlend: D3D12_BLEND_ONE,
-                        DestBlend: D3D12_BLEND_INV_SRC_ALPHA,
-                        BlendOp: D3D12_BLEND_OP_ADD,
-                        SrcBlendAlpha: D3D12_BLEND_ONE,
-                        DestBlendAlpha: D3D12_BLEND_ONE,
-                        BlendOpAlpha: D3D12_BLEND_OP_ADD,
-                        RenderTargetWriteMask: 0b1111,
-                        ..Default::default()
-                    },
-                    D3D12_RENDER_TARGET_BLEND_DESC::default(),
-                    D3D12_RENDER_TARGET_BLEND_DESC::default(),
-                    D3D12_RENDER_TARGET_BLEND_DESC::default(),
-                    D3D12_RENDER_TARGET_BLEND_DESC::default(),
-                    D3D12_RENDER_TARGET_BLEND_DESC::default(),
-                    D3D12_RENDER_TARGET_BLEND_DESC::default(),
-                    D3D12_RENDER_TARGET_BLEND_DESC::default(),
-                ],
-            },
-            DepthStencilState: D3D12_DEPTH_STENCIL_DESC {
-                DepthEnable: false.into(),
-                DepthFunc: D3D12_COMPARISON_FUNC_ALWAYS,
-                ..Default::default()
-            },
-            SampleMask: u32::MAX,
-            PrimitiveTopologyType: D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE,
-            NumRenderTargets: 1,
-            SampleDesc: DXGI_SAMPLE_DESC {
-                Count: 1,
-                Quality: 0,
-            },
-            RTVFormats: [
-                DXGI_FORMAT_R8G8B8A8_UNORM,
-                DXGI_FORMAT_UNKNOWN,
-                DXGI_FORMAT_UNKNOWN,
-                DXGI_FORMAT_UNKNOWN,
-                DXGI_FORMAT_UNKNOWN,
-                DXGI_FORMAT_UNKNOWN,
-                DXGI_FORMAT_UNKNOWN,
-                DXGI_FORMAT_UNKNOWN,
-            ],
+        // Textures and vertex colors use premultiplied alpha, so the source color is not multiplied again.
+        // Alpha is blended the same way, so a transparent window ends up with the coverage of UI over
+        // the app's output instead of saturating to opaque wherever UI was drawn.
+        let mut blend_state = D3D12_BLEND_DESC::default();
+        blend_state.RenderTarget[0] = D3D12_RENDER_TARGET_BLEND_DESC {
+            BlendEnable: true.into(),
+            LogicOpEnable: false.into(),
+            SrcBlend: D3D12_BLEND_ONE,
+            DestBlend: D3D12_BLEND_INV_SRC_ALPHA,
+            BlendOp: D3D12_BLEND_OP_ADD,
+            SrcBlendAlpha: D3D12_BLEND_ONE,
+            DestBlendAlpha: D3D12_BLEND_INV_SRC_ALPHA,
+            BlendOpAlpha: D3D12_BLEND_OP_ADD,
+            RenderTargetWriteMask: 0b1111,
             ..Default::default()
         };
-        let pipeline = unsafe { lib.device.CreateGraphicsPipelineState(&pipeline_desc)? };
+
+        let pipeline = GraphicsPipelineBuilder::new(root_signature, &vertex_shader)
+            .pixel_shader(&pixel_shader)
+            .input_layout(&input_element_descs)
+            .blend_state(blend_state)
+            .render_target_formats(&[render_target_format])
+            .build(lib)?;
 
         Ok(pipeline)
     }
@@ -1,17 +1,19 @@
-use std::ops::Deref;
+use std::{ops::Deref, time::Duration};
 
 use windows::Win32::{
-    Foundation::{CloseHandle, HANDLE, WAIT_OBJECT_0},
+    Foundation::{CloseHandle, HANDLE, WAIT_EVENT, WAIT_OBJECT_0, WAIT_TIMEOUT},
     System::Threading::{INFINITE, WaitForSingleObject},
 };
 
+use crate::error::GimsError;
+
 pub struct Event {
     handle: HANDLE,
 }
 
 impl Event {
     /// Creates an event with a default security descriptor, automatic reset, and no name
-    pub fn new(initially_signaled: bool) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new(initially_signaled: bool) -> Result<Self, GimsError> {
         let handle = unsafe {
             windows::Win32::System::Threading::CreateEventA(None, false, initially_signaled, None)
         }?;
@@ -19,18 +21,35 @@ impl Event {
         Ok(Event { handle })
     }
 
-    pub fn wait(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn wait(&self) -> Result<(), GimsError> {
         let result = unsafe { WaitForSingleObject(self.handle, INFINITE) };
         if result != WAIT_OBJECT_0 {
-            return Err(format!(
-                "Error while waiting for Windows event handle: {:#01X}",
-                result.0
-            )
-            .into());
+            return Err(wait_error(result));
         }
 
         Ok(())
     }
+
+    /// Waits at most `timeout`, which is rounded down to milliseconds.
+    /// Returns whether the event was signaled before the timeout elapsed.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<bool, GimsError> {
+        // INFINITE is u32::MAX, so longer timeouts are clamped just below it
+        let milliseconds = timeout.as_millis().min((INFINITE - 1) as u128) as u32;
+        let result = unsafe { WaitForSingleObject(self.handle, milliseconds) };
+        match result {
+            WAIT_OBJECT_0 => Ok(true),
+            WAIT_TIMEOUT => Ok(false),
+            _ => Err(wait_error(result)),
+        }
+    }
+}
+
+fn wait_error(result: WAIT_EVENT) -> GimsError {
+    format!(
+        "Error while waiting for Windows event handle: {:#01X}",
+        result.0
+    )
+    .into()
 }
 
 impl Deref for Event {
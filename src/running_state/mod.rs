@@ -1,45 +1,122 @@
-mod egui_renderer;
-mod event;
+pub mod blitter;
+pub(crate) mod capture;
+pub(crate) mod egui_renderer;
+pub(crate) mod event;
 mod swapchain;
-mod texture_manager;
+pub(crate) mod texture_manager;
 
-use std::mem::ManuallyDrop;
+use std::cell::Cell;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use windows::Win32::Graphics::{Direct3D12::*, Dxgi::*};
+use windows::Win32::Graphics::{
+    Direct3D12::*,
+    Dxgi::{Common::DXGI_FORMAT, *},
+};
 use windows::core::Interface;
-use winit::event::WindowEvent;
-use winit::window::Window;
+use winit::event::{DeviceEvent, ElementState, WindowEvent};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
+use winit::window::{Fullscreen, Window};
 
 use crate::FrameData;
 use crate::GPULib;
+use crate::barrier::{StateTracker, Transitions};
+use crate::error::GimsError;
+use crate::fence::Fence;
+use crate::gpu_timer::GpuTimer;
+use crate::input::InputState;
+use crate::running_state::blitter::Blitter;
+use crate::running_state::capture::{CaptureRequest, FrameCapture};
 use crate::running_state::egui_renderer::EguiRenderer;
-use crate::running_state::event::Event;
 use crate::running_state::swapchain::Swapchain;
-use crate::{App, FrameResources};
+use crate::settings::{self, GimsSettings, SubmissionCounter};
+use crate::upload_arena::UploadArena;
+use crate::{App, AppConfig, FrameResources, FrameTiming, MsaaTarget, PresentConfig, RenderMode};
 
 pub struct RunningFrameData {
     command_allocator: ID3D12CommandAllocator,
     command_list: ID3D12GraphicsCommandList10,
-    fence: ID3D12Fence,
-    event: Event,
+    /// Value of `RunningState::fence` signaled after the frame, 0 before its first submission
+    fence_value: u64,
+    upload_arena: UploadArena,
 }
 
+/// Fields are dropped in declaration order: the app and UI resources first,
+/// then the per-frame command lists and finally the swapchain and the device.
 pub struct RunningState<T> {
-    lib: Arc<GPULib>,
     app: T,
-    swapchain: Swapchain,
+    /// `None` if egui is disabled in the app config
+    egui_renderer: Option<EguiRenderer>,
+    blitter: Blitter,
     frame_data: FrameData<RunningFrameData>,
-    egui_renderer: EguiRenderer,
+    /// Signaled after every frame, the frames remember their values
+    fence: Fence,
+    /// Measures the GPU time of the frame command lists
+    gpu_timer: GpuTimer,
+    swapchain: Swapchain,
+    lib: Arc<GPULib>,
+    resource_states: StateTracker,
+    render_mode: RenderMode,
+    /// Point in time at which egui requested the next repaint, if any
+    repaint_at: Option<Instant>,
+    /// Number of frames submitted so far
+    frames_submitted: u64,
+    /// Flags for the next present, reset after every frame
+    present_flags: Cell<DXGI_PRESENT>,
+    /// Present settings exposed to the app while drawing, written back to `settings` afterwards
+    present_config: Cell<PresentConfig>,
+    /// Whether the window was reported as occluded by the last present
+    occluded: bool,
+    /// Whether the window has a zero client area, which happens when it is minimized
+    minimized: bool,
+    /// Occlusion state reported by winit
+    window_occluded: bool,
+    /// Modifier keys currently held down
+    modifiers: ModifiersState,
+    /// Keyboard and mouse state, updated from the events egui did not consume
+    input: InputState,
+    /// Factory used to detect changes of the adapter topology. Replaced by a new one once it is stale.
+    factory: IDXGIFactory7,
+    /// Set once the adapter of the device has disappeared, after which nothing is drawn anymore
+    device_lost: bool,
+    /// Runtime adjustable settings, applied after the UI of each frame
+    settings: GimsSettings,
+    /// Whether the settings panel is shown on top of the app's UI
+    settings_panel: bool,
+    /// Work recorded in the current frame
+    submission_counter: SubmissionCounter,
+    /// Creation time, from which `FrameTiming::elapsed` is measured
+    start_time: Instant,
+    /// Start of the previous frame, for the frame stats and the frame rate limit
+    last_frame_start: Option<Instant>,
+    /// Set by `shutdown`, after which no GPU work is in flight
+    shut_down: bool,
+    /// See `AppConfig::frame_timeout`
+    frame_timeout: Option<Duration>,
+    /// See `AppConfig::fullscreen_key`
+    fullscreen_key: Option<KeyCode>,
+    /// Capture requested by the app during the current frame
+    capture: CaptureRequest,
 }
 
+/// The reason for the removal of `device`, or `None` if it is still usable
+fn device_removed_reason(device: &ID3D12Device9) -> Option<windows::core::HRESULT> {
+    unsafe { device.GetDeviceRemovedReason() }
+        .err()
+        .map(|error| error.code())
+}
+
+/// Interval in which an occluded window checks whether it became visible again
+const OCCLUSION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 impl<T: App> RunningState<T> {
     pub fn new(
         window: Window,
         lib: Arc<GPULib>,
         app: T,
-        frame_count: usize,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
+        app_config: &AppConfig,
+    ) -> Result<Self, GimsError> {
+        let frame_count = app_config.frame_count;
         let window = Arc::new(window);
         let window_size = window.inner_size();
         let swapchain = Swapchain::new(
@@ -47,7 +124,7 @@ impl<T: App> RunningState<T> {
             window.clone(),
             window_size.width,
             window_size.height,
-            3,
+            app_config,
         )?;
 
         let frame_data = FrameData::try_from_fn(frame_count, |_| {
@@ -63,138 +140,606 @@ impl<T: App> RunningState<T> {
                 )
             }?;
 
-            // All fences start in the signaled state
-            let fence = unsafe { lib.device.CreateFence(1, D3D12_FENCE_FLAG_NONE) }?;
+            let upload_arena = UploadArena::new(lib.clone(), app_config.upload_arena_size)?;
 
-            let event = Event::new(false)?;
-
-            Ok::<_, Box<dyn std::error::Error>>(RunningFrameData {
+            Ok::<_, GimsError>(RunningFrameData {
                 command_allocator,
                 command_list,
-                fence,
-                event,
+                fence_value: 0,
+                upload_arena,
             })
         })?;
+        let fence = Fence::new(&lib)?;
+        let gpu_timer = GpuTimer::new(&lib, frame_count)?;
 
-        let egui_renderer = EguiRenderer::new(lib.clone(), window, frame_count)?;
+        let egui_renderer = if app_config.enable_egui {
+            let hardware_max_texture_side = lib.max_texture_dimension()? as usize;
+            let egui_max_texture_side = app_config
+                .egui_max_texture_side
+                .map_or(hardware_max_texture_side, |side| {
+                    side.min(hardware_max_texture_side)
+                });
+            Some(EguiRenderer::new(
+                lib.clone(),
+                window.clone(),
+                frame_count,
+                egui_max_texture_side,
+                app_config.swapchain_format,
+            )?)
+        } else {
+            None
+        };
+
+        let blitter = Blitter::new(lib.clone(), frame_count, app_config.swapchain_format)?;
+
+        let settings = GimsSettings {
+            present: app_config.present,
+            max_frame_rate: None,
+            fullscreen: window.fullscreen().is_some(),
+            adapter: lib.info.clone(),
+            frame_stats: Default::default(),
+        };
+
+        // Kick off the first frame explicitly instead of relying on the platform
+        // to send an initial redraw once the window becomes visible
+        window.request_redraw();
 
         Ok(RunningState {
+            factory: lib.factory.clone(),
+            device_lost: false,
             lib,
             app,
             swapchain,
             frame_data,
+            fence,
+            gpu_timer,
             egui_renderer,
+            blitter,
+            resource_states: StateTracker::default(),
+            render_mode: app_config.render_mode,
+            repaint_at: None,
+            frames_submitted: 0,
+            present_flags: Cell::new(DXGI_PRESENT::default()),
+            present_config: Cell::new(app_config.present),
+            occluded: false,
+            minimized: window_size.width == 0 || window_size.height == 0,
+            window_occluded: false,
+            modifiers: ModifiersState::empty(),
+            input: InputState::default(),
+            settings,
+            settings_panel: app_config.settings_panel,
+            submission_counter: SubmissionCounter::default(),
+            start_time: Instant::now(),
+            last_frame_start: None,
+            shut_down: false,
+            frame_timeout: app_config.frame_timeout,
+            fullscreen_key: app_config.fullscreen_key,
+            capture: CaptureRequest::default(),
         })
     }
 
-    pub fn draw(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn draw(&mut self) -> Result<(), GimsError> {
+        if self.device_lost {
+            return Ok(());
+        }
+        if !self.adapter_available()? {
+            self.device_lost = true;
+            self.app.device_lost();
+            return Ok(());
+        }
+
+        // Rendering is paused while the window is occluded. A test present checks if it is visible again
+        // without presenting anything.
+        if self.occluded {
+            let status = unsafe { self.swapchain.swapchain.Present(0, DXGI_PRESENT_TEST) };
+            if status == DXGI_STATUS_OCCLUDED {
+                self.repaint_at = Instant::now().checked_add(OCCLUSION_POLL_INTERVAL);
+                return Ok(());
+            }
+            self.occluded = false;
+        }
+
+        let frame_start = Instant::now();
+        let delta = self
+            .last_frame_start
+            .map_or(Duration::ZERO, |last_frame_start| {
+                frame_start - last_frame_start
+            });
+        if self.last_frame_start.is_some() {
+            self.settings.frame_stats.update(delta);
+        }
+        self.last_frame_start = Some(frame_start);
+        let timing = FrameTiming {
+            delta,
+            elapsed: frame_start - self.start_time,
+            frame_index: self.frames_submitted,
+        };
+
         let RunningFrameData {
             command_allocator,
             command_list,
-            fence,
-            event,
+            fence_value,
+            upload_arena,
         } = self.frame_data.get_current_mut();
 
         unsafe {
-            // Wait for completion of the frame and immediately reset the fence
-            fence.SetEventOnCompletion(1, **event)?;
-            event.wait()?;
-            fence.Signal(0)?;
+            // Wait for completion of the frame. Frames that have never been submitted are complete.
+            match self.frame_timeout {
+                Some(timeout) => {
+                    if !self.fence.wait_for_timeout(*fence_value, timeout)? {
+                        // A hung GPU is usually reset by the driver, which removes the device
+                        if let Some(reason) = device_removed_reason(&self.lib.device) {
+                            self.device_lost = true;
+                            self.app.device_lost();
+                            return Err(GimsError::DeviceRemoved(reason));
+                        }
+                        return Err(GimsError::GpuTimeout(timeout));
+                    }
+                }
+                None => self.fence.wait_for(*fence_value)?,
+            }
+            upload_arena.reset()?;
 
-            self.egui_renderer
-                .record_and_apply(|ctx| self.app.record_ui(ctx))?;
+            if let Some(egui_renderer) = &mut self.egui_renderer {
+                // The window may have left fullscreen without the panel, e.g. through the system menu
+                self.settings.fullscreen = self.swapchain.window.fullscreen().is_some();
+                egui_renderer.record_and_apply(|ctx| {
+                    self.app.record_ui(ctx);
+                    if self.settings_panel {
+                        settings::show_settings(ctx, &mut self.settings);
+                    }
+                })?;
+                Self::apply_settings(&self.settings, &mut self.swapchain);
+            }
 
-            command_allocator.Reset()?;
+            self.fence
+                .reset_command_allocator(command_allocator, *fence_value)?;
             command_list.Reset(&*command_allocator, None)?;
+        }
+        // The frame that used the queries before has finished, the fence was waited for above
+        self.gpu_timer.begin(command_list)?;
+        self.settings.frame_stats.gpu_frame_ms = self.gpu_timer.last_frame_ms();
 
-            command_list.ResourceBarrier(&[transition(
-                self.swapchain.current_render_target(),
-                D3D12_RESOURCE_STATE_PRESENT,
+        let mut transitions = Transitions::new(&mut self.resource_states).add(
+            self.swapchain.current_render_target(),
+            D3D12_RESOURCE_STATE_PRESENT,
+            D3D12_RESOURCE_STATE_RENDER_TARGET,
+        );
+        // A new multisampled target starts in the common state and stays a render target afterwards
+        if let Some(msaa_target) = &mut self.swapchain.msaa_target
+            && msaa_target.state != D3D12_RESOURCE_STATE_RENDER_TARGET
+        {
+            transitions = transitions.add(
+                &msaa_target.resource,
+                msaa_target.state,
                 D3D12_RESOURCE_STATE_RENDER_TARGET,
-            )]);
+            );
+            msaa_target.state = D3D12_RESOURCE_STATE_RENDER_TARGET;
         }
+        transitions.record(command_list);
 
         let (render_target_handle, render_target_handle_srgb) =
             self.swapchain.current_render_target_handle();
 
+        self.present_config.set(self.settings.present);
         let frame_resources = FrameResources {
             command_list,
+            window: &self.swapchain.window,
             render_target: self.swapchain.current_render_target(),
             render_target_handle,
             render_target_handle_srgb,
+            render_target_format: self.swapchain.format,
+            render_target_format_srgb: swapchain::srgb_view_format(self.swapchain.format),
             viewport: self.swapchain.viewport,
             scissor: self.swapchain.scissor,
+            egui_context: self.egui_renderer.as_ref().map(EguiRenderer::context),
+            present_flags: &self.present_flags,
+            present_config: &self.present_config,
+            upload_arena,
+            extra_render_target_handles: &self.swapchain.extra_render_target_handles,
+            msaa_target: self.swapchain.msaa_target.as_ref(),
+            depth_stencil_handles: &self.swapchain.depth_stencil_handles,
+            egui_texture_stats: self.egui_renderer.as_ref().map(EguiRenderer::texture_stats),
+            egui_user_textures: self.egui_renderer.as_ref().map(EguiRenderer::user_textures),
+            minimized: self.minimized,
+            occluded: self.window_occluded,
+            modifiers: self.modifiers,
+            input: &self.input,
+            timing,
+            gpu_frame_ms: self.gpu_timer.last_frame_ms(),
+            submission_counter: &self.submission_counter,
+            blitter: &self.blitter,
+            capture: &self.capture,
         };
-        self.app.draw(&frame_resources)?;
-        self.egui_renderer.draw(&self.lib, &frame_resources);
+        let result = self.app.draw(&frame_resources);
+        self.blitter.end_frame();
+        result?;
+        if let Some(msaa_target) = frame_resources.msaa_target {
+            Self::resolve_msaa_target(
+                &mut self.resource_states,
+                command_list,
+                msaa_target,
+                frame_resources.render_target,
+                frame_resources.render_target_format,
+            );
+        }
+        if let Some(egui_renderer) = &self.egui_renderer {
+            egui_renderer.draw(&self.lib, &frame_resources);
+        }
 
-        unsafe {
-            command_list.ResourceBarrier(&[transition(
+        // The copy has to be recorded before the present, which may discard the back buffer
+        let capture = if self.capture.is_requested() {
+            Some(FrameCapture::record(
+                &self.lib,
+                command_list,
+                &mut self.resource_states,
+                &self.swapchain.swapchain,
                 self.swapchain.current_render_target(),
-                D3D12_RESOURCE_STATE_RENDER_TARGET,
+            )?)
+        } else {
+            None
+        };
+        let render_target_state = if capture.is_some() {
+            D3D12_RESOURCE_STATE_COPY_SOURCE
+        } else {
+            D3D12_RESOURCE_STATE_RENDER_TARGET
+        };
+        Transitions::new(&mut self.resource_states)
+            .add(
+                self.swapchain.current_render_target(),
+                render_target_state,
                 D3D12_RESOURCE_STATE_PRESENT,
-            )]);
+            )
+            .record(command_list);
+
+        // Applied field by field, the command list still borrows the frame data
+        self.settings.present = self.present_config.get();
+        self.swapchain.set_present_config(self.settings.present);
+        self.submission_counter.add_command_lists(1);
+        self.settings.frame_stats.submissions = self.submission_counter.take();
 
+        self.gpu_timer.end(command_list);
+
+        unsafe {
             command_list.Close()?;
             self.lib
                 .queue
                 .ExecuteCommandLists(&[Some(command_list.cast()?)]);
-            self.lib.queue.Signal(&*fence, 1)?;
+            *fence_value = self.fence.signal(&self.lib.queue)?;
 
             // Present operation will be appended to the main queue
-            if self
-                .swapchain
-                .swapchain
-                .Present(1, DXGI_PRESENT::default())
-                .is_err()
-            {
+            let present_flags = self.present_flags.replace(DXGI_PRESENT::default());
+            let status = self.swapchain.present(present_flags);
+            if status == DXGI_ERROR_DEVICE_REMOVED || status == DXGI_ERROR_DEVICE_RESET {
+                // The present only reports that the device is gone, the device knows why
+                let reason = device_removed_reason(&self.lib.device).unwrap_or(status);
+                self.device_lost = true;
+                self.app.device_lost();
+                return Err(GimsError::DeviceRemoved(reason));
+            }
+            if status.is_err() {
                 return Err("DXGI present failed".into());
             }
+            self.occluded = status == DXGI_STATUS_OCCLUDED;
+        }
+
+        if let Some(capture) = capture {
+            // Taken first, so a failed capture is not repeated in the following frames
+            let (deliver, path) = self.capture.take();
+            self.fence.wait_latest()?;
+            // A failed screenshot is reported without ending the app
+            match capture.read() {
+                Ok(image) => {
+                    if let Some(path) = path
+                        && let Err(error) = image.save(&path)
+                    {
+                        println!("Failed to save screenshot {}: {}", path.display(), error);
+                    }
+                    if deliver {
+                        self.app.frame_captured(image);
+                    }
+                }
+                Err(error) => println!("Failed to capture frame: {}", error),
+            }
         }
 
         self.frame_data.increment_frame();
-        self.swapchain.window.request_redraw();
+        self.frames_submitted += 1;
+        self.input.end_frame();
+
+        let repaint_delay = self
+            .egui_renderer
+            .as_ref()
+            .map_or(Duration::MAX, EguiRenderer::repaint_delay);
+        let frame_rate_limit = self.settings.max_frame_rate.map(|max_frame_rate| {
+            frame_start + Duration::from_secs_f64(1.0 / max_frame_rate as f64)
+        });
+        match self.render_mode {
+            RenderMode::Continuous if self.occluded => {}
+            // The redraw is requested once `repaint_at` is reached
+            RenderMode::Continuous if frame_rate_limit.is_some() => {}
+            RenderMode::Continuous => self.swapchain.window.request_redraw(),
+            RenderMode::Reactive if repaint_delay.is_zero() => {
+                self.swapchain.window.request_redraw()
+            }
+            RenderMode::Reactive => {}
+        }
+        self.repaint_at = if self.occluded {
+            Instant::now().checked_add(OCCLUSION_POLL_INTERVAL)
+        } else if self.render_mode == RenderMode::Continuous
+            && let Some(frame_rate_limit) = frame_rate_limit
+        {
+            Some(frame_rate_limit)
+        } else {
+            Instant::now().checked_add(repaint_delay)
+        };
 
         Ok(())
     }
 
-    pub fn event(&mut self, event: &WindowEvent) {
-        // Let egui handle events and decide if they should be ignored from further processing
-        if self.egui_renderer.handle_event(event) {
-            return;
+    /// Resolves the multisampled target into the back buffer, so the UI is drawn without multisampling on top
+    fn resolve_msaa_target(
+        resource_states: &mut StateTracker,
+        command_list: &ID3D12GraphicsCommandList,
+        msaa_target: &MsaaTarget,
+        render_target: &ID3D12Resource,
+        format: DXGI_FORMAT,
+    ) {
+        Transitions::new(resource_states)
+            .add(
+                &msaa_target.resource,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+                D3D12_RESOURCE_STATE_RESOLVE_SOURCE,
+            )
+            .add(
+                render_target,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+                D3D12_RESOURCE_STATE_RESOLVE_DEST,
+            )
+            .record(command_list);
+        unsafe {
+            command_list.ResolveSubresource(render_target, 0, &msaa_target.resource, 0, format)
+        };
+        Transitions::new(resource_states)
+            .add(
+                &msaa_target.resource,
+                D3D12_RESOURCE_STATE_RESOLVE_SOURCE,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+            )
+            .add(
+                render_target,
+                D3D12_RESOURCE_STATE_RESOLVE_DEST,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+            )
+            .record(command_list);
+    }
+
+    /// Checks whether the adapter of the device still exists. Adding or removing adapters, e.g. plugging in
+    /// an external GPU or restarting a display driver, makes the factory stale, which is cheap to poll.
+    /// Only then the adapter is looked up in a new factory.
+    fn adapter_available(&mut self) -> Result<bool, GimsError> {
+        if unsafe { self.factory.IsCurrent() }.as_bool() {
+            return Ok(true);
         }
 
-        if let WindowEvent::Resized(_new_size) = event {
-            // Resize swapchain
+        self.factory = unsafe { CreateDXGIFactory2(DXGI_CREATE_FACTORY_FLAGS(0)) }?;
+        let luid = unsafe { self.lib.adapter.GetDesc1() }?.AdapterLuid;
+        let adapter = unsafe { self.factory.EnumAdapterByLuid::<IDXGIAdapter1>(luid) };
+
+        Ok(adapter.is_ok())
+    }
+
+    /// Whether the adapter of the device was removed and drawing has stopped
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost
+    }
+
+    /// Sets the number of vertical blanks to wait for per present, 0 disables vsync
+    pub fn set_present_interval(&mut self, sync_interval: u32) {
+        self.set_present_config(PresentConfig {
+            sync_interval,
+            ..self.settings.present
+        });
+    }
+
+    /// Sets vsync and tearing for the following presents
+    pub fn set_present_config(&mut self, present_config: PresentConfig) {
+        self.settings.present = present_config;
+        self.swapchain.set_present_config(present_config);
+    }
+
+    /// Applies changes made in the settings panel
+    fn apply_settings(settings: &GimsSettings, swapchain: &mut Swapchain) {
+        if settings.present != swapchain.present_config() {
+            swapchain.set_present_config(settings.present);
+        }
+        if settings.fullscreen != swapchain.window.fullscreen().is_some() {
+            swapchain
+                .window
+                .set_fullscreen(settings.fullscreen.then_some(Fullscreen::Borderless(None)));
+        }
+    }
+
+    /// Switches between borderless fullscreen and the normal window, see `FrameResources::toggle_fullscreen`
+    pub fn toggle_fullscreen(&mut self) {
+        crate::toggle_fullscreen(&self.swapchain.window);
+        self.settings.fullscreen = self.swapchain.window.fullscreen().is_some();
+    }
+
+    /// Whether the next frame should be drawn right after this one, without waiting for events
+    pub fn draws_continuously(&self) -> bool {
+        self.render_mode == RenderMode::Continuous
+            && !self.occluded
+            && self.settings.max_frame_rate.is_none()
+    }
+
+    /// Point in time at which the UI wants to be redrawn, e.g. for animations or tooltips,
+    /// or at which an occluded window checks its visibility again.
+    /// `None` if no repaint was requested.
+    pub fn repaint_at(&self) -> Option<Instant> {
+        // An occluded window draws nothing, so only its visibility is polled
+        if self.occluded {
+            return self.repaint_at;
+        }
+        let requested_repaint = self
+            .egui_renderer
+            .as_ref()
+            .and_then(EguiRenderer::requested_repaint_at);
+        match (self.repaint_at, requested_repaint) {
+            (Some(repaint_at), Some(requested_repaint)) => Some(repaint_at.min(requested_repaint)),
+            (repaint_at, requested_repaint) => repaint_at.or(requested_repaint),
+        }
+    }
+
+    /// Connects the UI to screen readers and shows the window, which has to be created invisible for this
+    #[cfg(feature = "accesskit")]
+    pub fn init_accesskit<E: From<egui_winit::accesskit_winit::Event> + Send>(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        event_loop_proxy: winit::event_loop::EventLoopProxy<E>,
+    ) {
+        if let Some(egui_renderer) = &mut self.egui_renderer {
+            egui_renderer.init_accesskit(event_loop, event_loop_proxy);
+        }
+        self.swapchain.window.set_visible(true);
+    }
+
+    #[cfg(feature = "accesskit")]
+    pub fn accesskit_event(&mut self, event: &egui_winit::accesskit_winit::Event) {
+        if let Some(egui_renderer) = &mut self.egui_renderer
+            && egui_renderer.handle_accesskit_event(&event.window_event)
+        {
+            self.swapchain.window.request_redraw();
+        }
+    }
+
+    pub fn request_redraw(&self) {
+        self.swapchain.window.request_redraw();
+    }
+
+    /// Events of input devices, which are not tied to a window
+    pub fn device_event(&mut self, event: &DeviceEvent) {
+        self.input.handle_device_event(event);
+    }
+
+    pub fn event(&mut self, event: &WindowEvent) -> Result<(), GimsError> {
+        // Visibility and modifiers are tracked regardless of whether egui consumes the event
+        let was_hidden = self.minimized || self.window_occluded;
+        match event {
+            WindowEvent::Resized(size) => self.minimized = size.width == 0 || size.height == 0,
+            WindowEvent::Occluded(occluded) => self.window_occluded = *occluded,
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            _ => {}
+        }
+        let hidden = self.minimized || self.window_occluded;
+        if hidden != was_hidden {
+            self.app.visibility_changed(hidden);
         }
+
+        // Let egui handle events and decide if they should be ignored from further processing.
+        // Without egui, every event may change what the app draws.
+        let response = match &mut self.egui_renderer {
+            Some(egui_renderer) => egui_renderer.handle_event(event),
+            None => egui_winit::EventResponse {
+                consumed: false,
+                repaint: true,
+            },
+        };
+        if response.repaint && self.render_mode == RenderMode::Reactive {
+            self.swapchain.window.request_redraw();
+        }
+        self.input.handle_event(event, response.consumed);
+        if response.consumed {
+            return Ok(());
+        }
+
+        if let WindowEvent::KeyboardInput { event, .. } = event
+            && event.state == ElementState::Pressed
+            && !event.repeat
+            && let Some(fullscreen_key) = self.fullscreen_key
+            && event.physical_key == PhysicalKey::Code(fullscreen_key)
+        {
+            // The resulting resize event resizes the swapchain like any other
+            self.toggle_fullscreen();
+        }
+
+        // Also reached when entering or leaving fullscreen
+        if let WindowEvent::Resized(new_size) = event {
+            // The old back buffers are released, so their states must not be carried over to the new ones
+            for render_target in &self.swapchain.render_targets {
+                self.resource_states.forget(render_target);
+            }
+            if let Some(msaa_target) = &self.swapchain.msaa_target {
+                self.resource_states.forget(&msaa_target.resource);
+            }
+            // Resizing waits for the GPU, which is where a removed device shows up outside of `draw`
+            if self
+                .swapchain
+                .resize(new_size.width, new_size.height)
+                .map_err(|error| self.check_device_removed(error))?
+            {
+                self.app.resized(new_size.width, new_size.height);
+            }
+            self.swapchain.window.request_redraw();
+        }
+
+        Ok(())
     }
 }
 
-fn transition(
-    resource: &ID3D12Resource,
-    before: D3D12_RESOURCE_STATES,
-    after: D3D12_RESOURCE_STATES,
-) -> D3D12_RESOURCE_BARRIER {
-    D3D12_RESOURCE_BARRIER {
-        Type: D3D12_RESOURCE_BARRIER_TYPE_TRANSITION,
-        Anonymous: D3D12_RESOURCE_BARRIER_0 {
-            Transition: ManuallyDrop::new(D3D12_RESOURCE_TRANSITION_BARRIER {
-                pResource: unsafe { std::mem::transmute_copy(resource) },
-                Subresource: D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
-                StateBefore: before,
-                StateAfter: after,
-            }),
-        },
-        ..Default::default()
+impl<T: App> RunningState<T> {
+    /// Replaces `error` with `GimsError::DeviceRemoved` and the reason reported by the device if the device
+    /// has been removed, and notifies the app the first time. Other errors are returned unchanged.
+    fn check_device_removed(&mut self, error: GimsError) -> GimsError {
+        let Some(reason) = device_removed_reason(&self.lib.device) else {
+            return error;
+        };
+        if !self.device_lost {
+            self.device_lost = true;
+            self.app.device_lost();
+        }
+        GimsError::DeviceRemoved(reason)
+    }
+}
+
+impl<T> RunningState<T> {
+    /// Waits until the GPU has finished all submitted frames, so all resources can be released afterwards.
+    /// Called on exit and from `Drop` as a fallback. The wait is bounded by `AppConfig::frame_timeout`,
+    /// so a hung GPU fails with `GimsError::GpuTimeout` instead of blocking the exit forever.
+    pub fn shutdown(&mut self) -> Result<(), GimsError> {
+        if self.shut_down {
+            return Ok(());
+        }
+        self.shut_down = true;
+
+        // A removed device never completes its work
+        if self.device_lost {
+            self.swapchain.skip_wait_on_drop();
+            return Ok(());
+        }
+
+        // Flushing the queue also covers work that was submitted without reaching its frame fence signal
+        match self.frame_timeout {
+            Some(timeout) => {
+                if !self.lib.wait_idle_timeout(timeout)? {
+                    self.swapchain.skip_wait_on_drop();
+                    return Err(GimsError::GpuTimeout(timeout));
+                }
+            }
+            None => self.lib.wait_idle()?,
+        }
+
+        Ok(())
     }
 }
 
 impl<T> Drop for RunningState<T> {
     fn drop(&mut self) {
-        // Wait for all frames to finish
-        self.frame_data.for_each_frame(|frame| unsafe {
-            frame.fence.SetEventOnCompletion(1, *frame.event).unwrap();
-            frame.event.wait().unwrap();
-        });
+        if let Err(error) = self.shutdown() {
+            println!(
+                "Error while waiting for the GPU during shutdown:\n{}",
+                error
+            );
+        }
     }
 }
@@ -1,17 +1,16 @@
-use std::{ffi::c_void, sync::Arc};
+use std::sync::Arc;
 
 use windows::Win32::Foundation::{HWND, RECT};
 use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::DirectComposition::*;
 use windows::Win32::Graphics::Dxgi::Common::*;
 use windows::Win32::Graphics::Dxgi::*;
-use windows::core::{HSTRING, Interface};
-use winit::{
-    raw_window_handle::{HasWindowHandle, RawWindowHandle},
-    window::Window,
-};
+use windows::core::{HRESULT, HSTRING, Interface};
+use winit::window::Window;
 
-use crate::GPULib;
-use crate::running_state::event::Event;
+use crate::error::GimsError;
+use crate::texture::{self, TextureDesc};
+use crate::{AppConfig, GPULib, MsaaTarget, PresentConfig, window_hwnd};
 
 pub struct Swapchain {
     pub render_target_heap: ID3D12DescriptorHeap,
@@ -21,6 +20,22 @@ pub struct Swapchain {
     pub window: Arc<Window>,
     pub viewport: D3D12_VIEWPORT,
     pub scissor: RECT,
+    /// Free RTV slots in `render_target_heap` after the swapchain's own views, reserved for the app
+    pub extra_render_target_handles: Vec<D3D12_CPU_DESCRIPTOR_HANDLE>,
+    /// Heap for depth stencil views reserved for the app, if any were requested
+    pub depth_stencil_heap: Option<ID3D12DescriptorHeap>,
+    pub depth_stencil_handles: Vec<D3D12_CPU_DESCRIPTOR_HANDLE>,
+    /// Multisampled target the app draws into, if multisampling is enabled
+    pub msaa_target: Option<MsaaTarget>,
+    /// Format of the back buffers and of the views in `render_target_heap`, see `AppConfig::swapchain_format`
+    pub format: DXGI_FORMAT,
+    present_config: PresentConfig,
+    /// Whether the swapchain was created with tearing support
+    tearing: bool,
+    /// Binds the swapchain to the window if it is transparent
+    composition: Option<Composition>,
+    /// Whether dropping waits for the GPU, which is skipped once the GPU will not finish its work
+    wait_on_drop: bool,
     lib: Arc<GPULib>,
 }
 
@@ -30,43 +45,65 @@ impl Swapchain {
         window: Arc<Window>,
         width: u32,
         height: u32,
-        frame_count: u32,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        let RawWindowHandle::Win32(window_handle) = window.window_handle()?.as_raw() else {
-            return Err("Failed to get Win32 window handle".into());
+        app_config: &AppConfig,
+    ) -> Result<Self, GimsError> {
+        let buffer_count = app_config.swapchain_buffer_count;
+        let extra_render_target_views = app_config.extra_render_target_views;
+        let depth_stencil_views = app_config.depth_stencil_views;
+        let present_config = app_config.present;
+        let format = app_config.swapchain_format;
+
+        let hwnd = window_hwnd(&window)?;
+
+        // Tearing support is requested whenever available, so `allow_tearing` can be toggled at runtime
+        let tearing = tearing_supported(&lib.factory);
+        let flags = if tearing {
+            DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING.0 as u32
+        } else {
+            0
         };
 
         let desc = DXGI_SWAP_CHAIN_DESC1 {
             Width: width,
             Height: height,
-            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            Format: format,
             SampleDesc: Common::DXGI_SAMPLE_DESC {
                 Count: 1,
                 Quality: 0,
             },
             BufferUsage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
-            BufferCount: frame_count,
-            SwapEffect: DXGI_SWAP_EFFECT_FLIP_DISCARD,
-            Scaling: DXGI_SCALING_STRETCH,
-            AlphaMode: DXGI_ALPHA_MODE_IGNORE,
+            BufferCount: buffer_count,
+            SwapEffect: app_config.swap_effect,
+            Scaling: app_config.swapchain_scaling,
+            AlphaMode: app_config.swapchain_alpha_mode,
+            Flags: flags,
             ..Default::default()
         };
 
-        let swapchain: IDXGISwapChain4 = unsafe {
-            lib.factory.CreateSwapChainForHwnd(
-                &lib.queue,
-                HWND(window_handle.hwnd.get() as *mut c_void),
-                &desc,
-                None,
-                None,
-            )
-        }?
-        .cast()?;
+        // Swapchains bound to a window directly ignore alpha, so transparent windows go through DirectComposition
+        let (swapchain, composition): (IDXGISwapChain4, _) = if app_config.is_transparent() {
+            let swapchain: IDXGISwapChain1 = unsafe {
+                lib.factory
+                    .CreateSwapChainForComposition(&lib.queue, &desc, None)
+            }
+            .map_err(GimsError::SwapchainCreation)?;
+            let composition = Composition::new(hwnd, &swapchain)?;
+            (swapchain.cast()?, Some(composition))
+        } else {
+            let swapchain: IDXGISwapChain1 = unsafe {
+                lib.factory
+                    .CreateSwapChainForHwnd(&lib.queue, hwnd, &desc, None, None)
+            }
+            .map_err(GimsError::SwapchainCreation)?;
+            (swapchain.cast()?, None)
+        };
+
+        set_color_space(&swapchain, format)?;
 
         let render_target_heap: ID3D12DescriptorHeap = unsafe {
             lib.device
                 .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
-                    NumDescriptors: frame_count,
+                    NumDescriptors: buffer_count + extra_render_target_views,
                     Type: D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
                     ..Default::default()
                 })
@@ -75,7 +112,7 @@ impl Swapchain {
         let render_target_heap_srgb: ID3D12DescriptorHeap = unsafe {
             lib.device
                 .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
-                    NumDescriptors: frame_count,
+                    NumDescriptors: buffer_count,
                     Type: D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
                     ..Default::default()
                 })
@@ -86,62 +123,65 @@ impl Swapchain {
                 .GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_RTV)
         };
 
-        let render_targets: Vec<ID3D12Resource> = (0..frame_count as usize)
-            .map(|frame| {
-                let render_target: ID3D12Resource =
-                    unsafe { swapchain.GetBuffer(frame.try_into()?) }?;
-
-                unsafe {
-                    lib.device.CreateRenderTargetView(
-                        &render_target,
-                        None,
-                        D3D12_CPU_DESCRIPTOR_HANDLE {
-                            ptr: render_target_heap.GetCPUDescriptorHandleForHeapStart().ptr
-                                + frame * rtv_descriptor_size as usize,
-                        },
-                    );
-                    lib.device.CreateRenderTargetView(
-                        &render_target,
-                        Some(&D3D12_RENDER_TARGET_VIEW_DESC {
-                            Format: DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
-                            ViewDimension: D3D12_RTV_DIMENSION_TEXTURE2D,
-                            Anonymous: D3D12_RENDER_TARGET_VIEW_DESC_0 {
-                                Texture2D: D3D12_TEX2D_RTV {
-                                    MipSlice: 0,
-                                    PlaneSlice: 0,
-                                },
-                            },
-                        }),
-                        D3D12_CPU_DESCRIPTOR_HANDLE {
-                            ptr: render_target_heap_srgb
-                                .GetCPUDescriptorHandleForHeapStart()
-                                .ptr
-                                + frame * rtv_descriptor_size as usize,
-                        },
-                    );
-
-                    render_target
-                        .SetName(&HSTRING::from(format!("Gimslib render target {}", frame)))?;
-                }
+        let render_targets = create_render_targets(
+            &lib,
+            &swapchain,
+            &render_target_heap,
+            &render_target_heap_srgb,
+            buffer_count,
+            format,
+        )?;
 
-                Ok(render_target)
+        let extra_render_target_handles = (buffer_count..buffer_count + extra_render_target_views)
+            .map(|slot| D3D12_CPU_DESCRIPTOR_HANDLE {
+                ptr: unsafe { render_target_heap.GetCPUDescriptorHandleForHeapStart() }.ptr
+                    + (slot * rtv_descriptor_size) as usize,
             })
-            .collect::<Result<_, Box<dyn std::error::Error>>>()?;
-
-        let viewport = D3D12_VIEWPORT {
-            TopLeftX: 0.0,
-            TopLeftY: 0.0,
-            Width: width as f32,
-            Height: height as f32,
-            MinDepth: 0.0,
-            MaxDepth: 1.0,
+            .collect();
+
+        let (depth_stencil_heap, depth_stencil_handles) = if depth_stencil_views > 0 {
+            let heap: ID3D12DescriptorHeap = unsafe {
+                lib.device
+                    .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                        NumDescriptors: depth_stencil_views,
+                        Type: D3D12_DESCRIPTOR_HEAP_TYPE_DSV,
+                        ..Default::default()
+                    })
+            }?;
+            let dsv_descriptor_size = unsafe {
+                lib.device
+                    .GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_DSV)
+            };
+            let handles = (0..depth_stencil_views)
+                .map(|slot| D3D12_CPU_DESCRIPTOR_HANDLE {
+                    ptr: unsafe { heap.GetCPUDescriptorHandleForHeapStart() }.ptr
+                        + (slot * dsv_descriptor_size) as usize,
+                })
+                .collect();
+            (Some(heap), handles)
+        } else {
+            (None, Vec::new())
         };
 
-        let scissor = RECT {
-            left: 0,
-            top: 0,
-            right: width as i32,
-            bottom: height as i32,
+        let (viewport, scissor) = viewport_and_scissor(width, height);
+
+        let sample_count = supported_sample_count(&lib, format, app_config.sample_count)?;
+        if sample_count != app_config.sample_count {
+            eprintln!(
+                "Warning: {} samples per pixel are not supported, using {}",
+                app_config.sample_count, sample_count
+            );
+        }
+        let msaa_target = if sample_count > 1 {
+            Some(create_msaa_target(
+                &lib,
+                width,
+                height,
+                format,
+                sample_count,
+            )?)
+        } else {
+            None
         };
 
         Ok(Swapchain {
@@ -153,15 +193,104 @@ impl Swapchain {
             viewport,
             scissor,
             render_targets,
+            extra_render_target_handles,
+            depth_stencil_heap,
+            depth_stencil_handles,
+            msaa_target,
+            format,
+            present_config,
+            tearing,
+            composition,
+            wait_on_drop: true,
         })
     }
 
+    /// Presents the current back buffer with the sync interval of the window's present config.
+    /// Tearing is allowed for presents without vsync if it was requested and is supported.
+    pub fn present(&self, mut flags: DXGI_PRESENT) -> HRESULT {
+        if self.tearing
+            && self.present_config.allow_tearing
+            && self.present_config.sync_interval == 0
+            && flags & DXGI_PRESENT_TEST == DXGI_PRESENT(0)
+        {
+            flags |= DXGI_PRESENT_ALLOW_TEARING;
+        }
+        unsafe {
+            self.swapchain
+                .Present(self.present_config.sync_interval, flags)
+        }
+    }
+
+    /// Resizes the back buffers to the new client area of the window and recreates their views in the existing heaps.
+    /// Waits for the GPU first, since no back buffer may be referenced by work in flight.
+    /// Zero sizes, which minimized windows report, are skipped until the window has an area again.
+    /// The slots reserved for the app stay valid, but views of size dependent app resources have to be rewritten.
+    /// Returns whether the back buffers were resized.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<bool, GimsError> {
+        if width == 0 || height == 0 {
+            return Ok(false);
+        }
+        let desc = unsafe { self.swapchain.GetDesc1() }?;
+        if desc.Width == width && desc.Height == height {
+            return Ok(false);
+        }
+
+        self.lib.wait_idle()?;
+        self.render_targets.clear();
+        unsafe {
+            // Format and flags have to stay the same, otherwise tearing presents fail afterwards
+            self.swapchain.ResizeBuffers(
+                desc.BufferCount,
+                width,
+                height,
+                DXGI_FORMAT_UNKNOWN,
+                DXGI_SWAP_CHAIN_FLAG(desc.Flags as i32),
+            )
+        }
+        .map_err(GimsError::SwapchainCreation)?;
+
+        self.render_targets = create_render_targets(
+            &self.lib,
+            &self.swapchain,
+            &self.render_target_heap,
+            &self.render_target_heap_srgb,
+            desc.BufferCount,
+            self.format,
+        )?;
+        (self.viewport, self.scissor) = viewport_and_scissor(width, height);
+        if let Some(msaa_target) = &mut self.msaa_target {
+            *msaa_target = create_msaa_target(
+                &self.lib,
+                width,
+                height,
+                self.format,
+                msaa_target.sample_count,
+            )?;
+        }
+
+        Ok(true)
+    }
+
+    pub fn present_config(&self) -> PresentConfig {
+        self.present_config
+    }
+
+    /// Changes vsync and tearing, effective from the next present
+    pub fn set_present_config(&mut self, present_config: PresentConfig) {
+        self.present_config = present_config;
+    }
+
+    /// Releases the buffers without waiting for the GPU when dropped, for a GPU that hung or was removed
+    pub fn skip_wait_on_drop(&mut self) {
+        self.wait_on_drop = false;
+    }
+
     pub fn current_render_target(&self) -> &ID3D12Resource {
         let index = unsafe { self.swapchain.GetCurrentBackBufferIndex() } as usize;
         &self.render_targets[index]
     }
 
-    /// Returns the UNORM and UNORM_SRGB handles to the current render target
+    /// Returns the handles to the current render target, see `srgb_view_format` for the format of the second one
     pub fn current_render_target_handle(
         &self,
     ) -> (D3D12_CPU_DESCRIPTOR_HANDLE, D3D12_CPU_DESCRIPTOR_HANDLE) {
@@ -192,20 +321,275 @@ impl Swapchain {
     }
 }
 
-impl Drop for Swapchain {
-    fn drop(&mut self) {
+/// DirectComposition objects showing a composition swapchain as the content of a window
+struct Composition {
+    _device: IDCompositionDevice,
+    _target: IDCompositionTarget,
+    _visual: IDCompositionVisual,
+}
+
+impl Composition {
+    fn new(hwnd: HWND, swapchain: &IDXGISwapChain1) -> Result<Self, GimsError> {
+        // Without a DXGI device, the composition device can only show swapchains, which is all that is needed here
+        let device: IDCompositionDevice =
+            unsafe { DCompositionCreateDevice(None::<&IDXGIDevice>) }?;
+        let target = unsafe { device.CreateTargetForHwnd(hwnd, true) }?;
+        let visual = unsafe { device.CreateVisual() }?;
         unsafe {
-            // Wait for queue idle
-            let fence: ID3D12Fence = self
-                .lib
-                .device
-                .CreateFence(0, D3D12_FENCE_FLAG_NONE)
-                .unwrap();
-            let event = Event::new(false).unwrap();
+            visual.SetContent(swapchain)?;
+            target.SetRoot(&visual)?;
+            device.Commit()?;
+        }
+
+        Ok(Composition {
+            _device: device,
+            _target: target,
+            _visual: visual,
+        })
+    }
+}
+
+/// Whether the display stack supports tearing, which is needed for variable refresh rate displays
+fn tearing_supported(factory: &IDXGIFactory7) -> bool {
+    // Win32 BOOL
+    let mut allow_tearing: i32 = 0;
+    let result = unsafe {
+        factory.CheckFeatureSupport(
+            DXGI_FEATURE_PRESENT_ALLOW_TEARING,
+            &mut allow_tearing as *mut _ as _,
+            size_of::<i32>() as u32,
+        )
+    };
+    result.is_ok() && allow_tearing != 0
+}
+
+/// Format of the second render target view, which gamma encodes shader output for 8 bit formats.
+/// HDR formats have no sRGB variant, so the view stores the output unchanged like the first one.
+pub(crate) fn srgb_view_format(format: DXGI_FORMAT) -> DXGI_FORMAT {
+    match format {
+        DXGI_FORMAT_R8G8B8A8_UNORM => DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        DXGI_FORMAT_B8G8R8A8_UNORM => DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
+        format => format,
+    }
+}
+
+/// Typeless variant of a swapchain format, so a texture can have both render target views
+fn typeless_format(format: DXGI_FORMAT) -> DXGI_FORMAT {
+    match format {
+        DXGI_FORMAT_R8G8B8A8_UNORM => DXGI_FORMAT_R8G8B8A8_TYPELESS,
+        DXGI_FORMAT_B8G8R8A8_UNORM => DXGI_FORMAT_B8G8R8A8_TYPELESS,
+        DXGI_FORMAT_R10G10B10A2_UNORM => DXGI_FORMAT_R10G10B10A2_TYPELESS,
+        DXGI_FORMAT_R16G16B16A16_FLOAT => DXGI_FORMAT_R16G16B16A16_TYPELESS,
+        format => format,
+    }
+}
+
+/// Sets the color space the display expects for `format`: HDR10 (PQ encoded BT.2020) for 10 bit back buffers
+/// and scRGB (linear BT.709 that may exceed 1) for 16 bit floats. 8 bit formats keep the default sRGB color space.
+/// Displays without HDR support keep the default as well, so the app still starts on them.
+fn set_color_space(swapchain: &IDXGISwapChain4, format: DXGI_FORMAT) -> Result<(), GimsError> {
+    let color_space = match format {
+        DXGI_FORMAT_R10G10B10A2_UNORM => DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020,
+        DXGI_FORMAT_R16G16B16A16_FLOAT => DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709,
+        _ => return Ok(()),
+    };
+
+    let support = unsafe { swapchain.CheckColorSpaceSupport(color_space) }?;
+    if support & DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT.0 as u32 == 0 {
+        eprintln!(
+            "Warning: the display does not support color space {:?} for {:?}, using sRGB",
+            color_space, format
+        );
+        return Ok(());
+    }
+    unsafe { swapchain.SetColorSpace1(color_space) }?;
+
+    Ok(())
+}
 
-            self.lib.queue.Signal(&fence, 1).unwrap();
-            fence.SetEventOnCompletion(1, *event).unwrap();
-            event.wait().unwrap();
+/// Gets the back buffers of the swapchain and writes their views in `format` and `srgb_view_format` into the heaps
+fn create_render_targets(
+    lib: &GPULib,
+    swapchain: &IDXGISwapChain4,
+    render_target_heap: &ID3D12DescriptorHeap,
+    render_target_heap_srgb: &ID3D12DescriptorHeap,
+    buffer_count: u32,
+    format: DXGI_FORMAT,
+) -> Result<Vec<ID3D12Resource>, GimsError> {
+    let rtv_descriptor_size = unsafe {
+        lib.device
+            .GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_RTV)
+    };
+
+    (0..buffer_count as usize)
+        .map(|frame| {
+            let render_target: ID3D12Resource = unsafe { swapchain.GetBuffer(frame.try_into()?) }?;
+
+            unsafe {
+                lib.device.CreateRenderTargetView(
+                    &render_target,
+                    None,
+                    D3D12_CPU_DESCRIPTOR_HANDLE {
+                        ptr: render_target_heap.GetCPUDescriptorHandleForHeapStart().ptr
+                            + frame * rtv_descriptor_size as usize,
+                    },
+                );
+                lib.device.CreateRenderTargetView(
+                    &render_target,
+                    Some(&D3D12_RENDER_TARGET_VIEW_DESC {
+                        Format: srgb_view_format(format),
+                        ViewDimension: D3D12_RTV_DIMENSION_TEXTURE2D,
+                        Anonymous: D3D12_RENDER_TARGET_VIEW_DESC_0 {
+                            Texture2D: D3D12_TEX2D_RTV {
+                                MipSlice: 0,
+                                PlaneSlice: 0,
+                            },
+                        },
+                    }),
+                    D3D12_CPU_DESCRIPTOR_HANDLE {
+                        ptr: render_target_heap_srgb
+                            .GetCPUDescriptorHandleForHeapStart()
+                            .ptr
+                            + frame * rtv_descriptor_size as usize,
+                    },
+                );
+
+                render_target
+                    .SetName(&HSTRING::from(format!("Gimslib render target {}", frame)))?;
+            }
+
+            Ok(render_target)
+        })
+        .collect()
+}
+
+/// Highest sample count up to `requested` that the GPU supports for the back buffer format
+fn supported_sample_count(
+    lib: &GPULib,
+    format: DXGI_FORMAT,
+    requested: u32,
+) -> Result<u32, GimsError> {
+    let mut sample_count = requested;
+    while sample_count > 1 {
+        let mut quality_levels = D3D12_FEATURE_DATA_MULTISAMPLE_QUALITY_LEVELS {
+            Format: format,
+            SampleCount: sample_count,
+            Flags: D3D12_MULTISAMPLE_QUALITY_LEVELS_FLAG_NONE,
+            NumQualityLevels: 0,
+        };
+        unsafe {
+            lib.device.CheckFeatureSupport(
+                D3D12_FEATURE_MULTISAMPLE_QUALITY_LEVELS,
+                &mut quality_levels as *mut _ as _,
+                size_of::<D3D12_FEATURE_DATA_MULTISAMPLE_QUALITY_LEVELS>() as u32,
+            )
+        }?;
+        if quality_levels.NumQualityLevels > 0 {
+            break;
+        }
+        sample_count /= 2;
+    }
+
+    Ok(sample_count)
+}
+
+/// Creates the multisampled color target with the same two views as the back buffers
+fn create_msaa_target(
+    lib: &GPULib,
+    width: u32,
+    height: u32,
+    format: DXGI_FORMAT,
+    sample_count: u32,
+) -> Result<MsaaTarget, GimsError> {
+    // Typeless, so both views can be created. Typeless resources have no optimized clear value.
+    let desc = TextureDesc {
+        flags: D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET,
+        sample_count,
+        ..TextureDesc::new_2d(width, height, typeless_format(format))
+    };
+    let resource = texture::create_texture(
+        lib,
+        &desc,
+        D3D12_HEAP_TYPE_DEFAULT,
+        Some("Gimslib multisampled render target"),
+    )?;
+
+    let heap: ID3D12DescriptorHeap = unsafe {
+        lib.device
+            .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                NumDescriptors: 2,
+                Type: D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
+                ..Default::default()
+            })
+    }?;
+    let rtv_descriptor_size = unsafe {
+        lib.device
+            .GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_RTV)
+    } as usize;
+    let render_target_handle = unsafe { heap.GetCPUDescriptorHandleForHeapStart() };
+    let render_target_handle_srgb = D3D12_CPU_DESCRIPTOR_HANDLE {
+        ptr: render_target_handle.ptr + rtv_descriptor_size,
+    };
+
+    for (format, handle) in [
+        (format, render_target_handle),
+        (srgb_view_format(format), render_target_handle_srgb),
+    ] {
+        unsafe {
+            lib.device.CreateRenderTargetView(
+                &resource,
+                Some(&D3D12_RENDER_TARGET_VIEW_DESC {
+                    Format: format,
+                    ViewDimension: D3D12_RTV_DIMENSION_TEXTURE2DMS,
+                    Anonymous: D3D12_RENDER_TARGET_VIEW_DESC_0 {
+                        Texture2DMS: D3D12_TEX2DMS_RTV::default(),
+                    },
+                }),
+                handle,
+            )
+        };
+    }
+
+    Ok(MsaaTarget {
+        resource,
+        render_target_handle,
+        render_target_handle_srgb,
+        sample_count,
+        _heap: heap,
+        state: D3D12_RESOURCE_STATE_COMMON,
+    })
+}
+
+fn viewport_and_scissor(width: u32, height: u32) -> (D3D12_VIEWPORT, RECT) {
+    let viewport = D3D12_VIEWPORT {
+        TopLeftX: 0.0,
+        TopLeftY: 0.0,
+        Width: width as f32,
+        Height: height as f32,
+        MinDepth: 0.0,
+        MaxDepth: 1.0,
+    };
+
+    let scissor = RECT {
+        left: 0,
+        top: 0,
+        right: width as i32,
+        bottom: height as i32,
+    };
+
+    (viewport, scissor)
+}
+
+impl Drop for Swapchain {
+    fn drop(&mut self) {
+        if !self.wait_on_drop {
+            return;
+        }
+        if let Err(error) = self.lib.wait_idle() {
+            println!(
+                "Error while waiting for the GPU to release the swapchain:\n{}",
+                error
+            );
         }
     }
 }
@@ -1,23 +1,103 @@
 use egui::{TextureId, epaint::ImageDelta};
+use std::cell::RefCell;
 use std::mem::ManuallyDrop;
 use std::ptr::null_mut;
 use std::{collections::HashMap, sync::Arc};
 use windows::Win32::Graphics::Direct3D12::*;
 use windows::Win32::Graphics::Dxgi::Common::{DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_UNKNOWN};
-use windows::core::{HSTRING, Interface};
+use windows::core::Interface;
+
+use crate::barrier::{StateTracker, Transitions};
+use crate::fence::Fence;
+use crate::texture::{self, CopyableFootprint, TextureDesc};
+use crate::{EguiTextureStats, error::GimsError, gpulib::GPULib};
+
+/// Textures of the app that can be shown in the UI, e.g. an offscreen render target as a viewport image.
+/// Registered textures are drawn with the pipeline of the UI, so they have to be in
+/// `D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE` when the UI is drawn at the end of the frame.
+///
+/// Textures can be registered from `App::draw` through `FrameResources::egui_user_textures`
+/// and used from the next call of `App::record_ui` on:
+/// ```ignore
+/// let texture_id = res.egui_user_textures.unwrap().register(0, &self.viewport_texture, None)?;
+/// // In record_ui
+/// ui.image((texture_id, egui::vec2(256.0, 256.0)));
+/// ```
+pub struct EguiUserTextures {
+    lib: Arc<GPULib>,
+    textures: RefCell<HashMap<u64, (ID3D12Resource, ID3D12DescriptorHeap)>>,
+    /// Replaced or unregistered textures, which frames in flight may still use
+    retired: RefCell<Vec<(ID3D12Resource, ID3D12DescriptorHeap)>>,
+}
+
+impl EguiUserTextures {
+    fn new(lib: Arc<GPULib>) -> Self {
+        EguiUserTextures {
+            lib,
+            textures: RefCell::default(),
+            retired: RefCell::default(),
+        }
+    }
+
+    /// Registers `texture` under `id`, replacing the texture previously registered under it.
+    /// The UI reads it through a shader resource view created from `view`, or from the texture's own format
+    /// and all mip levels if `view` is `None`. Returns the id to use in egui, e.g. with `egui::Image`.
+    pub fn register(
+        &self,
+        id: u64,
+        texture: &ID3D12Resource,
+        view: Option<&D3D12_SHADER_RESOURCE_VIEW_DESC>,
+    ) -> Result<TextureId, GimsError> {
+        let heap = TextureManager::create_heap_for_texture(&self.lib, texture, view)?;
+        if let Some(previous) = self
+            .textures
+            .borrow_mut()
+            .insert(id, (texture.clone(), heap))
+        {
+            self.retired.borrow_mut().push(previous);
+        }
+        Ok(TextureId::User(id))
+    }
 
-use crate::gpulib::GPULib;
+    /// Removes the texture registered under `id`. It is kept alive until the frames in flight have finished.
+    pub fn unregister(&self, id: u64) {
+        if let Some(previous) = self.textures.borrow_mut().remove(&id) {
+            self.retired.borrow_mut().push(previous);
+        }
+    }
+
+    /// Number of registered textures
+    pub fn len(&self) -> usize {
+        self.textures.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.borrow().is_empty()
+    }
+
+    fn get_descriptor_heap(&self, id: u64) -> Option<ID3D12DescriptorHeap> {
+        self.textures
+            .borrow()
+            .get(&id)
+            .map(|(_, heap)| heap.clone())
+    }
+}
 
 pub struct TextureManager {
     textures: HashMap<u64, (ID3D12Resource, ID3D12DescriptorHeap)>,
+    user_textures: EguiUserTextures,
     command_allocator: ID3D12CommandAllocator,
     command_list: ID3D12GraphicsCommandList,
-    fence: ID3D12Fence,
+    /// Signaled after each upload
+    fence: Fence,
+    /// Upload buffer read by the last submitted copy
+    upload_buffer: Option<ID3D12Resource>,
+    resource_states: StateTracker,
     lib: Arc<GPULib>,
 }
 
 impl TextureManager {
-    pub fn new(lib: Arc<GPULib>) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new(lib: Arc<GPULib>) -> Result<Self, GimsError> {
         let textures = HashMap::new();
 
         let command_allocator = unsafe {
@@ -32,73 +112,97 @@ impl TextureManager {
             )
         }?;
 
-        let fence = unsafe { lib.device.CreateFence(1, D3D12_FENCE_FLAG_NONE) }?;
+        let fence = Fence::new(&lib)?;
 
         Ok(TextureManager {
             textures,
             command_allocator,
             command_list,
             fence,
+            upload_buffer: None,
+            resource_states: StateTracker::default(),
+            user_textures: EguiUserTextures::new(lib.clone()),
             lib,
         })
     }
 
-    pub fn get_descriptor_heap(&self, texture: u64) -> Option<&ID3D12DescriptorHeap> {
-        self.textures.get(&texture).map(|texture| &texture.1)
+    /// Counts the textures and the video memory they occupy
+    pub fn stats(&self) -> EguiTextureStats {
+        let allocated_bytes = self
+            .textures
+            .values()
+            .map(|(texture, _)| unsafe {
+                self.lib
+                    .device
+                    .GetResourceAllocationInfo(0, &[texture.GetDesc()])
+                    .SizeInBytes
+            })
+            .sum();
+        // egui always stores the font atlas as the first managed texture
+        let font_atlas_size = self.textures.get(&0).map(|(texture, _)| {
+            let desc = unsafe { texture.GetDesc() };
+            [desc.Width as usize, desc.Height as usize]
+        });
+
+        EguiTextureStats {
+            managed_textures: self.textures.len(),
+            user_textures: self.user_textures.len(),
+            font_atlas_size,
+            allocated_bytes,
+        }
+    }
+
+    pub fn get_descriptor_heap(&self, texture: TextureId) -> Option<ID3D12DescriptorHeap> {
+        match texture {
+            TextureId::Managed(id) => self.textures.get(&id).map(|(_, heap)| heap.clone()),
+            TextureId::User(id) => self.user_textures.get_descriptor_heap(id),
+        }
+    }
+
+    pub fn user_textures(&self) -> &EguiUserTextures {
+        &self.user_textures
+    }
+
+    /// Takes the user textures that have been replaced or unregistered since the last call.
+    /// They have to be kept alive until the frames that may use them have finished.
+    pub fn take_retired_user_textures(&self) -> Vec<(ID3D12Resource, ID3D12DescriptorHeap)> {
+        self.user_textures.retired.take()
     }
 
-    pub fn set(
-        &mut self,
-        delta: &[(TextureId, ImageDelta)],
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn set(&mut self, delta: &[(TextureId, ImageDelta)]) -> Result<(), GimsError> {
         for (id, delta) in delta.iter().filter_map(|(id, delta)| match id {
             TextureId::Managed(id) => Some((id, delta)),
-            TextureId::User(_) => None, // Not supported yet
+            // User textures are uploaded by the app
+            TextureId::User(_) => None,
         }) {
             let width = delta.image.width() as u32;
             let height = delta.image.height() as u32;
 
-            // Row size aligned to 256 bytes
-            let aligned_row_bytes = 4 * width + D3D12_TEXTURE_DATA_PITCH_ALIGNMENT
-                - (4 * width) % D3D12_TEXTURE_DATA_PITCH_ALIGNMENT;
-
-            let upload_buffer =
-                Self::create_upload_buffer(&self.lib, aligned_row_bytes as u64 * height as u64)?;
-
-            Self::fill_buffer_aligned(&upload_buffer, &delta.image, aligned_row_bytes)?;
-
-            let mut new_resource = false;
-            let (destination_textue, _) = self
-                .textures
-                .entry(*id)
-                .or_insert_with(|| {
-                    new_resource = true;
-                    let texture =
-                        Self::create_texture(&self.lib, width, delta.image.height() as u32)
-                            .unwrap();
-                    let heap = Self::create_heap_for_texture(&self.lib, &texture).unwrap();
-                    (texture, heap)
-                })
-                .clone();
+            // Layout of the updated region in the upload buffer
+            let footprint = TextureDesc::new_2d(width, height, DXGI_FORMAT_R8G8B8A8_UNORM)
+                .copyable_footprint(&self.lib, 0)?;
+
+            let upload_buffer = Self::create_upload_buffer(&self.lib, footprint.total_bytes)?;
 
-            let source = D3D12_TEXTURE_COPY_LOCATION {
+            Self::fill_buffer_aligned(&upload_buffer, &delta.image, &footprint)?;
+
+            let new_resource = !self.textures.contains_key(id);
+            if new_resource {
+                let texture = Self::create_texture(&self.lib, width, height)?;
+                let heap = Self::create_heap_for_texture(&self.lib, &texture, None)?;
+                self.textures.insert(*id, (texture, heap));
+            }
+            let (destination_textue, _) = self.textures[id].clone();
+
+            let mut source = D3D12_TEXTURE_COPY_LOCATION {
                 Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
-                pResource: ManuallyDrop::new(Some(upload_buffer)),
+                pResource: ManuallyDrop::new(Some(upload_buffer.clone())),
                 Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
-                    PlacedFootprint: D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
-                        Offset: 0,
-                        Footprint: D3D12_SUBRESOURCE_FOOTPRINT {
-                            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
-                            Width: width,
-                            Height: height,
-                            Depth: 1,
-                            RowPitch: aligned_row_bytes,
-                        },
-                    },
+                    PlacedFootprint: footprint.layout,
                 },
             };
 
-            let destination = D3D12_TEXTURE_COPY_LOCATION {
+            let mut destination = D3D12_TEXTURE_COPY_LOCATION {
                 Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
                 pResource: ManuallyDrop::new(Some(destination_textue.clone())),
                 Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
@@ -117,28 +221,24 @@ impl TextureManager {
 
             let [dst_x, dst_y] = delta.pos.unwrap_or([0, 0]);
 
-            unsafe {
-                self.lib.queue.Wait(&self.fence, 1)?;
-                self.fence.Signal(0)?;
-
-                self.command_allocator.Reset()?;
-                self.command_list.Reset(&self.command_allocator, None)?;
-
-                self.command_list.ResourceBarrier(&[D3D12_RESOURCE_BARRIER {
-                    Type: D3D12_RESOURCE_BARRIER_TYPE_TRANSITION,
-                    Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
-                    Anonymous: D3D12_RESOURCE_BARRIER_0 {
-                        Transition: ManuallyDrop::new(D3D12_RESOURCE_TRANSITION_BARRIER {
-                            pResource: ManuallyDrop::new(Some(destination_textue.clone())),
-                            Subresource: 0,
-                            StateBefore: match new_resource {
-                                true => D3D12_RESOURCE_STATE_COMMON,
-                                false => D3D12_RESOURCE_STATE_GENERIC_READ,
-                            },
-                            StateAfter: D3D12_RESOURCE_STATE_COPY_DEST,
-                        }),
+            // The previous upload has to finish on the GPU before its allocator can be reused
+            self.fence.wait_latest()?;
+            self.fence
+                .reset_command_allocator(&self.command_allocator, self.fence.last_signaled())?;
+            unsafe { self.command_list.Reset(&self.command_allocator, None) }?;
+
+            Transitions::new(&mut self.resource_states)
+                .add(
+                    &destination_textue,
+                    match new_resource {
+                        true => D3D12_RESOURCE_STATE_COMMON,
+                        false => D3D12_RESOURCE_STATE_GENERIC_READ,
                     },
-                }]);
+                    D3D12_RESOURCE_STATE_COPY_DEST,
+                )
+                .record(&self.command_list);
+
+            unsafe {
                 self.command_list.CopyTextureRegion(
                     &destination,
                     dst_x as u32,
@@ -147,27 +247,30 @@ impl TextureManager {
                     &source,
                     Some(&soruce_box),
                 );
-                self.command_list.ResourceBarrier(&[D3D12_RESOURCE_BARRIER {
-                    Type: D3D12_RESOURCE_BARRIER_TYPE_TRANSITION,
-                    Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
-                    Anonymous: D3D12_RESOURCE_BARRIER_0 {
-                        Transition: ManuallyDrop::new(D3D12_RESOURCE_TRANSITION_BARRIER {
-                            pResource: ManuallyDrop::new(Some(destination_textue.clone())),
-                            Subresource: 0,
-                            StateBefore: D3D12_RESOURCE_STATE_COPY_DEST,
-                            StateAfter: D3D12_RESOURCE_STATE_GENERIC_READ,
-                        }),
-                    },
-                }]);
+                // Release the references taken for the copy locations
+                ManuallyDrop::drop(&mut source.pResource);
+                ManuallyDrop::drop(&mut destination.pResource);
+            }
+
+            Transitions::new(&mut self.resource_states)
+                .add(
+                    &destination_textue,
+                    D3D12_RESOURCE_STATE_COPY_DEST,
+                    D3D12_RESOURCE_STATE_GENERIC_READ,
+                )
+                .record(&self.command_list);
 
+            unsafe {
                 self.command_list.Close()?;
                 self.lib
                     .queue
                     .ExecuteCommandLists(&[Some(self.command_list.cast()?)]);
-                self.lib.queue.Signal(&self.fence, 1)?;
             }
+            self.fence.signal(&self.lib.queue)?;
+            // Replaces the buffer of the previous upload, which has finished after the wait above
+            self.upload_buffer = Some(upload_buffer);
         }
-        unsafe { self.lib.queue.Wait(&self.fence, 1) }?;
+        self.fence.queue_wait_latest(&self.lib.queue)?;
 
         Ok(())
     }
@@ -175,61 +278,25 @@ impl TextureManager {
     pub fn free(&mut self, textures: &[TextureId]) {
         for id in textures.iter().filter_map(|id| match id {
             TextureId::Managed(id) => Some(id),
-            TextureId::User(_) => None, // Not supported yet
+            // User textures are unregistered by the app
+            TextureId::User(_) => None,
         }) {
-            self.textures.remove(id);
+            if let Some((texture, _)) = self.textures.remove(id) {
+                self.resource_states.forget(&texture);
+            }
         }
     }
 
-    fn create_texture(
-        lib: &GPULib,
-        width: u32,
-        height: u32,
-    ) -> Result<ID3D12Resource, Box<dyn std::error::Error>> {
-        let heap_properties = D3D12_HEAP_PROPERTIES {
-            Type: D3D12_HEAP_TYPE_GPU_UPLOAD,
-            ..Default::default()
-        };
-
-        let resource_desc = D3D12_RESOURCE_DESC {
-            Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
-            Width: width as u64,
-            Height: height,
-            DepthOrArraySize: 1,
-            Alignment: 0,
-            MipLevels: 1,
-            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
-            SampleDesc: windows::Win32::Graphics::Dxgi::Common::DXGI_SAMPLE_DESC {
-                Count: 1,
-                Quality: 0,
-            },
-            Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
-            Flags: D3D12_RESOURCE_FLAGS::default(),
-        };
-
-        let mut resource_option = None;
-        unsafe {
-            lib.device.CreateCommittedResource(
-                &heap_properties,
-                D3D12_HEAP_FLAGS::default(),
-                &resource_desc,
-                D3D12_RESOURCE_STATE_COMMON,
-                None,
-                &mut resource_option,
-            )
-        }?;
-
-        let resource: ID3D12Resource =
-            resource_option.ok_or("Failed to create texture resource")?;
-        unsafe { resource.SetName(&HSTRING::from("Egui texture")) }?;
-
-        Ok(resource)
+    fn create_texture(lib: &GPULib, width: u32, height: u32) -> Result<ID3D12Resource, GimsError> {
+        texture::create_texture(
+            lib,
+            &TextureDesc::new_2d(width, height, DXGI_FORMAT_R8G8B8A8_UNORM),
+            D3D12_HEAP_TYPE_GPU_UPLOAD,
+            Some("Egui texture"),
+        )
     }
 
-    fn create_upload_buffer(
-        lib: &GPULib,
-        bytes: u64,
-    ) -> Result<ID3D12Resource, Box<dyn std::error::Error>> {
+    fn create_upload_buffer(lib: &GPULib, bytes: u64) -> Result<ID3D12Resource, GimsError> {
         let heap_properties = D3D12_HEAP_PROPERTIES {
             Type: D3D12_HEAP_TYPE_UPLOAD,
             ..Default::default()
@@ -261,65 +328,53 @@ impl TextureManager {
                 None,
                 &mut resource_option,
             )
-        }?;
+        }
+        .map_err(GimsError::resource_creation)?;
 
         resource_option.ok_or("Failed to create texture upload buffer".into())
     }
 
-    /// Fills an upload buffer with texture data and aligns it's rows to the specified byte count
+    /// Fills an upload buffer with texture data, placing rows as described by `footprint`
     fn fill_buffer_aligned(
         buffer: &ID3D12Resource,
         image_data: &egui::ImageData,
-        aligned_row_bytes: u32,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let width = image_data.width();
-        let height = image_data.height();
+        footprint: &CopyableFootprint,
+    ) -> Result<(), GimsError> {
         unsafe {
             // Get mapped slice to upload texture memory
             let mut ptr = null_mut();
             buffer.Map(0, None, Some(&mut ptr))?;
-            let mapped_slice = std::slice::from_raw_parts_mut(
-                ptr as *mut u8,
-                aligned_row_bytes as usize * height * 4,
-            );
-
-            // Fill texture with image data
-            match &image_data {
+            // The last row is not padded, so the buffer can be smaller than `row_pitch * height`
+            let mapped_slice =
+                std::slice::from_raw_parts_mut(ptr as *mut u8, buffer.GetDesc().Width as usize);
+
+            // Fill texture with image data.
+            // Both image kinds are stored as gamma encoded sRGBA with premultiplied alpha (`Color32`),
+            // which is what the ONE / INV_SRC_ALPHA blend of the egui pipeline expects, so no conversion is needed.
+            // Unmultiplied user images are premultiplied by egui when the `ColorImage` is created.
+            let result = match &image_data {
                 egui::ImageData::Color(color_image) => {
-                    let src_slice = color_image.as_raw();
-                    for row in 0..height {
-                        let dst_row_start = aligned_row_bytes as usize * row;
-                        let dst_row_end = dst_row_start + width * 4;
-                        let src_row_start = width * 4 * row;
-                        let src_row_end = src_row_start + width * 4;
-                        mapped_slice[dst_row_start..dst_row_end]
-                            .copy_from_slice(&src_slice[src_row_start..src_row_end]);
-                    }
+                    texture::copy_to_footprint(mapped_slice, color_image.as_raw(), footprint)
                 }
                 egui::ImageData::Font(font_image) => {
-                    for (i, pixel) in font_image
+                    let pixels: Vec<u8> = font_image
                         .srgba_pixels(None)
-                        .map(|pixel| [pixel.r(), pixel.g(), pixel.b(), pixel.a()])
-                        .enumerate()
-                    {
-                        let x = i % width;
-                        let y = i / width;
-                        let dst_index = y * aligned_row_bytes as usize + x * 4;
-                        mapped_slice[dst_index..(dst_index + 4)].copy_from_slice(&pixel);
-                    }
+                        .flat_map(|pixel| pixel.to_array())
+                        .collect();
+                    texture::copy_to_footprint(mapped_slice, &pixels, footprint)
                 }
             };
 
             buffer.Unmap(0, None);
+            result
         }
-
-        Ok(())
     }
 
     fn create_heap_for_texture(
         lib: &GPULib,
         texture: &ID3D12Resource,
-    ) -> Result<ID3D12DescriptorHeap, Box<dyn std::error::Error>> {
+        view: Option<&D3D12_SHADER_RESOURCE_VIEW_DESC>,
+    ) -> Result<ID3D12DescriptorHeap, GimsError> {
         let heap: ID3D12DescriptorHeap = unsafe {
             lib.device
                 .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
@@ -332,7 +387,7 @@ impl TextureManager {
         unsafe {
             lib.device.CreateShaderResourceView(
                 texture,
-                None,
+                view.map(|view| view as *const _),
                 heap.GetCPUDescriptorHandleForHeapStart(),
             )
         };
@@ -0,0 +1,292 @@
+use std::sync::Arc;
+
+use windows::{Win32::Graphics::Direct3D12::*, core::HSTRING};
+
+use crate::{error::GimsError, gpulib::GPULib};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorderColor {
+    TransparentBlack,
+    OpaqueBlack,
+    OpaqueWhite,
+}
+
+impl BorderColor {
+    fn static_border_color(self) -> D3D12_STATIC_BORDER_COLOR {
+        match self {
+            BorderColor::TransparentBlack => D3D12_STATIC_BORDER_COLOR_TRANSPARENT_BLACK,
+            BorderColor::OpaqueBlack => D3D12_STATIC_BORDER_COLOR_OPAQUE_BLACK,
+            BorderColor::OpaqueWhite => D3D12_STATIC_BORDER_COLOR_OPAQUE_WHITE,
+        }
+    }
+
+    fn rgba(self) -> [f32; 4] {
+        match self {
+            BorderColor::TransparentBlack => [0.0, 0.0, 0.0, 0.0],
+            BorderColor::OpaqueBlack => [0.0, 0.0, 0.0, 1.0],
+            BorderColor::OpaqueWhite => [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// Description of a sampler, usable both as a static sampler in a root signature
+/// and as a descriptor in a `SamplerHeap`.
+#[derive(Debug, Clone, Copy)]
+pub struct SamplerDesc {
+    pub filter: D3D12_FILTER,
+    pub address_u: D3D12_TEXTURE_ADDRESS_MODE,
+    pub address_v: D3D12_TEXTURE_ADDRESS_MODE,
+    pub address_w: D3D12_TEXTURE_ADDRESS_MODE,
+    pub mip_lod_bias: f32,
+    /// Only used by anisotropic filters, valid values are 1 to 16
+    pub max_anisotropy: u32,
+    /// Only used by comparison filters
+    pub comparison_func: D3D12_COMPARISON_FUNC,
+    /// Only used with `D3D12_TEXTURE_ADDRESS_MODE_BORDER`
+    pub border_color: BorderColor,
+    pub min_lod: f32,
+    pub max_lod: f32,
+}
+
+impl Default for SamplerDesc {
+    /// Trilinear filtering with clamped texture coordinates
+    fn default() -> Self {
+        SamplerDesc {
+            filter: D3D12_FILTER_MIN_MAG_MIP_LINEAR,
+            address_u: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+            address_v: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+            address_w: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+            mip_lod_bias: 0.0,
+            max_anisotropy: 1,
+            comparison_func: D3D12_COMPARISON_FUNC_NEVER,
+            border_color: BorderColor::OpaqueBlack,
+            min_lod: 0.0,
+            max_lod: f32::MAX,
+        }
+    }
+}
+
+impl SamplerDesc {
+    /// Nearest neighbor filtering, e.g. for pixel art or exact texel fetches
+    pub fn point() -> Self {
+        SamplerDesc::default().with_filter(D3D12_FILTER_MIN_MAG_MIP_POINT)
+    }
+
+    /// Trilinear filtering
+    pub fn linear() -> Self {
+        SamplerDesc::default()
+    }
+
+    /// Anisotropic filtering with the specified maximum anisotropy (1 to 16)
+    pub fn anisotropic(max_anisotropy: u32) -> Self {
+        SamplerDesc {
+            filter: D3D12_FILTER_ANISOTROPIC,
+            max_anisotropy: max_anisotropy.clamp(1, D3D12_MAX_MAXANISOTROPY),
+            ..Default::default()
+        }
+    }
+
+    /// Linear comparison sampler for hardware filtered shadow map lookups with `SampleCmp`.
+    /// Coordinates outside the shadow map compare against an opaque white border, so they count as lit.
+    pub fn shadow_comparison(comparison_func: D3D12_COMPARISON_FUNC) -> Self {
+        SamplerDesc {
+            filter: D3D12_FILTER_COMPARISON_MIN_MAG_LINEAR_MIP_POINT,
+            comparison_func,
+            border_color: BorderColor::OpaqueWhite,
+            ..Default::default()
+        }
+        .with_address_mode(D3D12_TEXTURE_ADDRESS_MODE_BORDER)
+    }
+
+    pub fn with_filter(mut self, filter: D3D12_FILTER) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the same address mode for all three texture coordinates
+    pub fn with_address_mode(mut self, mode: D3D12_TEXTURE_ADDRESS_MODE) -> Self {
+        self.address_u = mode;
+        self.address_v = mode;
+        self.address_w = mode;
+        self
+    }
+
+    pub fn with_border_color(mut self, border_color: BorderColor) -> Self {
+        self.border_color = border_color;
+        self
+    }
+
+    pub fn with_lod_range(mut self, min_lod: f32, max_lod: f32) -> Self {
+        self.min_lod = min_lod;
+        self.max_lod = max_lod;
+        self
+    }
+
+    pub fn with_mip_lod_bias(mut self, mip_lod_bias: f32) -> Self {
+        self.mip_lod_bias = mip_lod_bias;
+        self
+    }
+
+    /// Creates a static sampler for a root signature bound to the specified register
+    pub fn static_sampler(
+        &self,
+        shader_register: u32,
+        register_space: u32,
+        visibility: D3D12_SHADER_VISIBILITY,
+    ) -> D3D12_STATIC_SAMPLER_DESC {
+        D3D12_STATIC_SAMPLER_DESC {
+            Filter: self.filter,
+            AddressU: self.address_u,
+            AddressV: self.address_v,
+            AddressW: self.address_w,
+            MipLODBias: self.mip_lod_bias,
+            MaxAnisotropy: self.max_anisotropy,
+            ComparisonFunc: self.comparison_func,
+            BorderColor: self.border_color.static_border_color(),
+            MinLOD: self.min_lod,
+            MaxLOD: self.max_lod,
+            ShaderRegister: shader_register,
+            RegisterSpace: register_space,
+            ShaderVisibility: visibility,
+        }
+    }
+
+    /// Creates a description for a sampler descriptor
+    pub fn sampler_desc(&self) -> D3D12_SAMPLER_DESC {
+        D3D12_SAMPLER_DESC {
+            Filter: self.filter,
+            AddressU: self.address_u,
+            AddressV: self.address_v,
+            AddressW: self.address_w,
+            MipLODBias: self.mip_lod_bias,
+            MaxAnisotropy: self.max_anisotropy,
+            ComparisonFunc: self.comparison_func,
+            BorderColor: self.border_color.rgba(),
+            MinLOD: self.min_lod,
+            MaxLOD: self.max_lod,
+        }
+    }
+}
+
+/// Shader visible descriptor heap for samplers which can be selected at draw time through a descriptor table
+pub struct SamplerHeap {
+    lib: Arc<GPULib>,
+    heap: ID3D12DescriptorHeap,
+    descriptor_size: u32,
+    capacity: u32,
+    len: u32,
+}
+
+impl SamplerHeap {
+    /// Creates an empty heap with space for `capacity` samplers.
+    /// Shader visible sampler heaps are limited to `D3D12_MAX_SHADER_VISIBLE_SAMPLER_HEAP_SIZE` entries.
+    pub fn new(lib: Arc<GPULib>, capacity: u32, name: Option<&str>) -> Result<Self, GimsError> {
+        if capacity == 0 || capacity > D3D12_MAX_SHADER_VISIBLE_SAMPLER_HEAP_SIZE {
+            return Err(format!(
+                "Sampler heap capacity must be between 1 and {}",
+                D3D12_MAX_SHADER_VISIBLE_SAMPLER_HEAP_SIZE
+            )
+            .into());
+        }
+
+        let heap: ID3D12DescriptorHeap = unsafe {
+            lib.device
+                .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                    Type: D3D12_DESCRIPTOR_HEAP_TYPE_SAMPLER,
+                    NumDescriptors: capacity,
+                    Flags: D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+                    NodeMask: 0,
+                })
+        }?;
+        if let Some(name) = name {
+            unsafe { heap.SetName(&HSTRING::from(name)) }?;
+        }
+
+        let descriptor_size = unsafe {
+            lib.device
+                .GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_SAMPLER)
+        };
+
+        Ok(SamplerHeap {
+            lib,
+            heap,
+            descriptor_size,
+            capacity,
+            len: 0,
+        })
+    }
+
+    /// Writes a new sampler into the next free slot and returns its index in the heap
+    pub fn create_sampler(&mut self, desc: &SamplerDesc) -> Result<u32, GimsError> {
+        if self.len == self.capacity {
+            return Err("Sampler heap is full".into());
+        }
+
+        let index = self.len;
+        unsafe {
+            self.lib
+                .device
+                .CreateSampler(&desc.sampler_desc(), self.cpu_handle(index))
+        };
+        self.len += 1;
+
+        Ok(index)
+    }
+
+    /// The number of samplers created in this heap
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn heap(&self) -> &ID3D12DescriptorHeap {
+        &self.heap
+    }
+
+    pub fn cpu_handle(&self, index: u32) -> D3D12_CPU_DESCRIPTOR_HANDLE {
+        D3D12_CPU_DESCRIPTOR_HANDLE {
+            ptr: unsafe { self.heap.GetCPUDescriptorHandleForHeapStart() }.ptr
+                + (index * self.descriptor_size) as usize,
+        }
+    }
+
+    /// GPU handle of a sampler for use with `SetGraphicsRootDescriptorTable`
+    pub fn gpu_handle(&self, index: u32) -> D3D12_GPU_DESCRIPTOR_HANDLE {
+        D3D12_GPU_DESCRIPTOR_HANDLE {
+            ptr: unsafe { self.heap.GetGPUDescriptorHandleForHeapStart() }.ptr
+                + (index * self.descriptor_size) as u64,
+        }
+    }
+
+    /// Binds this heap together with the shader visible CBV/SRV/UAV heap of the draw, if any.
+    /// Only one heap of each type can be bound at a time and every call of `SetDescriptorHeaps`
+    /// replaces all of them, so both heaps have to be set in the same call.
+    pub fn bind(
+        &self,
+        command_list: &ID3D12GraphicsCommandList,
+        resource_heap: Option<&ID3D12DescriptorHeap>,
+    ) {
+        let heaps = [resource_heap.cloned(), Some(self.heap.clone())];
+        let heaps = match resource_heap {
+            Some(_) => &heaps[..],
+            None => &heaps[1..],
+        };
+        unsafe { command_list.SetDescriptorHeaps(heaps) };
+    }
+
+    /// Descriptor table range of a single sampler bound to the specified register.
+    /// Selecting a sampler per draw is done by pointing the table at `gpu_handle(index)`.
+    pub fn descriptor_range(shader_register: u32, register_space: u32) -> D3D12_DESCRIPTOR_RANGE1 {
+        D3D12_DESCRIPTOR_RANGE1 {
+            RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SAMPLER,
+            NumDescriptors: 1,
+            BaseShaderRegister: shader_register,
+            RegisterSpace: register_space,
+            Flags: D3D12_DESCRIPTOR_RANGE_FLAG_NONE,
+            OffsetInDescriptorsFromTableStart: 0,
+        }
+    }
+}
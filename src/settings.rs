@@ -0,0 +1,152 @@
+use std::{cell::Cell, time::Duration};
+
+use crate::{PresentConfig, gpulib::GpuInfo};
+
+/// Runtime adjustable settings of the running app together with the information shown next to them.
+/// The library owns one instance, which is shown with `show_settings` if `AppConfig::settings_panel` is set,
+/// and applies changes right after the UI of the frame has been recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GimsSettings {
+    /// Vsync and tearing of the window
+    pub present: PresentConfig,
+    /// Upper limit of frames per second in `RenderMode::Continuous`, `None` for no limit besides vsync
+    pub max_frame_rate: Option<u32>,
+    /// Whether the window covers its monitor as a borderless fullscreen window
+    pub fullscreen: bool,
+    /// Adapter the app runs on, for display only
+    pub adapter: GpuInfo,
+    /// Timing of the last frames, updated by the library every frame
+    pub frame_stats: FrameStats,
+}
+
+/// Frame timing shown in the settings panel
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    /// Time between the start of the last two frames
+    pub frame_time: Duration,
+    /// Frames per second, smoothed over the last frames
+    pub frames_per_second: f32,
+    /// GPU time of the frame command list in milliseconds, measured `frame_count` frames ago
+    pub gpu_frame_ms: f64,
+    /// Work recorded in the previous frame
+    pub submissions: SubmissionStats,
+}
+
+/// Number of GPU commands recorded in a frame, to spot CPU side submission overhead
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubmissionStats {
+    pub command_lists: u32,
+    pub draws: u32,
+    pub dispatches: u32,
+}
+
+/// Counts the work recorded in the current frame, see `FrameResources::submission_counter`.
+/// The library counts its own command list and the draws of the UI and of blits,
+/// apps report theirs after recording them. Counting has no GPU cost.
+#[derive(Debug, Default)]
+pub struct SubmissionCounter {
+    command_lists: Cell<u32>,
+    draws: Cell<u32>,
+    dispatches: Cell<u32>,
+}
+
+impl SubmissionCounter {
+    /// Counts command lists submitted by the app in addition to the one of the frame
+    pub fn add_command_lists(&self, count: u32) {
+        self.command_lists.set(self.command_lists.get() + count);
+    }
+
+    pub fn add_draws(&self, count: u32) {
+        self.draws.set(self.draws.get() + count);
+    }
+
+    pub fn add_dispatches(&self, count: u32) {
+        self.dispatches.set(self.dispatches.get() + count);
+    }
+
+    /// Returns the counts of the finished frame and starts counting the next one
+    pub(crate) fn take(&self) -> SubmissionStats {
+        SubmissionStats {
+            command_lists: self.command_lists.take(),
+            draws: self.draws.take(),
+            dispatches: self.dispatches.take(),
+        }
+    }
+}
+
+impl FrameStats {
+    /// Weight of the newest frame in the smoothed frame rate
+    const SMOOTHING: f32 = 0.1;
+
+    /// Records the time since the previous frame
+    pub(crate) fn update(&mut self, frame_time: Duration) {
+        self.frame_time = frame_time;
+        let frames_per_second = 1.0 / frame_time.as_secs_f32().max(f32::EPSILON);
+        self.frames_per_second = if self.frames_per_second == 0.0 {
+            frames_per_second
+        } else {
+            self.frames_per_second + (frames_per_second - self.frames_per_second) * Self::SMOOTHING
+        };
+    }
+}
+
+/// Shows `settings` in a window, e.g. for quick demos and debugging. Changes are written back to `settings`.
+pub fn show_settings(ctx: &egui::Context, settings: &mut GimsSettings) {
+    egui::Window::new("Settings")
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.heading("Presentation");
+            let mut vsync = settings.present.sync_interval > 0;
+            if ui.checkbox(&mut vsync, "Vsync").changed() {
+                settings.present.sync_interval = vsync as u32;
+            }
+            if vsync {
+                ui.add(
+                    egui::Slider::new(&mut settings.present.sync_interval, 1..=4)
+                        .text("Vertical blanks per frame"),
+                );
+            } else {
+                ui.checkbox(&mut settings.present.allow_tearing, "Allow tearing");
+            }
+
+            let mut limit_frame_rate = settings.max_frame_rate.is_some();
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut limit_frame_rate, "Limit frame rate");
+                let mut max_frame_rate = settings.max_frame_rate.unwrap_or(60);
+                ui.add_enabled(
+                    limit_frame_rate,
+                    egui::DragValue::new(&mut max_frame_rate)
+                        .range(1..=1000)
+                        .suffix(" fps"),
+                );
+                settings.max_frame_rate = limit_frame_rate.then_some(max_frame_rate);
+            });
+
+            ui.checkbox(&mut settings.fullscreen, "Fullscreen");
+
+            ui.separator();
+            ui.heading("Adapter");
+            ui.label(&settings.adapter.description);
+            ui.label(format!(
+                "Dedicated video memory: {} MiB",
+                settings.adapter.dedicated_video_memory >> 20
+            ));
+            if let Some(driver_version) = settings.adapter.driver_version_string() {
+                ui.label(format!("Driver: {}", driver_version));
+            }
+
+            ui.separator();
+            ui.heading("Frame");
+            ui.label(format!(
+                "{:.1} fps ({:.2} ms)",
+                settings.frame_stats.frames_per_second,
+                settings.frame_stats.frame_time.as_secs_f64() * 1000.0
+            ));
+            ui.label(format!("GPU: {:.2} ms", settings.frame_stats.gpu_frame_ms));
+            let submissions = settings.frame_stats.submissions;
+            ui.label(format!(
+                "{} command lists, {} draws, {} dispatches",
+                submissions.command_lists, submissions.draws, submissions.dispatches
+            ));
+        });
+}
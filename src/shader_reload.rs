@@ -0,0 +1,151 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{
+        Arc,
+        mpsc::{self, Receiver},
+    },
+};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use windows::Win32::Graphics::Direct3D12::ID3D12PipelineState;
+
+use crate::{error::GimsError, gpulib::GPULib};
+
+/// Identifies a pipeline registered with `ShaderReloader::watch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchedPipeline(usize);
+
+type RebuildFn = Box<dyn FnMut(&str) -> Result<ID3D12PipelineState, GimsError> + Send>;
+
+struct WatchedShader {
+    path: PathBuf,
+    rebuild: RebuildFn,
+    pipeline: ID3D12PipelineState,
+}
+
+/// Rebuilds pipelines when their HLSL files change on disk, so shaders can be tweaked while the app runs.
+/// Files are only watched in debug builds; release builds build every pipeline once.
+/// Requires the `shader-reload` feature.
+///
+/// Call `poll` at the start of `App::draw` and look up the pipelines afterwards:
+/// ```ignore
+/// let pipeline = reloader.watch("examples/09_culled_cube/shader.hlsl", move |source| {
+///     let vertex_shader = compile_shader(source, "VS_main", "vs_6_5")?;
+///     let pixel_shader = compile_shader(source, "PS_main", "ps_6_5")?;
+///     GraphicsPipelineBuilder::new(&root_signature, &vertex_shader)
+///         .pixel_shader(&pixel_shader)
+///         .build(&lib)
+/// })?;
+/// // In draw
+/// reloader.poll();
+/// res.command_list.SetPipelineState(reloader.pipeline(pipeline));
+/// ```
+pub struct ShaderReloader {
+    lib: Arc<GPULib>,
+    shaders: Vec<WatchedShader>,
+    watcher: RecommendedWatcher,
+    /// Directories registered with the watcher, each only once
+    watched_directories: HashSet<PathBuf>,
+    events: Receiver<notify::Result<notify::Event>>,
+}
+
+impl ShaderReloader {
+    pub fn new(lib: Arc<GPULib>) -> Result<Self, GimsError> {
+        let (sender, events) = mpsc::channel();
+        Ok(ShaderReloader {
+            lib,
+            shaders: Vec::new(),
+            watcher: notify::recommended_watcher(sender)?,
+            watched_directories: HashSet::new(),
+            events,
+        })
+    }
+
+    /// Reads the HLSL file at `path` and builds its pipeline with `rebuild`, which receives the source code.
+    /// `rebuild` is called again whenever the file changes. The first build has to succeed.
+    pub fn watch(
+        &mut self,
+        path: impl Into<PathBuf>,
+        mut rebuild: impl FnMut(&str) -> Result<ID3D12PipelineState, GimsError> + Send + 'static,
+    ) -> Result<WatchedPipeline, GimsError> {
+        // Events carry absolute paths
+        let path = std::fs::canonicalize(path.into())?;
+        let pipeline = rebuild(&std::fs::read_to_string(&path)?)?;
+
+        // The directory is watched instead of the file, since editors often save by replacing the file
+        if cfg!(debug_assertions)
+            && let Some(directory) = path.parent()
+            && self.watched_directories.insert(directory.to_path_buf())
+        {
+            self.watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        }
+
+        self.shaders.push(WatchedShader {
+            path,
+            rebuild: Box::new(rebuild),
+            pipeline,
+        });
+        Ok(WatchedPipeline(self.shaders.len() - 1))
+    }
+
+    /// The current pipeline built from the watched file
+    pub fn pipeline(&self, pipeline: WatchedPipeline) -> &ID3D12PipelineState {
+        &self.shaders[pipeline.0].pipeline
+    }
+
+    /// Rebuilds the pipelines whose files have changed since the last call.
+    /// Waits for the GPU once before replacing pipelines, since frames in flight may still use the old ones.
+    /// On failure, the old pipeline is kept and the error is printed, so a typo does not end the app.
+    /// Returns whether any pipeline was replaced.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = HashSet::new();
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) => {
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any
+                    ) {
+                        changed.extend(event.paths);
+                    }
+                }
+                Err(error) => eprintln!("Failed to watch the shader files: {}", error),
+            }
+        }
+
+        // Failed builds are not retried until the file changes again
+        let mut rebuilt = Vec::new();
+        for (index, shader) in self.shaders.iter_mut().enumerate() {
+            if !changed.contains(&shader.path) {
+                continue;
+            }
+            let pipeline = std::fs::read_to_string(&shader.path)
+                .map_err(GimsError::from)
+                .and_then(|source| (shader.rebuild)(&source));
+            match pipeline {
+                Ok(pipeline) => rebuilt.push((index, pipeline)),
+                Err(error) => eprintln!(
+                    "Failed to rebuild the pipeline of {}, keeping the previous one:\n{}",
+                    shader.path.display(),
+                    error
+                ),
+            }
+        }
+        if rebuilt.is_empty() {
+            return false;
+        }
+
+        if let Err(error) = self.lib.wait_idle() {
+            eprintln!(
+                "Failed to wait for the GPU before replacing pipelines: {}",
+                error
+            );
+            return false;
+        }
+        for (index, pipeline) in rebuilt {
+            self.shaders[index].pipeline = pipeline;
+        }
+        true
+    }
+}
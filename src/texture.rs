@@ -0,0 +1,745 @@
+use std::{mem::ManuallyDrop, path::Path};
+
+use windows::{
+    Win32::{
+        Foundation::HANDLE,
+        Graphics::{
+            Direct3D12::*,
+            Dxgi::Common::{
+                DXGI_FORMAT, DXGI_FORMAT_R8G8B8A8_UNORM_SRGB, DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC,
+            },
+        },
+    },
+    core::{HSTRING, Interface},
+};
+
+use crate::{
+    barrier::{StateTracker, Transitions},
+    error::GimsError,
+    gpulib::GPULib,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureDimension {
+    /// A single 2D texture
+    Texture2D,
+    /// An array of 2D textures with the specified number of slices, e.g. for shadow map cascades
+    Texture2DArray(u16),
+    /// Six square 2D faces in the order +X, -X, +Y, -Y, +Z, -Z
+    Cube,
+    /// An array of the specified number of cubes, each consisting of six faces
+    CubeArray(u16),
+    /// A volume texture with the specified depth, e.g. for 3D color grading lookup tables
+    Texture3D(u16),
+}
+
+impl TextureDimension {
+    /// The number of 2D slices stored in the resource.
+    /// For volume textures this is the depth, which becomes the `DepthOrArraySize` of the resource.
+    pub fn array_size(&self) -> u32 {
+        match self {
+            TextureDimension::Texture2D => 1,
+            TextureDimension::Texture2DArray(slices) => *slices as u32,
+            TextureDimension::Cube => 6,
+            TextureDimension::CubeArray(cubes) => *cubes as u32 * 6,
+            TextureDimension::Texture3D(depth) => *depth as u32,
+        }
+    }
+
+    /// The number of depth slices of a single subresource, which is only larger than 1 for volume textures
+    pub fn depth(&self) -> u32 {
+        match self {
+            TextureDimension::Texture3D(depth) => *depth as u32,
+            _ => 1,
+        }
+    }
+}
+
+/// Optimized clear value of a render target or depth buffer.
+/// Clears with exactly this value take the fast clear path, others work but trigger a debug layer warning.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClearValue {
+    /// For textures with `D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET`
+    Color([f32; 4]),
+    /// For textures with `D3D12_RESOURCE_FLAG_ALLOW_DEPTH_STENCIL`
+    DepthStencil { depth: f32, stencil: u8 },
+}
+
+/// Description of a texture resource and the views created for it
+#[derive(Debug, Clone, Copy)]
+pub struct TextureDesc {
+    pub width: u32,
+    pub height: u32,
+    pub dimension: TextureDimension,
+    pub format: DXGI_FORMAT,
+    pub mip_levels: u16,
+    pub flags: D3D12_RESOURCE_FLAGS,
+    /// Samples per pixel, larger than 1 for multisampled render targets and depth buffers
+    pub sample_count: u32,
+    /// Passed to resource creation, has to match the clear calls of the app
+    pub clear_value: Option<ClearValue>,
+}
+
+impl TextureDesc {
+    /// Description of a single 2D texture without mipmaps
+    pub fn new_2d(width: u32, height: u32, format: DXGI_FORMAT) -> Self {
+        TextureDesc {
+            width,
+            height,
+            dimension: TextureDimension::Texture2D,
+            format,
+            mip_levels: 1,
+            flags: D3D12_RESOURCE_FLAG_NONE,
+            sample_count: 1,
+            clear_value: None,
+        }
+    }
+
+    /// A 2D render target, which is cleared to `color` most of the time
+    pub fn render_target(width: u32, height: u32, format: DXGI_FORMAT, color: [f32; 4]) -> Self {
+        TextureDesc {
+            flags: D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET,
+            clear_value: Some(ClearValue::Color(color)),
+            ..Self::new_2d(width, height, format)
+        }
+    }
+
+    /// A 2D depth buffer, which is cleared to `depth` and a stencil of 0 most of the time
+    pub fn depth_buffer(width: u32, height: u32, format: DXGI_FORMAT, depth: f32) -> Self {
+        TextureDesc {
+            flags: D3D12_RESOURCE_FLAG_ALLOW_DEPTH_STENCIL,
+            clear_value: Some(ClearValue::DepthStencil { depth, stencil: 0 }),
+            ..Self::new_2d(width, height, format)
+        }
+    }
+
+    /// The optimized clear value passed to `CreateCommittedResource`, after checking that it fits the resource
+    pub fn optimized_clear_value(&self) -> Result<Option<D3D12_CLEAR_VALUE>, GimsError> {
+        let Some(clear_value) = self.clear_value else {
+            return Ok(None);
+        };
+        if self.format == DXGI_FORMAT_UNKNOWN {
+            return Err("Clear values need a texture format".into());
+        }
+
+        let anonymous = match clear_value {
+            ClearValue::Color(color) => {
+                if (self.flags & D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET).0 == 0 {
+                    return Err(
+                        "Color clear values need D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET".into(),
+                    );
+                }
+                D3D12_CLEAR_VALUE_0 { Color: color }
+            }
+            ClearValue::DepthStencil { depth, stencil } => {
+                if (self.flags & D3D12_RESOURCE_FLAG_ALLOW_DEPTH_STENCIL).0 == 0 {
+                    return Err(
+                        "Depth stencil clear values need D3D12_RESOURCE_FLAG_ALLOW_DEPTH_STENCIL"
+                            .into(),
+                    );
+                }
+                D3D12_CLEAR_VALUE_0 {
+                    DepthStencil: D3D12_DEPTH_STENCIL_VALUE {
+                        Depth: depth,
+                        Stencil: stencil,
+                    },
+                }
+            }
+        };
+
+        Ok(Some(D3D12_CLEAR_VALUE {
+            Format: self.format,
+            Anonymous: anonymous,
+        }))
+    }
+
+    /// Creates the matching `D3D12_RESOURCE_DESC` after checking the dimension constraints
+    pub fn resource_desc(&self) -> Result<D3D12_RESOURCE_DESC, GimsError> {
+        let array_size = self.dimension.array_size();
+        if array_size == 0 {
+            return Err("Texture arrays need at least one slice".into());
+        }
+        let (resource_dimension, max_array_size) = match self.dimension {
+            TextureDimension::Texture3D(_) => (
+                D3D12_RESOURCE_DIMENSION_TEXTURE3D,
+                D3D12_REQ_TEXTURE3D_U_V_OR_W_DIMENSION,
+            ),
+            _ => (
+                D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+                D3D12_REQ_TEXTURE2D_ARRAY_AXIS_DIMENSION,
+            ),
+        };
+        if array_size > max_array_size {
+            return Err(format!(
+                "Texture array size or depth {} exceeds the maximum of {}",
+                array_size, max_array_size
+            )
+            .into());
+        }
+        if matches!(
+            self.dimension,
+            TextureDimension::Cube | TextureDimension::CubeArray(_)
+        ) && self.width != self.height
+        {
+            return Err("Cube map faces must be square".into());
+        }
+        if self.sample_count > 1
+            && (self.mip_levels != 1
+                || !matches!(
+                    self.dimension,
+                    TextureDimension::Texture2D | TextureDimension::Texture2DArray(_)
+                ))
+        {
+            return Err("Multisampled textures must be 2D textures without mipmaps".into());
+        }
+
+        Ok(D3D12_RESOURCE_DESC {
+            Dimension: resource_dimension,
+            Width: self.width as u64,
+            Height: self.height,
+            DepthOrArraySize: array_size as u16,
+            Alignment: 0,
+            MipLevels: self.mip_levels,
+            Format: self.format,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: self.sample_count,
+                Quality: 0,
+            },
+            Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+            Flags: self.flags,
+        })
+    }
+
+    /// Creates a shader resource view description covering all mips and slices of the texture.
+    /// Cube dimensions result in a `TextureCube`/`TextureCubeArray` view.
+    pub fn shader_resource_view_desc(&self) -> D3D12_SHADER_RESOURCE_VIEW_DESC {
+        let mip_levels = self.mip_levels as u32;
+        let (view_dimension, anonymous) = match self.dimension {
+            TextureDimension::Texture2D => (
+                D3D12_SRV_DIMENSION_TEXTURE2D,
+                D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                    Texture2D: D3D12_TEX2D_SRV {
+                        MostDetailedMip: 0,
+                        MipLevels: mip_levels,
+                        PlaneSlice: 0,
+                        ResourceMinLODClamp: 0.0,
+                    },
+                },
+            ),
+            TextureDimension::Texture2DArray(slices) => (
+                D3D12_SRV_DIMENSION_TEXTURE2DARRAY,
+                D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                    Texture2DArray: D3D12_TEX2D_ARRAY_SRV {
+                        MostDetailedMip: 0,
+                        MipLevels: mip_levels,
+                        FirstArraySlice: 0,
+                        ArraySize: slices as u32,
+                        PlaneSlice: 0,
+                        ResourceMinLODClamp: 0.0,
+                    },
+                },
+            ),
+            TextureDimension::Cube => (
+                D3D12_SRV_DIMENSION_TEXTURECUBE,
+                D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                    TextureCube: D3D12_TEXCUBE_SRV {
+                        MostDetailedMip: 0,
+                        MipLevels: mip_levels,
+                        ResourceMinLODClamp: 0.0,
+                    },
+                },
+            ),
+            TextureDimension::CubeArray(cubes) => (
+                D3D12_SRV_DIMENSION_TEXTURECUBEARRAY,
+                D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                    TextureCubeArray: D3D12_TEXCUBE_ARRAY_SRV {
+                        MostDetailedMip: 0,
+                        MipLevels: mip_levels,
+                        First2DArrayFace: 0,
+                        NumCubes: cubes as u32,
+                        ResourceMinLODClamp: 0.0,
+                    },
+                },
+            ),
+            TextureDimension::Texture3D(_) => (
+                D3D12_SRV_DIMENSION_TEXTURE3D,
+                D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                    Texture3D: D3D12_TEX3D_SRV {
+                        MostDetailedMip: 0,
+                        MipLevels: mip_levels,
+                        ResourceMinLODClamp: 0.0,
+                    },
+                },
+            ),
+        };
+
+        D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: self.format,
+            ViewDimension: view_dimension,
+            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            Anonymous: anonymous,
+        }
+    }
+
+    /// Checks that an existing resource was created with the dimensions of this description
+    fn validate_resource(&self, texture: &ID3D12Resource) -> Result<(), GimsError> {
+        let expected = self.resource_desc()?;
+        let actual = unsafe { texture.GetDesc() };
+        if actual.Dimension != expected.Dimension
+            || actual.DepthOrArraySize != expected.DepthOrArraySize
+            || actual.Width != expected.Width
+            || actual.Height != expected.Height
+        {
+            return Err(format!(
+                "Texture description {:?} does not match the resource it is used with",
+                self.dimension
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Layout of a subresource in an upload or readback buffer, computed by the device.
+    /// Subresources are numbered `mip + slice * mip_levels`. Works for all formats, including block compressed ones.
+    pub fn copyable_footprint(
+        &self,
+        lib: &GPULib,
+        subresource: u32,
+    ) -> Result<CopyableFootprint, GimsError> {
+        copyable_footprint(lib, &self.resource_desc()?, subresource)
+    }
+}
+
+/// Layout of a single subresource in a buffer as returned by `ID3D12Device::GetCopyableFootprints`
+#[derive(Debug, Clone, Copy)]
+pub struct CopyableFootprint {
+    /// Offset and footprint, usable as the `PlacedFootprint` of a `D3D12_TEXTURE_COPY_LOCATION`
+    pub layout: D3D12_PLACED_SUBRESOURCE_FOOTPRINT,
+    /// Number of rows per depth slice. Block compressed formats have one row per row of blocks.
+    pub row_count: u32,
+    /// Size of a row without the padding up to `layout.Footprint.RowPitch`
+    pub row_bytes: u64,
+    /// Size of the buffer needed for the subresource
+    pub total_bytes: u64,
+}
+
+/// Queries the buffer layout of one subresource of `resource_desc` from the device
+pub fn copyable_footprint(
+    lib: &GPULib,
+    resource_desc: &D3D12_RESOURCE_DESC,
+    subresource: u32,
+) -> Result<CopyableFootprint, GimsError> {
+    let mut layout = D3D12_PLACED_SUBRESOURCE_FOOTPRINT::default();
+    let mut row_count = 0;
+    let mut row_bytes = 0;
+    let mut total_bytes = 0;
+    unsafe {
+        lib.device.GetCopyableFootprints(
+            resource_desc,
+            subresource,
+            1,
+            0,
+            Some(&mut layout),
+            Some(&mut row_count),
+            Some(&mut row_bytes),
+            Some(&mut total_bytes),
+        )
+    };
+
+    // Invalid descriptions and subresource indices are reported with the maximum value
+    if total_bytes == u64::MAX {
+        return Err(format!(
+            "No copyable footprint for subresource {} of the resource description",
+            subresource
+        )
+        .into());
+    }
+
+    Ok(CopyableFootprint {
+        layout,
+        row_count,
+        row_bytes,
+        total_bytes,
+    })
+}
+
+/// Copies tightly packed rows from `source` into mapped upload memory laid out as described by `footprint`,
+/// starting at `footprint.layout.Offset`. Walks all depth slices, each taking `RowPitch * row_count` bytes,
+/// so volume textures are uploaded in one go. Block compressed formats are copied one row of blocks at a time.
+pub fn copy_to_footprint(
+    destination: &mut [u8],
+    source: &[u8],
+    footprint: &CopyableFootprint,
+) -> Result<(), GimsError> {
+    let offset = footprint.layout.Offset as usize;
+    let row_bytes = footprint.row_bytes as usize;
+    let row_pitch = footprint.layout.Footprint.RowPitch as usize;
+    let row_count = footprint.row_count as usize;
+    let depth = footprint.layout.Footprint.Depth as usize;
+    if row_count == 0 || depth == 0 {
+        return Ok(());
+    }
+
+    let source_size = row_bytes * row_count * depth;
+    if source.len() < source_size {
+        return Err(format!(
+            "Texture data has {} bytes, but the footprint needs {}",
+            source.len(),
+            source_size
+        )
+        .into());
+    }
+    // The last row is not padded
+    let slice_pitch = row_pitch * row_count;
+    if destination.len()
+        < offset + slice_pitch * (depth - 1) + row_pitch * (row_count - 1) + row_bytes
+    {
+        return Err("Upload buffer is smaller than the texture footprint".into());
+    }
+
+    for slice in 0..depth {
+        for row in 0..row_count {
+            let source_start = (slice * row_count + row) * row_bytes;
+            let destination_start = offset + slice * slice_pitch + row * row_pitch;
+            destination[destination_start..destination_start + row_bytes]
+                .copy_from_slice(&source[source_start..source_start + row_bytes]);
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a committed texture resource in the `COMMON` state.
+/// Depth buffers can not be promoted from `COMMON` implicitly and need a transition to `DEPTH_WRITE` before use.
+pub fn create_texture(
+    lib: &GPULib,
+    desc: &TextureDesc,
+    heap_type: D3D12_HEAP_TYPE,
+    name: Option<&str>,
+) -> Result<ID3D12Resource, GimsError> {
+    let heap_properties = D3D12_HEAP_PROPERTIES {
+        Type: heap_type,
+        ..Default::default()
+    };
+
+    let resource_desc = desc.resource_desc()?;
+    let clear_value = desc.optimized_clear_value()?;
+
+    let mut resource_option = None;
+    unsafe {
+        lib.device.CreateCommittedResource(
+            &heap_properties,
+            D3D12_HEAP_FLAGS::default(),
+            &resource_desc,
+            D3D12_RESOURCE_STATE_COMMON,
+            clear_value
+                .as_ref()
+                .map(|clear_value| clear_value as *const _),
+            &mut resource_option,
+        )
+    }
+    .map_err(GimsError::resource_creation)?;
+
+    let resource: ID3D12Resource = resource_option.ok_or("Failed to create texture resource")?;
+    if let Some(name) = name {
+        unsafe { resource.SetName(&HSTRING::from(name)) }?;
+    }
+
+    Ok(resource)
+}
+
+/// Writes a shader resource view for the whole texture to `handle`.
+/// Fails if `desc` does not describe the dimensions `texture` was created with.
+pub fn create_shader_resource_view(
+    lib: &GPULib,
+    texture: &ID3D12Resource,
+    desc: &TextureDesc,
+    handle: D3D12_CPU_DESCRIPTOR_HANDLE,
+) -> Result<(), GimsError> {
+    desc.validate_resource(texture)?;
+    unsafe {
+        lib.device.CreateShaderResourceView(
+            texture,
+            Some(&desc.shader_resource_view_desc()),
+            handle,
+        )
+    };
+
+    Ok(())
+}
+
+/// Writes a render target view for a single slice (or cube face) and mip of the texture to `handle`.
+/// Cube faces are addressed as `cube_index * 6 + face`, volume textures by their depth slice within the mip.
+pub fn create_slice_render_target_view(
+    lib: &GPULib,
+    texture: &ID3D12Resource,
+    desc: &TextureDesc,
+    slice: u32,
+    mip: u32,
+    handle: D3D12_CPU_DESCRIPTOR_HANDLE,
+) -> Result<(), GimsError> {
+    desc.validate_resource(texture)?;
+    if mip >= desc.mip_levels as u32 {
+        return Err(format!(
+            "Mip {} is out of range for a texture with {} mip levels",
+            mip, desc.mip_levels
+        )
+        .into());
+    }
+    // Volume textures halve their depth with each mip
+    let slice_count = match desc.dimension {
+        TextureDimension::Texture3D(depth) => (depth as u32 >> mip).max(1),
+        dimension => dimension.array_size(),
+    };
+    if slice >= slice_count {
+        return Err(format!(
+            "Slice {} is out of range for mip {} of a texture with {} slices",
+            slice, mip, slice_count
+        )
+        .into());
+    }
+
+    let rtv_desc = match desc.dimension {
+        TextureDimension::Texture3D(_) => D3D12_RENDER_TARGET_VIEW_DESC {
+            Format: desc.format,
+            ViewDimension: D3D12_RTV_DIMENSION_TEXTURE3D,
+            Anonymous: D3D12_RENDER_TARGET_VIEW_DESC_0 {
+                Texture3D: D3D12_TEX3D_RTV {
+                    MipSlice: mip,
+                    FirstWSlice: slice,
+                    WSize: 1,
+                },
+            },
+        },
+        _ => D3D12_RENDER_TARGET_VIEW_DESC {
+            Format: desc.format,
+            ViewDimension: D3D12_RTV_DIMENSION_TEXTURE2DARRAY,
+            Anonymous: D3D12_RENDER_TARGET_VIEW_DESC_0 {
+                Texture2DArray: D3D12_TEX2D_ARRAY_RTV {
+                    MipSlice: mip,
+                    FirstArraySlice: slice,
+                    ArraySize: 1,
+                    PlaneSlice: 0,
+                },
+            },
+        },
+    };
+    unsafe {
+        lib.device
+            .CreateRenderTargetView(texture, Some(&rtv_desc), handle)
+    };
+
+    Ok(())
+}
+
+/// A texture uploaded with `upload_texture_2d` together with its shader resource view
+pub struct UploadedTexture {
+    pub resource: ID3D12Resource,
+    /// Non shader visible heap holding only the view, to be copied into the app's heap with `CopyDescriptorsSimple`
+    pub descriptor_heap: ID3D12DescriptorHeap,
+    /// Shader resource view of the whole texture
+    pub descriptor: D3D12_CPU_DESCRIPTOR_HANDLE,
+}
+
+/// Creates a 2D texture in GPU memory and fills it with `data`, whose rows are tightly packed, see `upload_texture`.
+/// Meant for loading at startup, use `AssetLoader` to stream textures while rendering.
+pub fn upload_texture_2d(
+    lib: &GPULib,
+    width: u32,
+    height: u32,
+    format: DXGI_FORMAT,
+    data: &[u8],
+) -> Result<UploadedTexture, GimsError> {
+    upload_texture(lib, &TextureDesc::new_2d(width, height, format), data)
+}
+
+/// Creates a 2D or volume texture without mipmaps in GPU memory and fills it with `data`, whose rows are
+/// tightly packed and whose depth slices follow each other.
+/// Blocks until the copy has finished on `GPULib::queue`, after which the texture is in
+/// `D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE` and the staging buffer has been released.
+pub fn upload_texture(
+    lib: &GPULib,
+    desc: &TextureDesc,
+    data: &[u8],
+) -> Result<UploadedTexture, GimsError> {
+    // Only the first subresource is filled, which holds all depth slices of a volume texture
+    if desc.mip_levels != 1
+        || !matches!(
+            desc.dimension,
+            TextureDimension::Texture2D | TextureDimension::Texture3D(_)
+        )
+    {
+        return Err(format!(
+            "Only 2D and volume textures without mipmaps can be uploaded, got {:?} with {} mip levels",
+            desc.dimension, desc.mip_levels
+        )
+        .into());
+    }
+    let footprint = desc.copyable_footprint(lib, 0)?;
+
+    let upload_buffer = create_staging_buffer(lib, footprint.total_bytes)?;
+    unsafe {
+        let mut mapped = std::ptr::null_mut();
+        upload_buffer.Map(0, Some(&D3D12_RANGE::default()), Some(&mut mapped))?;
+        let destination =
+            std::slice::from_raw_parts_mut(mapped as *mut u8, footprint.total_bytes as usize);
+        let result = copy_to_footprint(destination, data, &footprint);
+        upload_buffer.Unmap(0, None);
+        result?;
+    }
+
+    let texture = create_texture(lib, desc, D3D12_HEAP_TYPE_DEFAULT, None)?;
+
+    // The allocator backs the recorded commands, so it has to live until they have executed
+    let (_command_allocator, command_list) =
+        lib.create_command_list(D3D12_COMMAND_LIST_TYPE_DIRECT)?;
+
+    let mut resource_states = StateTracker::default();
+    Transitions::new(&mut resource_states)
+        .add(
+            &texture,
+            D3D12_RESOURCE_STATE_COMMON,
+            D3D12_RESOURCE_STATE_COPY_DEST,
+        )
+        .record(&command_list);
+
+    let mut destination = D3D12_TEXTURE_COPY_LOCATION {
+        pResource: ManuallyDrop::new(Some(texture.clone())),
+        Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+        Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+            SubresourceIndex: 0,
+        },
+    };
+    let mut source = D3D12_TEXTURE_COPY_LOCATION {
+        pResource: ManuallyDrop::new(Some(upload_buffer.clone())),
+        Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
+        Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+            PlacedFootprint: footprint.layout,
+        },
+    };
+    unsafe {
+        command_list.CopyTextureRegion(&destination, 0, 0, 0, &source, None);
+        // Release the references taken for the copy locations
+        ManuallyDrop::drop(&mut destination.pResource);
+        ManuallyDrop::drop(&mut source.pResource);
+    }
+
+    Transitions::new(&mut resource_states)
+        .add(
+            &texture,
+            D3D12_RESOURCE_STATE_COPY_DEST,
+            D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+        )
+        .record(&command_list);
+
+    let fence: ID3D12Fence = unsafe { lib.device.CreateFence(0, D3D12_FENCE_FLAG_NONE) }?;
+    unsafe {
+        command_list.Close()?;
+        lib.queue.ExecuteCommandLists(&[Some(command_list.cast()?)]);
+        lib.queue.Signal(&fence, 1)?;
+        // A null event handle blocks until the fence reaches the value
+        fence.SetEventOnCompletion(1, HANDLE::default())?;
+    }
+    // The staging buffer and the command list are released here, after the copy has finished
+    drop(upload_buffer);
+
+    let descriptor_heap: ID3D12DescriptorHeap = unsafe {
+        lib.device
+            .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                Type: D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+                NumDescriptors: 1,
+                Flags: D3D12_DESCRIPTOR_HEAP_FLAG_NONE,
+                NodeMask: 0,
+            })
+    }?;
+    let descriptor = unsafe { descriptor_heap.GetCPUDescriptorHandleForHeapStart() };
+    create_shader_resource_view(lib, &texture, desc, descriptor)?;
+
+    Ok(UploadedTexture {
+        resource: texture,
+        descriptor_heap,
+        descriptor,
+    })
+}
+
+/// Loads a PNG or JPEG file into a texture in GPU memory, see `upload_texture_2d`.
+/// The image is converted to RGBA8 and stored as `DXGI_FORMAT_R8G8B8A8_UNORM_SRGB`, since image files are gamma encoded,
+/// so sampling returns linear colors. Returns the texture and a shader visible heap with its shader resource view
+/// in the first slot, ready for `SetDescriptorHeaps` and a descriptor table.
+pub fn load_from_file(
+    lib: &GPULib,
+    path: &Path,
+) -> Result<(ID3D12Resource, ID3D12DescriptorHeap), GimsError> {
+    let image = image::open(path)
+        .map_err(|error| format!("Failed to load {}: {}", path.display(), error))?
+        .into_rgba8();
+    let texture = upload_texture_2d(
+        lib,
+        image.width(),
+        image.height(),
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        image.as_raw(),
+    )?;
+    unsafe {
+        texture
+            .resource
+            .SetName(&HSTRING::from(path.display().to_string()))
+    }?;
+
+    let descriptor_heap: ID3D12DescriptorHeap = unsafe {
+        lib.device
+            .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                Type: D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+                NumDescriptors: 1,
+                Flags: D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+                NodeMask: 0,
+            })
+    }?;
+    unsafe {
+        lib.device.CopyDescriptorsSimple(
+            1,
+            descriptor_heap.GetCPUDescriptorHandleForHeapStart(),
+            texture.descriptor,
+            D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+        )
+    };
+
+    Ok((texture.resource, descriptor_heap))
+}
+
+fn create_staging_buffer(lib: &GPULib, size: u64) -> Result<ID3D12Resource, GimsError> {
+    lib.validate_buffer_size(size)?;
+
+    let mut resource_option = None;
+    unsafe {
+        lib.device.CreateCommittedResource(
+            &D3D12_HEAP_PROPERTIES {
+                Type: D3D12_HEAP_TYPE_UPLOAD,
+                ..Default::default()
+            },
+            D3D12_HEAP_FLAGS::default(),
+            &D3D12_RESOURCE_DESC {
+                Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+                Width: size,
+                Height: 1,
+                DepthOrArraySize: 1,
+                Alignment: 0,
+                MipLevels: 1,
+                Format: DXGI_FORMAT_UNKNOWN,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+                Flags: D3D12_RESOURCE_FLAGS::default(),
+            },
+            D3D12_RESOURCE_STATE_GENERIC_READ,
+            None,
+            &mut resource_option,
+        )
+    }
+    .map_err(GimsError::resource_creation)?;
+
+    Ok(resource_option.ok_or("Failed to create texture staging buffer")?)
+}
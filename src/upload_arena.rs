@@ -0,0 +1,171 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use windows::{
+    Win32::Graphics::{
+        Direct3D12::*,
+        Dxgi::Common::{DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC},
+    },
+    core::h,
+};
+
+use crate::{error::GimsError, gpulib::GPULib};
+
+/// Upload buffer which stays mapped for its whole lifetime
+struct UploadChunk {
+    resource: ID3D12Resource,
+    gpu_address: u64,
+    mapped: *mut u8,
+    size: u64,
+    /// Offset of the first free byte
+    offset: u64,
+}
+
+impl UploadChunk {
+    fn new(lib: &GPULib, size: u64) -> Result<Self, GimsError> {
+        lib.validate_buffer_size(size)?;
+
+        let heap_properties = D3D12_HEAP_PROPERTIES {
+            Type: D3D12_HEAP_TYPE_UPLOAD,
+            ..Default::default()
+        };
+        let resource_desc = D3D12_RESOURCE_DESC {
+            Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+            Width: size,
+            Height: 1,
+            DepthOrArraySize: 1,
+            Alignment: 0,
+            MipLevels: 1,
+            Format: DXGI_FORMAT_UNKNOWN,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+            Flags: D3D12_RESOURCE_FLAGS::default(),
+        };
+
+        let mut resource_option: Option<ID3D12Resource> = None;
+        unsafe {
+            lib.device.CreateCommittedResource(
+                &heap_properties,
+                D3D12_HEAP_FLAGS::default(),
+                &resource_desc,
+                D3D12_RESOURCE_STATE_GENERIC_READ,
+                None,
+                &mut resource_option,
+            )
+        }
+        .map_err(GimsError::resource_creation)?;
+        let resource = resource_option.ok_or("Failed to create upload arena buffer")?;
+        unsafe { resource.SetName(h!("Upload arena")) }?;
+
+        // Upload heaps may stay mapped while the GPU reads from them
+        let mut mapped = std::ptr::null_mut();
+        unsafe {
+            resource.Map(0, Some(&D3D12_RANGE::default()), Some(&mut mapped))?;
+        }
+
+        Ok(UploadChunk {
+            gpu_address: unsafe { resource.GetGPUVirtualAddress() },
+            resource,
+            mapped: mapped as *mut u8,
+            size,
+            offset: 0,
+        })
+    }
+}
+
+impl Drop for UploadChunk {
+    fn drop(&mut self) {
+        unsafe { self.resource.Unmap(0, None) };
+    }
+}
+
+/// Linear allocator for data the GPU only needs during a single frame, e.g. transient constants or debug vertices.
+/// Each frame in flight has its own arena, which is reset once the GPU has finished that frame.
+/// Allocations never move, if the arena runs full another buffer is added.
+/// Subsequent frames then use one buffer large enough for everything allocated in that frame.
+pub struct UploadArena {
+    lib: Arc<GPULib>,
+    chunks: RefCell<Vec<UploadChunk>>,
+}
+
+impl UploadArena {
+    pub(crate) fn new(lib: Arc<GPULib>, size: u64) -> Result<Self, GimsError> {
+        let chunk = UploadChunk::new(&lib, size.max(1))?;
+
+        Ok(UploadArena {
+            lib,
+            chunks: RefCell::new(vec![chunk]),
+        })
+    }
+
+    /// Allocates `bytes` bytes aligned for constant buffers (`D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT`).
+    /// Returns the GPU virtual address of the allocation and the mapped memory to write the data to.
+    /// The memory is write combined, so it should only be written and never read.
+    pub fn alloc_upload(&self, bytes: usize) -> Result<(u64, &mut [u8]), GimsError> {
+        self.alloc_upload_aligned(bytes, D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT as u64)
+    }
+
+    /// Like `alloc_upload`, but with a custom alignment, which has to be a power of two
+    // Every allocation is a distinct range of the mapped memory and the arena is only reset through `&mut self`,
+    // so handing out mutable slices from a shared reference cannot alias.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_upload_aligned(
+        &self,
+        bytes: usize,
+        alignment: u64,
+    ) -> Result<(u64, &mut [u8]), GimsError> {
+        if !alignment.is_power_of_two() {
+            return Err("Upload arena alignment must be a power of two".into());
+        }
+
+        let bytes = bytes as u64;
+        let mut chunks = self.chunks.borrow_mut();
+        let fits =
+            |chunk: &UploadChunk| chunk.offset.next_multiple_of(alignment) + bytes <= chunk.size;
+        if !chunks.last().is_some_and(fits) {
+            let size = chunks.last().map_or(0, |chunk| chunk.size).max(bytes);
+            chunks.push(UploadChunk::new(&self.lib, size)?);
+        }
+
+        let chunk = chunks.last_mut().unwrap();
+        let offset = chunk.offset.next_multiple_of(alignment);
+        chunk.offset = offset + bytes;
+
+        let data = unsafe {
+            std::slice::from_raw_parts_mut(chunk.mapped.add(offset as usize), bytes as usize)
+        };
+        Ok((chunk.gpu_address + offset, data))
+    }
+
+    /// Copies `data` into the arena and returns its GPU virtual address
+    pub fn upload<T: bytemuck::Pod>(&self, data: &[T]) -> Result<u64, GimsError> {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let (gpu_address, memory) = self.alloc_upload(bytes.len())?;
+        memory.copy_from_slice(bytes);
+        Ok(gpu_address)
+    }
+
+    /// Number of bytes allocated since the last reset
+    pub fn allocated_bytes(&self) -> u64 {
+        self.chunks.borrow().iter().map(|chunk| chunk.offset).sum()
+    }
+
+    /// Frees all allocations. Must only be called once the GPU has finished reading them.
+    pub(crate) fn reset(&mut self) -> Result<(), GimsError> {
+        let chunks = self.chunks.get_mut();
+        if chunks.len() > 1 {
+            // Replace the chunks by one which fits the whole frame
+            let total_size = chunks.iter().map(|chunk| chunk.size).sum();
+            chunks.clear();
+            chunks.push(UploadChunk::new(&self.lib, total_size)?);
+        }
+        for chunk in chunks.iter_mut() {
+            chunk.offset = 0;
+        }
+
+        Ok(())
+    }
+}
@@ -1,14 +1,19 @@
 use std::{ops::Deref, sync::Arc};
 
 use windows::{
-    Win32::Graphics::{
-        Direct3D12::*,
-        Dxgi::Common::{DXGI_FORMAT, DXGI_FORMAT_UNKNOWN},
+    Win32::{
+        Foundation::HANDLE,
+        Graphics::{
+            Direct3D12::*,
+            Dxgi::Common::{DXGI_FORMAT, DXGI_FORMAT_UNKNOWN},
+        },
     },
-    core::HSTRING,
+    core::{HSTRING, Interface},
 };
 
-use crate::gpulib::GPULib;
+use crate::{error::GimsError, gpulib::GPULib};
+
+const CONSTANT_BUFFER_ALIGNMENT: usize = D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT as usize;
 
 #[derive(Debug, Clone, Copy)]
 pub enum BufferLocation {
@@ -16,18 +21,26 @@ pub enum BufferLocation {
     Cpu,
     /// GPU memory, mapping is possible. Only usable on modern graphics cards with ResizableBAR.
     GpuUpload,
+    /// GPU memory, mapping is impossible. Writes go through an internal staging buffer in CPU memory
+    /// and are copied on the copy queue, waiting for the copy to finish. Slower to write than the other locations,
+    /// but fast to read on every graphics card.
+    GpuDefault,
 }
 
 /// Direct3D 12 buffer which is automatically resized to fit the data written to it.
 /// It will never shrink automatically, so smaller future writes happen immediately
-/// without a new allocation. It dereferences to it's internal `ID3D12Resource`.
+/// without a new allocation. Use `shrink_to_fit` to release unused space. It dereferences to it's internal `ID3D12Resource`.
 pub struct VectorConstantBuffer<T> {
     lib: Arc<GPULib>,
     resource: ID3D12Resource,
     max_size: usize,
     current_len: usize,
     location: BufferLocation,
+    /// Flags of the resource, kept when it is reallocated
+    flags: D3D12_RESOURCE_FLAGS,
     name: Option<String>,
+    /// Upload buffer for `BufferLocation::GpuDefault`, kept for the next write
+    staging: Option<ID3D12Resource>,
     data_type: std::marker::PhantomData<T>,
 }
 
@@ -40,8 +53,36 @@ impl<T> VectorConstantBuffer<T> {
         initial_size: usize,
         location: BufferLocation,
         name: Option<String>,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        let resource = Self::create_resource(&lib, initial_size, location, &name)?;
+    ) -> Result<Self, GimsError> {
+        Self::with_flags(
+            lib,
+            initial_size,
+            location,
+            D3D12_RESOURCE_FLAGS::default(),
+            name,
+        )
+    }
+
+    /// Like `new`, but creates the resource with `flags`, e.g. `D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS`
+    /// for buffers written by compute shaders, see `create_unordered_access_view`. Unordered access is only
+    /// possible in `BufferLocation::GpuDefault`, other locations fail with it.
+    pub fn with_flags(
+        lib: Arc<GPULib>,
+        initial_size: usize,
+        location: BufferLocation,
+        flags: D3D12_RESOURCE_FLAGS,
+        name: Option<String>,
+    ) -> Result<Self, GimsError> {
+        if flags.contains(D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS)
+            && !matches!(location, BufferLocation::GpuDefault)
+        {
+            return Err(format!(
+                "Unordered access requires BufferLocation::GpuDefault, but the location is {:?}",
+                location
+            )
+            .into());
+        }
+        let resource = Self::create_resource(&lib, initial_size, location, flags, &name)?;
 
         Ok(VectorConstantBuffer {
             lib,
@@ -49,7 +90,9 @@ impl<T> VectorConstantBuffer<T> {
             max_size: initial_size,
             current_len: 0,
             location,
+            flags,
             name,
+            staging: None,
             data_type: std::marker::PhantomData,
         })
     }
@@ -58,46 +101,23 @@ impl<T> VectorConstantBuffer<T> {
         lib: &GPULib,
         count: usize,
         location: BufferLocation,
+        flags: D3D12_RESOURCE_FLAGS,
         name: &Option<String>,
-    ) -> Result<ID3D12Resource, Box<dyn std::error::Error>> {
-        let heap_properties = D3D12_HEAP_PROPERTIES {
-            Type: match location {
-                BufferLocation::Cpu => D3D12_HEAP_TYPE_UPLOAD,
-                BufferLocation::GpuUpload => D3D12_HEAP_TYPE_GPU_UPLOAD,
-            },
-            ..Default::default()
-        };
+    ) -> Result<ID3D12Resource, GimsError> {
+        // Rounded up, so constant buffer views spanning all items stay inside the buffer
+        let size: u64 = count
+            .checked_mul(size_of::<T>())
+            .and_then(|size| size.checked_next_multiple_of(CONSTANT_BUFFER_ALIGNMENT))
+            .ok_or("Vector constant buffer size overflows")?
+            .try_into()?;
+        lib.validate_buffer_size(size)?;
 
-        let resource_desc = D3D12_RESOURCE_DESC {
-            Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
-            Width: (count * size_of::<T>()).try_into()?,
-            Height: 1,
-            DepthOrArraySize: 1,
-            Alignment: 0,
-            MipLevels: 1,
-            Format: DXGI_FORMAT_UNKNOWN,
-            SampleDesc: windows::Win32::Graphics::Dxgi::Common::DXGI_SAMPLE_DESC {
-                Count: 1,
-                Quality: 0,
-            },
-            Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
-            Flags: D3D12_RESOURCE_FLAGS::default(),
+        let heap_type = match location {
+            BufferLocation::Cpu => D3D12_HEAP_TYPE_UPLOAD,
+            BufferLocation::GpuUpload => D3D12_HEAP_TYPE_GPU_UPLOAD,
+            BufferLocation::GpuDefault => D3D12_HEAP_TYPE_DEFAULT,
         };
-
-        let mut resource_option = None;
-        unsafe {
-            lib.device.CreateCommittedResource(
-                &heap_properties,
-                D3D12_HEAP_FLAGS::default(),
-                &resource_desc,
-                D3D12_RESOURCE_STATE_COMMON,
-                None,
-                &mut resource_option,
-            )
-        }?;
-
-        let resource: ID3D12Resource =
-            resource_option.ok_or("Failed to create resource for vector constant buffer")?;
+        let resource = create_buffer(lib, heap_type, size, flags, D3D12_RESOURCE_STATE_COMMON)?;
 
         if let Some(name) = name {
             unsafe { resource.SetName(&HSTRING::from(name)) }?;
@@ -115,6 +135,34 @@ impl<T> VectorConstantBuffer<T> {
         self.current_len == 0
     }
 
+    /// The number of items that fit into the buffer before it has to be reallocated
+    pub fn capacity(&self) -> usize {
+        self.max_size
+    }
+
+    /// Sets the length to zero while keeping the allocation for future uploads
+    pub fn clear(&mut self) {
+        self.current_len = 0;
+    }
+
+    /// Reallocates the buffer to fit exactly its current items, e.g. after a large transient upload.
+    /// Returns the previous buffer, which frames in flight may still use, so it has to be kept alive
+    /// until they finish, just like the one returned by `upload_deferred_delete`.
+    /// Returns `None` if the buffer already fits. Empty buffers keep space for one item.
+    pub fn shrink_to_fit(&mut self) -> Result<Option<ID3D12Resource>, GimsError> {
+        let size = self.current_len.max(1);
+        if self.max_size <= size {
+            return Ok(None);
+        }
+
+        let new_resource =
+            Self::create_resource(&self.lib, size, self.location, self.flags, &self.name)?;
+        self.copy_items_to(&new_resource, self.current_len)?;
+        self.max_size = size;
+
+        Ok(Some(std::mem::replace(&mut self.resource, new_resource)))
+    }
+
     /// Creates a `D3D12_VERTEX_BUFFER_VIEW` for the internal `ID3D12Resource`, spanning the buffer's entire current length.
     /// Stride is set to the size of the buffer's type.
     pub fn vertex_buffer_view(&self) -> D3D12_VERTEX_BUFFER_VIEW {
@@ -134,11 +182,148 @@ impl<T> VectorConstantBuffer<T> {
             Format: format,
         }
     }
+
+    /// Creates a `D3D12_CONSTANT_BUFFER_VIEW_DESC` spanning the buffer's entire current length,
+    /// with the size rounded up to the 256 byte granularity of constant buffer views
+    pub fn constant_buffer_view_desc(&self) -> D3D12_CONSTANT_BUFFER_VIEW_DESC {
+        D3D12_CONSTANT_BUFFER_VIEW_DESC {
+            BufferLocation: unsafe { self.resource.GetGPUVirtualAddress() },
+            SizeInBytes: (self.current_len * size_of::<T>())
+                .next_multiple_of(CONSTANT_BUFFER_ALIGNMENT) as u32,
+        }
+    }
+
+    /// The GPU address of the item at `index`, e.g. for `SetGraphicsRootConstantBufferView` with one item per draw.
+    /// Panics if the size of `T` is not a multiple of 256 bytes, since constant buffers have to be aligned to it.
+    /// Pad `T` with `#[repr(C, align(256))]` to use it this way.
+    pub fn gpu_address_of(&self, index: usize) -> u64 {
+        assert!(
+            size_of::<T>() % CONSTANT_BUFFER_ALIGNMENT == 0,
+            "Item size of {} bytes is not a multiple of the constant buffer alignment",
+            size_of::<T>()
+        );
+        assert!(
+            index < self.max_size,
+            "Index {} exceeds the buffer capacity of {}",
+            index,
+            self.max_size
+        );
+        let address = unsafe { self.resource.GetGPUVirtualAddress() };
+        address + (index * size_of::<T>()) as u64
+    }
+
+    /// Creates a `D3D12_UNORDERED_ACCESS_VIEW_DESC` of a structured buffer spanning the buffer's entire current length,
+    /// with the size of `T` as stride
+    pub fn unordered_access_view_desc(&self) -> D3D12_UNORDERED_ACCESS_VIEW_DESC {
+        D3D12_UNORDERED_ACCESS_VIEW_DESC {
+            Format: DXGI_FORMAT_UNKNOWN,
+            ViewDimension: D3D12_UAV_DIMENSION_BUFFER,
+            Anonymous: D3D12_UNORDERED_ACCESS_VIEW_DESC_0 {
+                Buffer: D3D12_BUFFER_UAV {
+                    FirstElement: 0,
+                    NumElements: self.current_len as u32,
+                    StructureByteStride: size_of::<T>() as u32,
+                    CounterOffsetInBytes: 0,
+                    Flags: D3D12_BUFFER_UAV_FLAG_NONE,
+                },
+            },
+        }
+    }
+
+    /// Writes the view of `unordered_access_view_desc` to `handle`, e.g. for a
+    /// `RWStructuredBuffer<T>` written by a compute shader. The buffer has to be created by `with_flags` with
+    /// `D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS`. Shaders access the view through a descriptor table,
+    /// so `handle` has to be in a shader visible `D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV` heap, or in a
+    /// non shader visible one from which it is copied with `CopyDescriptorsSimple`.
+    pub fn create_unordered_access_view(&self, handle: D3D12_CPU_DESCRIPTOR_HANDLE) {
+        let desc = self.unordered_access_view_desc();
+        unsafe {
+            self.lib.device.CreateUnorderedAccessView(
+                &self.resource,
+                None::<&ID3D12Resource>,
+                Some(&desc),
+                handle,
+            )
+        };
+    }
+
+    /// Creates a `D3D12_VERTEX_BUFFER_VIEW` spanning `count` items starting at item `start`,
+    /// e.g. for one mesh within a batched buffer. Fails if the range exceeds the current length.
+    pub fn vertex_buffer_view_range(
+        &self,
+        start: usize,
+        count: usize,
+    ) -> Result<D3D12_VERTEX_BUFFER_VIEW, GimsError> {
+        self.check_range(start, count)?;
+
+        Ok(D3D12_VERTEX_BUFFER_VIEW {
+            BufferLocation: unsafe { self.resource.GetGPUVirtualAddress() }
+                + (start * size_of::<T>()) as u64,
+            SizeInBytes: (count * size_of::<T>()) as u32,
+            StrideInBytes: size_of::<T>() as u32,
+        })
+    }
+
+    /// Creates a `D3D12_INDEX_BUFFER_VIEW` with the specified format spanning `count` items starting at item `start`.
+    /// Fails if the range exceeds the current length.
+    pub fn index_buffer_view_range(
+        &self,
+        start: usize,
+        count: usize,
+        format: DXGI_FORMAT,
+    ) -> Result<D3D12_INDEX_BUFFER_VIEW, GimsError> {
+        self.check_range(start, count)?;
+
+        Ok(D3D12_INDEX_BUFFER_VIEW {
+            BufferLocation: unsafe { self.resource.GetGPUVirtualAddress() }
+                + (start * size_of::<T>()) as u64,
+            SizeInBytes: (count * size_of::<T>()) as u32,
+            Format: format,
+        })
+    }
+
+    /// Copies the first `count` items into a newly allocated buffer
+    fn copy_items_to(&self, resource: &ID3D12Resource, count: usize) -> Result<(), GimsError> {
+        if let BufferLocation::GpuDefault = self.location {
+            let size = (count * size_of::<T>()) as u64;
+            return copy_buffer(&self.lib, resource, 0, &self.resource, 0, size);
+        }
+
+        unsafe {
+            let mut source = std::ptr::null_mut();
+            let mut destination = std::ptr::null_mut();
+            self.resource.Map(0, None, Some(&mut source))?;
+            resource.Map(0, None, Some(&mut destination))?;
+            std::ptr::copy_nonoverlapping(
+                source as *const u8,
+                destination as *mut u8,
+                count * size_of::<T>(),
+            );
+            resource.Unmap(0, None);
+            self.resource.Unmap(0, Some(&D3D12_RANGE::default()));
+        }
+        Ok(())
+    }
+
+    fn check_range(&self, start: usize, count: usize) -> Result<(), GimsError> {
+        if start
+            .checked_add(count)
+            .is_none_or(|end| end > self.current_len)
+        {
+            return Err(format!(
+                "Range of {} items starting at {} exceeds the buffer length of {}",
+                count, start, self.current_len
+            )
+            .into());
+        }
+
+        Ok(())
+    }
 }
 
 impl<T: Clone> VectorConstantBuffer<T> {
     /// Copy new data into the buffer
-    pub fn upload(&mut self, data: &[T]) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn upload(&mut self, data: &[T]) -> Result<(), GimsError> {
         self.upload_deferred_delete(data)?;
         Ok(())
     }
@@ -148,26 +333,147 @@ impl<T: Clone> VectorConstantBuffer<T> {
     pub fn upload_deferred_delete(
         &mut self,
         data: &[T],
-    ) -> Result<Option<ID3D12Resource>, Box<dyn std::error::Error>> {
+    ) -> Result<Option<ID3D12Resource>, GimsError> {
         let deleted_resource = if self.max_size < data.len() {
-            let new_resource =
-                Self::create_resource(&self.lib, data.len(), self.location, &self.name)?;
+            let new_resource = Self::create_resource(
+                &self.lib,
+                data.len(),
+                self.location,
+                self.flags,
+                &self.name,
+            )?;
+            self.max_size = data.len();
             Some(std::mem::replace(&mut self.resource, new_resource))
         } else {
             None
         };
         self.current_len = data.len();
+        self.write_items(0, data)?;
 
-        unsafe {
-            let mut pointer = std::ptr::null_mut();
-            self.resource.Map(0, None, Some(&mut pointer))?;
-            let slice = std::slice::from_raw_parts_mut(pointer as *mut T, data.len());
-            slice.clone_from_slice(data);
-            self.resource.Unmap(0, None);
+        Ok(deleted_resource)
+    }
+
+    /// Copy `data` into the buffer starting at item `offset`, keeping the items before and after it.
+    /// The length grows to the end of the written range if it lies past the current length.
+    /// `offset` must not exceed the current length, since the items in between would be undefined.
+    pub fn upload_at(&mut self, offset: usize, data: &[T]) -> Result<(), GimsError> {
+        self.upload_at_deferred_delete(offset, data)?;
+        Ok(())
+    }
+
+    /// Like `upload_at`, while returning the potentially discarded previous buffer.
+    /// When the buffer grows, its current items are copied into the new one.
+    pub fn upload_at_deferred_delete(
+        &mut self,
+        offset: usize,
+        data: &[T],
+    ) -> Result<Option<ID3D12Resource>, GimsError> {
+        if offset > self.current_len {
+            return Err(format!(
+                "Upload offset {} exceeds the buffer length of {}",
+                offset, self.current_len
+            )
+            .into());
+        }
+        let end = offset
+            .checked_add(data.len())
+            .ok_or("Vector constant buffer size overflows")?;
+
+        let deleted_resource = if self.max_size < end {
+            let new_resource =
+                Self::create_resource(&self.lib, end, self.location, self.flags, &self.name)?;
+            self.copy_items_to(&new_resource, offset)?;
+            self.max_size = end;
+            Some(std::mem::replace(&mut self.resource, new_resource))
+        } else {
+            None
+        };
+        if end > self.max_size {
+            return Err("Upload range exceeds the buffer capacity".into());
         }
 
+        self.write_items(offset, data)?;
+        self.current_len = self.current_len.max(end);
+
         Ok(deleted_resource)
     }
+
+    /// Writes `data` starting at item `offset`, which has to fit into the current buffer
+    fn write_items(&mut self, offset: usize, data: &[T]) -> Result<(), GimsError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        if !matches!(self.location, BufferLocation::GpuDefault) {
+            return write_mapped(&self.resource, offset, data);
+        }
+
+        let size = (data.len() * size_of::<T>()) as u64;
+        let staging = match &self.staging {
+            Some(staging) if unsafe { staging.GetDesc() }.Width >= size => staging.clone(),
+            _ => {
+                let staging = create_buffer(
+                    &self.lib,
+                    D3D12_HEAP_TYPE_UPLOAD,
+                    size,
+                    D3D12_RESOURCE_FLAGS::default(),
+                    D3D12_RESOURCE_STATE_GENERIC_READ,
+                )?;
+                self.staging = Some(staging.clone());
+                staging
+            }
+        };
+        write_mapped(&staging, 0, data)?;
+        copy_buffer(
+            &self.lib,
+            &self.resource,
+            (offset * size_of::<T>()) as u64,
+            &staging,
+            0,
+            size,
+        )
+    }
+
+    /// Copies the current items out of the buffer, e.g. to debug uploads or to read results written by compute shaders.
+    /// Mapping does not synchronize with the GPU, so wait for the work that writes the buffer first,
+    /// e.g. with `GPULib::wait_idle`. The mappable locations are upload heaps, which are write combined,
+    /// and `GpuDefault` buffers are copied to a readback buffer first, so downloading is slow
+    /// and only meant for small buffers or debugging.
+    pub fn download(&self) -> Result<Vec<T>, GimsError> {
+        if self.current_len == 0 {
+            return Ok(Vec::new());
+        }
+
+        let size = self.current_len * size_of::<T>();
+        let source = if let BufferLocation::GpuDefault = self.location {
+            let readback = create_buffer(
+                &self.lib,
+                D3D12_HEAP_TYPE_READBACK,
+                size as u64,
+                D3D12_RESOURCE_FLAGS::default(),
+                D3D12_RESOURCE_STATE_COPY_DEST,
+            )?;
+            copy_buffer(&self.lib, &readback, 0, &self.resource, 0, size as u64)?;
+            readback
+        } else {
+            self.resource.clone()
+        };
+
+        unsafe {
+            let mut pointer = std::ptr::null_mut();
+            source.Map(
+                0,
+                Some(&D3D12_RANGE {
+                    Begin: 0,
+                    End: size,
+                }),
+                Some(&mut pointer),
+            )?;
+            let data = std::slice::from_raw_parts(pointer as *const T, self.current_len).to_vec();
+            // Nothing was written
+            source.Unmap(0, Some(&D3D12_RANGE::default()));
+            Ok(data)
+        }
+    }
 }
 
 impl<T> Deref for VectorConstantBuffer<T> {
@@ -176,3 +482,95 @@ impl<T> Deref for VectorConstantBuffer<T> {
         &self.resource
     }
 }
+
+fn create_buffer(
+    lib: &GPULib,
+    heap_type: D3D12_HEAP_TYPE,
+    size: u64,
+    flags: D3D12_RESOURCE_FLAGS,
+    state: D3D12_RESOURCE_STATES,
+) -> Result<ID3D12Resource, GimsError> {
+    let heap_properties = D3D12_HEAP_PROPERTIES {
+        Type: heap_type,
+        ..Default::default()
+    };
+    let resource_desc = D3D12_RESOURCE_DESC {
+        Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+        Width: size,
+        Height: 1,
+        DepthOrArraySize: 1,
+        Alignment: 0,
+        MipLevels: 1,
+        Format: DXGI_FORMAT_UNKNOWN,
+        SampleDesc: windows::Win32::Graphics::Dxgi::Common::DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+        Flags: flags,
+    };
+
+    let mut resource_option = None;
+    unsafe {
+        lib.device.CreateCommittedResource(
+            &heap_properties,
+            D3D12_HEAP_FLAGS::default(),
+            &resource_desc,
+            state,
+            None,
+            &mut resource_option,
+        )
+    }
+    .map_err(GimsError::resource_creation)?;
+
+    Ok(resource_option.ok_or("Failed to create resource for vector constant buffer")?)
+}
+
+/// Clones `data` into a mappable buffer, starting at item `offset`
+fn write_mapped<T: Clone>(
+    resource: &ID3D12Resource,
+    offset: usize,
+    data: &[T],
+) -> Result<(), GimsError> {
+    unsafe {
+        let mut pointer = std::ptr::null_mut();
+        resource.Map(0, Some(&D3D12_RANGE::default()), Some(&mut pointer))?;
+        let slice = std::slice::from_raw_parts_mut((pointer as *mut T).add(offset), data.len());
+        slice.clone_from_slice(data);
+        resource.Unmap(
+            0,
+            Some(&D3D12_RANGE {
+                Begin: offset * size_of::<T>(),
+                End: (offset + data.len()) * size_of::<T>(),
+            }),
+        );
+    }
+    Ok(())
+}
+
+/// Copies `size` bytes between buffers on the copy queue and waits for the copy to finish.
+/// Buffers in the common state are promoted to the copy states implicitly and decay back afterwards,
+/// so no barriers are needed.
+fn copy_buffer(
+    lib: &GPULib,
+    destination: &ID3D12Resource,
+    destination_offset: u64,
+    source: &ID3D12Resource,
+    source_offset: u64,
+    size: u64,
+) -> Result<(), GimsError> {
+    // The allocator backs the recorded commands, so it has to live until they have executed
+    let (_command_allocator, command_list) =
+        lib.create_command_list(D3D12_COMMAND_LIST_TYPE_COPY)?;
+    let fence: ID3D12Fence = unsafe { lib.device.CreateFence(0, D3D12_FENCE_FLAG_NONE) }?;
+    unsafe {
+        command_list.CopyBufferRegion(destination, destination_offset, source, source_offset, size);
+        command_list.Close()?;
+        lib.copy_queue
+            .ExecuteCommandLists(&[Some(command_list.cast()?)]);
+        lib.copy_queue.Signal(&fence, 1)?;
+        // A null event handle blocks until the fence reaches the value
+        fence.SetEventOnCompletion(1, HANDLE::default())?;
+    }
+    Ok(())
+}
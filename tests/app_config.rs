@@ -0,0 +1,35 @@
+use gimslib_rs::{App, AppConfig, FrameResources, run_app_with_loading_screen};
+
+struct EmptyApp;
+
+impl App for EmptyApp {
+    fn draw(
+        &mut self,
+        _frame_resources: &FrameResources,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
+
+#[test]
+fn frame_count_is_bounded() {
+    let config = |frame_count| AppConfig {
+        frame_count,
+        ..Default::default()
+    };
+    assert!(config(0).validate().is_err());
+    assert!(config(1).validate().is_err());
+    assert!(config(2).validate().is_ok());
+    assert!(config(16).validate().is_ok());
+    assert!(config(17).validate().is_err());
+}
+
+#[test]
+fn loading_screen_requires_egui() {
+    // Rejected before any window is created
+    let config = AppConfig {
+        enable_egui: false,
+        ..Default::default()
+    };
+    assert!(run_app_with_loading_screen(config, |_, _| EmptyApp).is_err());
+}
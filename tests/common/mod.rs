@@ -0,0 +1,15 @@
+use std::sync::Arc;
+
+use gimslib_rs::gpulib::GPULib;
+
+/// Creates the library, falling back to WARP on machines without a hardware adapter.
+/// Returns `None` if not even WARP is available, e.g. outside of Windows.
+pub fn create_lib() -> Option<Arc<GPULib>> {
+    match GPULib::new_with_preference(true, true) {
+        Ok(lib) => Some(Arc::new(lib)),
+        Err(error) => {
+            eprintln!("Skipping test, no Direct3D 12 device available: {error}");
+            None
+        }
+    }
+}
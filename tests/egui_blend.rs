@@ -0,0 +1,293 @@
+mod common;
+
+use std::mem::ManuallyDrop;
+
+use egui::ColorImage;
+use gimslib_rs::{
+    barrier,
+    error::GimsError,
+    gpulib::{GPULib, compile_shader},
+    pipeline::{GraphicsPipelineBuilder, draw_fullscreen, fullscreen_triangle_vertex_shader},
+    root_signature::RootSignatureBuilder,
+    texture::{self, TextureDesc},
+};
+use windows::{
+    Win32::{
+        Foundation::HANDLE,
+        Graphics::{
+            Direct3D12::*,
+            Dxgi::Common::{DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_UNKNOWN, DXGI_SAMPLE_DESC},
+        },
+    },
+    core::Interface,
+};
+
+use common::create_lib;
+
+/// Outputs the only texel of the texture, like the egui pixel shader with a white vertex color
+const PIXEL_SHADER: &str = "
+Texture2D<float4> ui_texture : register(t0);
+
+float4 main() : SV_Target
+{
+    return ui_texture.Load(int3(0, 0, 0));
+}
+";
+
+/// The blend state of the egui pipeline, which expects premultiplied alpha
+fn egui_blend_state() -> D3D12_BLEND_DESC {
+    let mut blend_state = D3D12_BLEND_DESC::default();
+    blend_state.RenderTarget[0] = D3D12_RENDER_TARGET_BLEND_DESC {
+        BlendEnable: true.into(),
+        SrcBlend: D3D12_BLEND_ONE,
+        DestBlend: D3D12_BLEND_INV_SRC_ALPHA,
+        BlendOp: D3D12_BLEND_OP_ADD,
+        SrcBlendAlpha: D3D12_BLEND_ONE,
+        DestBlendAlpha: D3D12_BLEND_INV_SRC_ALPHA,
+        BlendOpAlpha: D3D12_BLEND_OP_ADD,
+        RenderTargetWriteMask: D3D12_COLOR_WRITE_ENABLE_ALL.0 as u8,
+        ..Default::default()
+    };
+    blend_state
+}
+
+fn create_readback_buffer(lib: &GPULib, size: u64) -> ID3D12Resource {
+    let mut resource_option = None;
+    unsafe {
+        lib.device.CreateCommittedResource(
+            &D3D12_HEAP_PROPERTIES {
+                Type: D3D12_HEAP_TYPE_READBACK,
+                ..Default::default()
+            },
+            D3D12_HEAP_FLAGS::default(),
+            &D3D12_RESOURCE_DESC {
+                Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+                Width: size,
+                Height: 1,
+                DepthOrArraySize: 1,
+                Alignment: 0,
+                MipLevels: 1,
+                Format: DXGI_FORMAT_UNKNOWN,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+                Flags: D3D12_RESOURCE_FLAGS::default(),
+            },
+            D3D12_RESOURCE_STATE_COPY_DEST,
+            None,
+            &mut resource_option,
+        )
+    }
+    .unwrap();
+    resource_option.unwrap()
+}
+
+/// Draws the single texel of `image` over a 1x1 render target cleared to `background` and reads back the result
+fn blend_over(lib: &GPULib, image: &ColorImage, background: [f32; 4]) -> [u8; 4] {
+    let uploaded =
+        texture::upload_texture_2d(lib, 1, 1, DXGI_FORMAT_R8G8B8A8_UNORM, image.as_raw()).unwrap();
+
+    let root_signature = RootSignatureBuilder::new()
+        .add_descriptor_table(
+            &[D3D12_DESCRIPTOR_RANGE1 {
+                RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+                NumDescriptors: 1,
+                BaseShaderRegister: 0,
+                RegisterSpace: 0,
+                Flags: D3D12_DESCRIPTOR_RANGE_FLAG_NONE,
+                OffsetInDescriptorsFromTableStart: 0,
+            }],
+            D3D12_SHADER_VISIBILITY_PIXEL,
+        )
+        .build(lib)
+        .unwrap();
+    let vertex_shader = fullscreen_triangle_vertex_shader().unwrap();
+    let pixel_shader = compile_shader(PIXEL_SHADER, "main", "ps_6_5").unwrap();
+    let pipeline = GraphicsPipelineBuilder::new(&root_signature, &vertex_shader)
+        .pixel_shader(&pixel_shader)
+        .blend_state(egui_blend_state())
+        .build(lib)
+        .unwrap();
+
+    let shader_heap: ID3D12DescriptorHeap = unsafe {
+        lib.device
+            .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                Type: D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+                NumDescriptors: 1,
+                Flags: D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+                NodeMask: 0,
+            })
+    }
+    .unwrap();
+    unsafe {
+        lib.device.CopyDescriptorsSimple(
+            1,
+            shader_heap.GetCPUDescriptorHandleForHeapStart(),
+            uploaded.descriptor,
+            D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+        )
+    };
+
+    let target_desc = TextureDesc::render_target(1, 1, DXGI_FORMAT_R8G8B8A8_UNORM, background);
+    let render_target =
+        texture::create_texture(lib, &target_desc, D3D12_HEAP_TYPE_DEFAULT, None).unwrap();
+    let rtv_heap: ID3D12DescriptorHeap = unsafe {
+        lib.device
+            .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                Type: D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
+                NumDescriptors: 1,
+                Flags: D3D12_DESCRIPTOR_HEAP_FLAG_NONE,
+                NodeMask: 0,
+            })
+    }
+    .unwrap();
+    let rtv = unsafe { rtv_heap.GetCPUDescriptorHandleForHeapStart() };
+    unsafe { lib.device.CreateRenderTargetView(&render_target, None, rtv) };
+
+    let footprint = target_desc.copyable_footprint(lib, 0).unwrap();
+    let readback = create_readback_buffer(lib, footprint.total_bytes);
+
+    let (_command_allocator, command_list) = lib
+        .create_command_list(D3D12_COMMAND_LIST_TYPE_DIRECT)
+        .unwrap();
+    unsafe {
+        barrier::record(
+            &command_list,
+            &[barrier::transition(
+                &render_target,
+                D3D12_RESOURCE_STATE_COMMON,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+            )],
+        );
+        command_list.ClearRenderTargetView(rtv, &background, None);
+        command_list.OMSetRenderTargets(1, Some(&rtv), false, None);
+        command_list.RSSetViewports(&[D3D12_VIEWPORT {
+            Width: 1.0,
+            Height: 1.0,
+            MaxDepth: 1.0,
+            ..Default::default()
+        }]);
+        command_list.RSSetScissorRects(&[windows::Win32::Foundation::RECT {
+            right: 1,
+            bottom: 1,
+            ..Default::default()
+        }]);
+        command_list.SetGraphicsRootSignature(&root_signature);
+        command_list.SetPipelineState(&pipeline);
+        command_list.SetDescriptorHeaps(&[Some(shader_heap.clone())]);
+        command_list
+            .SetGraphicsRootDescriptorTable(0, shader_heap.GetGPUDescriptorHandleForHeapStart());
+    }
+    draw_fullscreen(&command_list);
+
+    let mut destination = D3D12_TEXTURE_COPY_LOCATION {
+        pResource: ManuallyDrop::new(Some(readback.clone())),
+        Type: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
+        Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+            PlacedFootprint: footprint.layout,
+        },
+    };
+    let mut source = D3D12_TEXTURE_COPY_LOCATION {
+        pResource: ManuallyDrop::new(Some(render_target.clone())),
+        Type: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+        Anonymous: D3D12_TEXTURE_COPY_LOCATION_0 {
+            SubresourceIndex: 0,
+        },
+    };
+    unsafe {
+        barrier::record(
+            &command_list,
+            &[barrier::transition(
+                &render_target,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+                D3D12_RESOURCE_STATE_COPY_SOURCE,
+            )],
+        );
+        command_list.CopyTextureRegion(&destination, 0, 0, 0, &source, None);
+        ManuallyDrop::drop(&mut destination.pResource);
+        ManuallyDrop::drop(&mut source.pResource);
+    }
+
+    let fence: ID3D12Fence = unsafe { lib.device.CreateFence(0, D3D12_FENCE_FLAG_NONE) }.unwrap();
+    unsafe {
+        command_list.Close().unwrap();
+        lib.queue
+            .ExecuteCommandLists(&[Some(command_list.cast().unwrap())]);
+        lib.queue.Signal(&fence, 1).unwrap();
+        // A null event handle blocks until the fence reaches the value
+        fence.SetEventOnCompletion(1, HANDLE::default()).unwrap();
+    }
+
+    unsafe {
+        let mut pointer = std::ptr::null_mut();
+        readback
+            .Map(
+                0,
+                Some(&D3D12_RANGE {
+                    Begin: 0,
+                    End: footprint.total_bytes as usize,
+                }),
+                Some(&mut pointer),
+            )
+            .unwrap();
+        let texel = ((pointer as *const u8).add(footprint.layout.Offset as usize)
+            as *const [u8; 4])
+            .read_unaligned();
+        readback.Unmap(0, Some(&D3D12_RANGE::default()));
+        texel
+    }
+}
+
+fn assert_close(actual: [u8; 4], expected: [u8; 4]) {
+    assert!(
+        actual
+            .iter()
+            .zip(expected)
+            .all(|(&actual, expected)| actual.abs_diff(expected) <= 1),
+        "blended {actual:?}, expected {expected:?}"
+    );
+}
+
+#[test]
+fn unmultiplied_image_blends_without_dark_fringes() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    if let Err(GimsError::ShaderCompilerNotFound(library)) = fullscreen_triangle_vertex_shader() {
+        eprintln!("Skipping test, {library} not found");
+        return;
+    }
+
+    // egui premultiplies unmultiplied images when they are created, which is what gets uploaded
+    let image = ColorImage::from_rgba_unmultiplied([1, 1], &[255, 255, 255, 128]);
+    let [r, g, b, a] = image.pixels[0].to_array();
+    assert!(r <= a && g <= a && b <= a);
+
+    // Over black only the premultiplied texel remains. Multiplying by alpha once more would halve it.
+    let blended = blend_over(&lib, &image, [0.0, 0.0, 0.0, 1.0]);
+    assert_close(blended, [r, g, b, 255]);
+
+    // Over white a white texel stays white at any coverage, while a dark fringe would show as gray
+    let blended = blend_over(&lib, &image, [1.0, 1.0, 1.0, 1.0]);
+    let inverse_alpha = 255 - a as u32;
+    let over_white = |channel: u8| (channel as u32 + inverse_alpha).min(255) as u8;
+    assert_close(blended, [over_white(r), over_white(g), over_white(b), 255]);
+    assert_close(blended, [255, 255, 255, 255]);
+}
+
+#[test]
+fn transparent_image_keeps_background() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    if let Err(GimsError::ShaderCompilerNotFound(library)) = fullscreen_triangle_vertex_shader() {
+        eprintln!("Skipping test, {library} not found");
+        return;
+    }
+
+    let image = ColorImage::from_rgba_unmultiplied([1, 1], &[255, 0, 0, 0]);
+    let blended = blend_over(&lib, &image, [0.0, 0.0, 1.0, 1.0]);
+    assert_close(blended, [0, 0, 255, 255]);
+}
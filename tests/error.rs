@@ -0,0 +1,25 @@
+use gimslib_rs::error::GimsError;
+
+#[test]
+fn gims_error_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+    assert_send_sync::<GimsError>();
+
+    // Can be passed on as the error type used across threads
+    let boxed: Box<dyn std::error::Error + Send + Sync> = GimsError::OutOfMemory.into();
+    assert!(matches!(GimsError::from(boxed), GimsError::Other(_)));
+}
+
+#[test]
+fn app_errors_keep_their_variant() {
+    let boxed: Box<dyn std::error::Error> = Box::new(GimsError::NoSuitableAdapter);
+    assert!(matches!(
+        GimsError::from(boxed),
+        GimsError::NoSuitableAdapter
+    ));
+
+    let boxed: Box<dyn std::error::Error> = "Failed to load the scene".into();
+    let error = GimsError::from(boxed);
+    assert!(matches!(error, GimsError::Message(_)));
+    assert_eq!(error.to_string(), "Failed to load the scene");
+}
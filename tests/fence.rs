@@ -0,0 +1,78 @@
+mod common;
+
+use std::time::Duration;
+
+use gimslib_rs::fence::Fence;
+use windows::{Win32::Graphics::Direct3D12::*, core::Interface};
+
+use common::create_lib;
+
+#[test]
+fn allocator_reset_waits_for_its_commands() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    let mut fence = Fence::new(&lib).unwrap();
+    let (command_allocator, command_list) = lib
+        .create_command_list(D3D12_COMMAND_LIST_TYPE_DIRECT)
+        .unwrap();
+
+    // The queue waits for a value only the CPU signals, so the commands stay in flight until then
+    let gate: ID3D12Fence = unsafe { lib.device.CreateFence(0, D3D12_FENCE_FLAG_NONE) }.unwrap();
+    unsafe {
+        command_list.Close().unwrap();
+        lib.queue.Wait(&gate, 1).unwrap();
+        lib.queue
+            .ExecuteCommandLists(&[Some(command_list.cast().unwrap())]);
+    }
+    let value = fence.signal(&lib.queue).unwrap();
+
+    assert!(
+        fence
+            .reset_command_allocator(&command_allocator, value)
+            .is_err()
+    );
+
+    unsafe { gate.Signal(1) }.unwrap();
+    fence.wait_for(value).unwrap();
+    fence
+        .reset_command_allocator(&command_allocator, value)
+        .unwrap();
+    unsafe { command_list.Reset(&command_allocator, None) }.unwrap();
+}
+
+#[test]
+fn timed_out_wait_does_not_end_later_waits_early() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    let mut fence = Fence::new(&lib).unwrap();
+
+    // Each value is only reached after the CPU opened the gate for it
+    let gate: ID3D12Fence = unsafe { lib.device.CreateFence(0, D3D12_FENCE_FLAG_NONE) }.unwrap();
+    unsafe { lib.queue.Wait(&gate, 1) }.unwrap();
+    let first = fence.signal(&lib.queue).unwrap();
+    unsafe { lib.queue.Wait(&gate, 2) }.unwrap();
+    let second = fence.signal(&lib.queue).unwrap();
+
+    assert!(
+        !fence
+            .wait_for_timeout(first, Duration::from_millis(10))
+            .unwrap()
+    );
+
+    // Reaching the first value sets the event armed by the timed out wait
+    unsafe { gate.Signal(1) }.unwrap();
+    while !fence.is_complete(first) {
+        std::thread::yield_now();
+    }
+    assert!(
+        !fence
+            .wait_for_timeout(second, Duration::from_millis(50))
+            .unwrap()
+    );
+
+    unsafe { gate.Signal(2) }.unwrap();
+    fence.wait_for(second).unwrap();
+    assert!(fence.is_complete(second));
+}
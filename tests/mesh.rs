@@ -0,0 +1,127 @@
+use gimslib_rs::mesh::parse_obj;
+
+#[test]
+fn negative_indices_count_back_from_the_last_element() {
+    let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vn 0 0 1
+f -3/-1/-1 -2/-1/-1 -1/-1/-1
+";
+    let (vertices, indices) = parse_obj(source).unwrap();
+    assert_eq!(indices, [0, 1, 2]);
+    assert_eq!(vertices[0].position, [0.0, 0.0, 0.0]);
+    assert_eq!(vertices[1].position, [1.0, 0.0, 0.0]);
+    assert_eq!(vertices[2].position, [0.0, 1.0, 0.0]);
+    // The V coordinate is flipped to the top left origin of Direct3D
+    assert!(vertices.iter().all(|vertex| vertex.uv == [0.0, 1.0]));
+    assert!(
+        vertices
+            .iter()
+            .all(|vertex| vertex.normal == [0.0, 0.0, 1.0])
+    );
+}
+
+#[test]
+fn polygons_are_split_into_triangle_fans() {
+    let source = "
+v 0 0 0
+v 1 0 0
+v 2 1 0
+v 1 2 0
+v 0 1 0
+f 1 2 3 4 5
+";
+    let (vertices, indices) = parse_obj(source).unwrap();
+    assert_eq!(vertices.len(), 5);
+    assert_eq!(indices, [0, 1, 2, 0, 2, 3, 0, 3, 4]);
+}
+
+#[test]
+fn shared_vertices_are_reused() {
+    let source = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vn 0 0 1
+f 1//1 2//1 3//1
+f 1//1 3//1 4//1
+";
+    let (vertices, indices) = parse_obj(source).unwrap();
+    assert_eq!(vertices.len(), 4);
+    assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+}
+
+#[test]
+fn missing_normals_are_generated_from_faces() {
+    // Two triangles of a quad folded by 90 degrees along the X axis
+    let source = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 0 -1
+v 1 0 -1
+f 1 2 3
+f 1 2 5 4
+";
+    let (vertices, indices) = parse_obj(source).unwrap();
+    assert_eq!(indices.len(), 9);
+
+    let approx_eq = |a: [f32; 3], b: [f32; 3]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5);
+    // Only in the vertical triangle
+    assert!(approx_eq(vertices[2].normal, [0.0, 0.0, 1.0]));
+    // Only in the horizontal quad
+    assert!(approx_eq(vertices[3].normal, [0.0, 1.0, 0.0]));
+    // Shared by both, weighted by area
+    let shared = vertices[0].normal;
+    assert!(approx_eq(
+        shared,
+        [0.0, 2.0 / 5.0_f32.sqrt(), 1.0 / 5.0_f32.sqrt()]
+    ));
+}
+
+#[test]
+fn explicit_normals_are_kept() {
+    let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vn 1 0 0
+f 1//1 2 3
+";
+    let (vertices, _) = parse_obj(source).unwrap();
+    assert_eq!(vertices[0].normal, [1.0, 0.0, 0.0]);
+    assert_eq!(vertices[1].normal, [0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn out_of_range_indices_are_rejected() {
+    let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 4
+";
+    let error = parse_obj(source).unwrap_err().to_string();
+    assert!(error.contains("Line 5"), "{error}");
+    assert!(error.contains("out of range"), "{error}");
+
+    // Zero is not a valid index, negative ones may not reach before the first element
+    assert!(parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2").is_err());
+    assert!(parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -4 -2 -1").is_err());
+    // Texture coordinates and normals are checked as well
+    assert!(parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/1 3/1").is_err());
+    assert!(parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1//1 2//1 3//1").is_err());
+}
+
+#[test]
+fn faces_with_less_than_three_vertices_are_rejected() {
+    let error = parse_obj("v 0 0 0\nv 1 0 0\nf 1 2")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Line 3"), "{error}");
+    assert!(error.contains("at least 3"), "{error}");
+}
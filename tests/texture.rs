@@ -0,0 +1,125 @@
+mod common;
+
+use gimslib_rs::texture::{
+    CopyableFootprint, TextureDesc, TextureDimension, copy_to_footprint,
+    create_slice_render_target_view, create_texture,
+};
+use windows::Win32::Graphics::{Direct3D12::*, Dxgi::Common::DXGI_FORMAT_R8G8B8A8_UNORM};
+
+use common::create_lib;
+
+/// Footprint of a tightly packed RGBA8 volume at `offset`, with rows padded to `row_pitch` bytes
+fn footprint(
+    width: u32,
+    height: u32,
+    depth: u32,
+    row_pitch: u32,
+    offset: u64,
+) -> CopyableFootprint {
+    let row_bytes = width as u64 * 4;
+    let total_bytes = offset + row_pitch as u64 * (height * depth - 1) as u64 + row_bytes;
+    CopyableFootprint {
+        layout: D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
+            Offset: offset,
+            Footprint: D3D12_SUBRESOURCE_FOOTPRINT {
+                Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                Width: width,
+                Height: height,
+                Depth: depth,
+                RowPitch: row_pitch,
+            },
+        },
+        row_count: height,
+        row_bytes,
+        total_bytes,
+    }
+}
+
+#[test]
+fn copy_to_footprint_pads_every_row_of_every_slice() {
+    let footprint = footprint(2, 3, 2, 16, 0);
+    let source: Vec<u8> = (1..=2 * 3 * 2 * 4).collect();
+    let mut destination = vec![0; footprint.total_bytes as usize];
+    copy_to_footprint(&mut destination, &source, &footprint).unwrap();
+
+    // Slices start `RowPitch * Height` bytes apart, rows `RowPitch` bytes apart
+    for slice in 0..2 {
+        for row in 0..3 {
+            let source_start = (slice * 3 + row) * 8;
+            let destination_start = slice * 48 + row * 16;
+            assert_eq!(
+                destination[destination_start..destination_start + 8],
+                source[source_start..source_start + 8],
+                "slice {slice}, row {row}"
+            );
+            // The padding is left untouched, except after the very last row, which has none
+            if destination_start + 16 <= destination.len() {
+                assert!(
+                    destination[destination_start + 8..destination_start + 16]
+                        .iter()
+                        .all(|&byte| byte == 0),
+                    "padding of slice {slice}, row {row}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn copy_to_footprint_starts_at_the_offset() {
+    let footprint = footprint(1, 1, 2, 8, 4);
+    let mut destination = vec![0; footprint.total_bytes as usize];
+    copy_to_footprint(&mut destination, &[1, 2, 3, 4, 5, 6, 7, 8], &footprint).unwrap();
+
+    assert_eq!(
+        destination,
+        [0, 0, 0, 0, 1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8]
+    );
+}
+
+#[test]
+fn copy_to_footprint_rejects_short_buffers() {
+    let footprint = footprint(2, 2, 2, 16, 0);
+    let mut destination = vec![0; footprint.total_bytes as usize];
+    // One slice is missing
+    assert!(copy_to_footprint(&mut destination, &[0; 16], &footprint).is_err());
+
+    let mut destination = vec![0; footprint.total_bytes as usize - 1];
+    assert!(copy_to_footprint(&mut destination, &[0; 32], &footprint).is_err());
+}
+
+#[test]
+fn slice_render_target_view_checks_mip_and_depth() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    let desc = TextureDesc {
+        dimension: TextureDimension::Texture3D(8),
+        mip_levels: 3,
+        flags: D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET,
+        ..TextureDesc::new_2d(8, 8, DXGI_FORMAT_R8G8B8A8_UNORM)
+    };
+    let texture = create_texture(&lib, &desc, D3D12_HEAP_TYPE_DEFAULT, None).unwrap();
+    let heap: ID3D12DescriptorHeap = unsafe {
+        lib.device
+            .CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                Type: D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
+                NumDescriptors: 1,
+                Flags: D3D12_DESCRIPTOR_HEAP_FLAG_NONE,
+                NodeMask: 0,
+            })
+    }
+    .unwrap();
+    let handle = unsafe { heap.GetCPUDescriptorHandleForHeapStart() };
+    let view =
+        |slice, mip| create_slice_render_target_view(&lib, &texture, &desc, slice, mip, handle);
+
+    assert!(view(7, 0).is_ok());
+    assert!(view(8, 0).is_err());
+    // The depth halves with each mip
+    assert!(view(3, 1).is_ok());
+    assert!(view(4, 1).is_err());
+    assert!(view(1, 2).is_ok());
+    assert!(view(2, 2).is_err());
+    assert!(view(0, 3).is_err());
+}
@@ -0,0 +1,194 @@
+mod common;
+
+use gimslib_rs::vector_constant_buffer::{BufferLocation, VectorConstantBuffer};
+use windows::Win32::Graphics::Direct3D12::*;
+
+use common::create_lib;
+
+/// Reads `len` items back from a mappable buffer
+fn read_back(resource: &ID3D12Resource, len: usize) -> Vec<u32> {
+    unsafe {
+        let mut pointer = std::ptr::null_mut();
+        resource
+            .Map(
+                0,
+                Some(&D3D12_RANGE {
+                    Begin: 0,
+                    End: len * size_of::<u32>(),
+                }),
+                Some(&mut pointer),
+            )
+            .unwrap();
+        let data = std::slice::from_raw_parts(pointer as *const u32, len).to_vec();
+        resource.Unmap(0, Some(&D3D12_RANGE::default()));
+        data
+    }
+}
+
+#[test]
+fn upload_within_capacity() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    let mut buffer = VectorConstantBuffer::<u32>::new(lib, 8, BufferLocation::Cpu, None).unwrap();
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.capacity(), 8);
+
+    let data = [1, 2, 3, 4];
+    let deleted = buffer.upload_deferred_delete(&data).unwrap();
+    assert!(deleted.is_none());
+    assert_eq!(buffer.len(), 4);
+    assert_eq!(buffer.capacity(), 8);
+    assert_eq!(read_back(&buffer, buffer.len()), data);
+}
+
+#[test]
+fn upload_grows_past_capacity() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    let mut buffer = VectorConstantBuffer::<u32>::new(lib, 2, BufferLocation::Cpu, None).unwrap();
+    buffer.upload(&[7, 8]).unwrap();
+    let old_resource: ID3D12Resource = (*buffer).clone();
+
+    let data: Vec<u32> = (0..100).collect();
+    let deleted = buffer.upload_deferred_delete(&data).unwrap();
+    assert_eq!(deleted.as_ref(), Some(&old_resource));
+    assert_eq!(buffer.len(), 100);
+    assert!(buffer.capacity() >= 100);
+    assert_eq!(read_back(&buffer, buffer.len()), data);
+
+    // The previous buffer is still alive and untouched until the caller drops it
+    assert_eq!(read_back(&deleted.unwrap(), 2), [7, 8]);
+
+    // Smaller uploads reuse the grown buffer
+    let deleted = buffer.upload_deferred_delete(&[5]).unwrap();
+    assert!(deleted.is_none());
+    assert_eq!(buffer.len(), 1);
+    assert!(buffer.capacity() >= 100);
+    assert_eq!(read_back(&buffer, 1), [5]);
+}
+
+#[test]
+fn download_returns_current_items() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    let mut buffer = VectorConstantBuffer::<u32>::new(lib, 4, BufferLocation::Cpu, None).unwrap();
+    assert!(buffer.download().unwrap().is_empty());
+
+    buffer.upload(&[1, 2, 3]).unwrap();
+    assert_eq!(buffer.download().unwrap(), [1, 2, 3]);
+
+    // Items past the current length are not returned
+    buffer.upload(&[9]).unwrap();
+    assert_eq!(buffer.download().unwrap(), [9]);
+}
+
+#[test]
+fn upload_at_keeps_surrounding_items() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    let mut buffer = VectorConstantBuffer::<u32>::new(lib, 4, BufferLocation::Cpu, None).unwrap();
+    buffer.upload(&[1, 2, 3, 4]).unwrap();
+
+    buffer.upload_at(1, &[8, 9]).unwrap();
+    assert_eq!(buffer.len(), 4);
+    assert_eq!(buffer.download().unwrap(), [1, 8, 9, 4]);
+
+    // Writing past the capacity grows the buffer and keeps the items before the offset
+    buffer.upload_at(3, &[5, 6, 7]).unwrap();
+    assert_eq!(buffer.len(), 6);
+    assert!(buffer.capacity() >= 6);
+    assert_eq!(buffer.download().unwrap(), [1, 8, 9, 5, 6, 7]);
+
+    assert!(buffer.upload_at(7, &[0]).is_err());
+}
+
+#[test]
+fn shrink_to_fit_keeps_items() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    let mut buffer = VectorConstantBuffer::<u32>::new(lib, 64, BufferLocation::Cpu, None).unwrap();
+    buffer.upload(&[1, 2, 3]).unwrap();
+
+    let deleted = buffer.shrink_to_fit().unwrap();
+    assert!(deleted.is_some());
+    assert_eq!(buffer.capacity(), 3);
+    assert_eq!(buffer.download().unwrap(), [1, 2, 3]);
+    assert!(buffer.shrink_to_fit().unwrap().is_none());
+
+    buffer.clear();
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.capacity(), 3);
+}
+
+#[test]
+fn constant_buffer_view_is_aligned() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    let mut buffer = VectorConstantBuffer::<u32>::new(lib, 4, BufferLocation::Cpu, None).unwrap();
+    buffer.upload(&[1, 2, 3]).unwrap();
+
+    let desc = buffer.constant_buffer_view_desc();
+    assert_eq!(desc.BufferLocation, unsafe {
+        buffer.GetGPUVirtualAddress()
+    });
+    assert_eq!(desc.SizeInBytes, 256);
+    assert!(unsafe { buffer.GetDesc() }.Width >= 256);
+}
+
+#[test]
+fn default_location_uploads_through_staging() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    let mut buffer =
+        VectorConstantBuffer::<u32>::new(lib, 2, BufferLocation::GpuDefault, None).unwrap();
+    buffer.upload(&[1, 2]).unwrap();
+    assert_eq!(buffer.download().unwrap(), [1, 2]);
+
+    // Growing copies the existing items on the GPU
+    buffer.upload_at(2, &[3, 4, 5]).unwrap();
+    assert_eq!(buffer.download().unwrap(), [1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn unordered_access_view_spans_items() {
+    let Some(lib) = create_lib() else {
+        return;
+    };
+    assert!(
+        VectorConstantBuffer::<[f32; 4]>::with_flags(
+            lib.clone(),
+            4,
+            BufferLocation::Cpu,
+            D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
+            None,
+        )
+        .is_err()
+    );
+
+    let mut buffer = VectorConstantBuffer::<[f32; 4]>::with_flags(
+        lib,
+        4,
+        BufferLocation::GpuDefault,
+        D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS,
+        None,
+    )
+    .unwrap();
+    buffer.upload(&[[0.0; 4]; 3]).unwrap();
+    assert!(
+        unsafe { buffer.GetDesc() }
+            .Flags
+            .contains(D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS)
+    );
+
+    let desc = buffer.unordered_access_view_desc();
+    let view = unsafe { desc.Anonymous.Buffer };
+    assert_eq!(view.NumElements, 3);
+    assert_eq!(view.StructureByteStride, 16);
+}